@@ -0,0 +1,12 @@
+-- Default admin account for fresh installs (username: admin, password: 5555)
+-- Password hash for "5555" using SHA-256
+INSERT OR IGNORE INTO profil (id, nama_pengguna, email, nama_lengkap, password_hash, role, aktif_status)
+VALUES (
+  '79622d5d-e798-41b6-9d8a-35761f353fee',
+  'admin',
+  'cs@gemiprint.com',
+  'GEMIPRINT',
+  'c1f330d0aff31c1c87403f1e4347bcc21aff7c179908723535f2b31723702525',
+  'admin',
+  1
+);
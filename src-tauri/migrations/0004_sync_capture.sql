@@ -0,0 +1,6 @@
+-- Flag table checked by the sync capture triggers.
+-- While it holds a row, writes are not copied into sync_queue (used when
+-- applying changes that came from the cloud).
+CREATE TABLE sync_capture_pause (
+  id INTEGER PRIMARY KEY CHECK (id = 1)
+);
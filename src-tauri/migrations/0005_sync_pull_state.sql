@@ -0,0 +1,9 @@
+-- Per-table high-water mark for pulling changes from Supabase.
+-- cursor_value/cursor_id are the (timestamp, id) of the last row applied.
+CREATE TABLE sync_pull_state (
+  table_name TEXT PRIMARY KEY,
+  cursor_column TEXT NOT NULL,
+  cursor_value TEXT,
+  cursor_id TEXT,
+  last_pulled_at TEXT
+);
@@ -0,0 +1,26 @@
+-- Remote sync_version the queued change was based on (NULL = push unconditionally)
+ALTER TABLE sync_queue ADD COLUMN base_version INTEGER;
+
+-- Both sides of every conflict detected while pushing
+CREATE TABLE sync_conflicts (
+  id TEXT PRIMARY KEY,
+  table_name TEXT NOT NULL,
+  record_id TEXT NOT NULL,
+  operation TEXT NOT NULL,
+  local_data TEXT,
+  remote_data TEXT,
+  remote_version INTEGER,
+  policy TEXT NOT NULL,
+  status TEXT NOT NULL DEFAULT 'open' CHECK(status IN ('open', 'resolved')),
+  resolution TEXT CHECK(resolution IN ('local', 'remote', 'merged')),
+  detected_at TEXT NOT NULL,
+  resolved_at TEXT
+);
+
+CREATE INDEX idx_sync_conflicts_status ON sync_conflicts(status, detected_at);
+
+-- Per-table override of the default conflict policy
+CREATE TABLE sync_conflict_policy (
+  table_name TEXT PRIMARY KEY,
+  policy TEXT NOT NULL CHECK(policy IN ('last_writer_wins', 'local_wins', 'remote_wins', 'merge', 'manual'))
+);
@@ -0,0 +1,10 @@
+-- Retry bookkeeping for sync_queue
+ALTER TABLE sync_queue ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE sync_queue ADD COLUMN last_error TEXT;
+ALTER TABLE sync_queue ADD COLUMN next_attempt_at TEXT;
+
+-- Older builds marked failures as 'failed' for good and stored the error in
+-- synced_at. Move the error where it belongs and give those entries another try.
+UPDATE sync_queue
+SET last_error = synced_at, synced_at = NULL, status = 'pending', attempts = 1
+WHERE status = 'failed';
@@ -0,0 +1,6 @@
+-- Key/value settings owned by the Rust side (sync schedule, ...)
+CREATE TABLE app_settings (
+  key TEXT PRIMARY KEY,
+  value TEXT NOT NULL,
+  updated_at TEXT NOT NULL
+);
@@ -0,0 +1,4 @@
+-- When a queue entry last failed, so the sync dashboard can list recent errors
+ALTER TABLE sync_queue ADD COLUMN last_attempt_at TEXT;
+
+CREATE INDEX idx_sync_queue_table_status ON sync_queue(table_name, operation, status);
@@ -0,0 +1,25 @@
+-- Stock ledger: one row per change of barang.jumlah_stok, quantities in satuan_dasar
+CREATE TABLE mutasi_stok (
+  id TEXT PRIMARY KEY,
+  barang_id TEXT NOT NULL,
+  tanggal TEXT NOT NULL,
+  jenis TEXT NOT NULL CHECK(jenis IN ('SALDO_AWAL', 'PENJUALAN', 'PEMBELIAN', 'RUSAK_PRODUKSI', 'PENYESUAIAN', 'STOK_OPNAME')),
+  jumlah REAL NOT NULL,
+  stok_sebelum REAL NOT NULL,
+  stok_sesudah REAL NOT NULL,
+  satuan TEXT,
+  referensi_tabel TEXT,
+  referensi_id TEXT,
+  catatan TEXT,
+  dibuat_oleh TEXT,
+  dibuat_pada TEXT DEFAULT (datetime('now')),
+  sync_status TEXT DEFAULT 'pending' CHECK(sync_status IN ('pending', 'synced', 'conflict')),
+  last_synced_at TEXT,
+  sync_version INTEGER DEFAULT 1,
+  FOREIGN KEY (barang_id) REFERENCES barang(id) ON DELETE CASCADE,
+  FOREIGN KEY (dibuat_oleh) REFERENCES profil(id)
+);
+
+CREATE INDEX idx_mutasi_stok_barang ON mutasi_stok(barang_id, tanggal);
+CREATE INDEX idx_mutasi_stok_referensi ON mutasi_stok(referensi_tabel, referensi_id);
+CREATE INDEX idx_mutasi_stok_sync_status ON mutasi_stok(sync_status);
@@ -0,0 +1,46 @@
+-- Stock opname (physical count) sessions and their counted lines.
+-- Quantities are in satuan_dasar; harga_beli is per satuan_dasar at snapshot time.
+CREATE TABLE stok_opname (
+  id TEXT PRIMARY KEY,
+  nomor_opname TEXT UNIQUE NOT NULL,
+  tanggal TEXT NOT NULL,
+  status TEXT DEFAULT 'DRAFT' CHECK(status IN ('DRAFT', 'DIPOSTING', 'DIBATALKAN')),
+  kategori_ids TEXT,
+  catatan TEXT,
+  dibuat_oleh TEXT,
+  diposting_pada TEXT,
+  dibuat_pada TEXT DEFAULT (datetime('now')),
+  diperbarui_pada TEXT DEFAULT (datetime('now')),
+  sync_status TEXT DEFAULT 'pending' CHECK(sync_status IN ('pending', 'synced', 'conflict')),
+  last_synced_at TEXT,
+  sync_version INTEGER DEFAULT 1,
+  FOREIGN KEY (dibuat_oleh) REFERENCES profil(id)
+);
+
+CREATE INDEX idx_stok_opname_status ON stok_opname(status);
+CREATE INDEX idx_stok_opname_sync_status ON stok_opname(sync_status);
+
+CREATE TABLE item_stok_opname (
+  id TEXT PRIMARY KEY,
+  stok_opname_id TEXT NOT NULL,
+  barang_id TEXT NOT NULL,
+  stok_sistem REAL NOT NULL,
+  jumlah_hitung REAL,
+  jumlah_input REAL,
+  satuan_input TEXT,
+  faktor_konversi REAL,
+  harga_beli REAL DEFAULT 0,
+  catatan TEXT,
+  dihitung_pada TEXT,
+  dibuat_pada TEXT DEFAULT (datetime('now')),
+  diperbarui_pada TEXT DEFAULT (datetime('now')),
+  sync_status TEXT DEFAULT 'pending' CHECK(sync_status IN ('pending', 'synced', 'conflict')),
+  last_synced_at TEXT,
+  sync_version INTEGER DEFAULT 1,
+  UNIQUE (stok_opname_id, barang_id),
+  FOREIGN KEY (stok_opname_id) REFERENCES stok_opname(id) ON DELETE CASCADE,
+  FOREIGN KEY (barang_id) REFERENCES barang(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_item_stok_opname_opname ON item_stok_opname(stok_opname_id);
+CREATE INDEX idx_item_stok_opname_sync_status ON item_stok_opname(sync_status);
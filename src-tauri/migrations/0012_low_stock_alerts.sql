@@ -0,0 +1,8 @@
+-- Barang currently below their minimum that a notification was already sent for.
+-- A row is removed once the stock recovers, so the next crossing alerts again.
+CREATE TABLE low_stock_alerts (
+  barang_id TEXT PRIMARY KEY,
+  jumlah_stok REAL NOT NULL,
+  level_stok_minimum REAL NOT NULL,
+  alerted_at TEXT NOT NULL
+);
@@ -0,0 +1,12 @@
+-- Triggers only found in databases copied from the old bundled template;
+-- sqlite-schema.sql never had them. The timestamp triggers rewrite
+-- diperbarui_pada in SQLite's own format and make sync capture queue every
+-- edit twice. The payment trigger sets jumlah_terbayar to the pelunasan sum
+-- alone, dropping the down payment taken at the sale; the app updates the
+-- receivable itself.
+DROP TRIGGER IF EXISTS update_kategori_bahan_timestamp;
+DROP TRIGGER IF EXISTS update_subkategori_bahan_timestamp;
+DROP TRIGGER IF EXISTS update_satuan_bahan_timestamp;
+DROP TRIGGER IF EXISTS update_spesifikasi_cepat_bahan_timestamp;
+DROP TRIGGER IF EXISTS update_piutang_penjualan_timestamp;
+DROP TRIGGER IF EXISTS update_piutang_penjualan_after_payment;
@@ -0,0 +1,245 @@
+use crate::migrations;
+use crate::schema::quote_ident;
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// File extension of encrypted backup archives
+pub const EXTENSION: &str = "gpbak";
+
+/// Archive layout: `MAGIC` | manifest length (u32 LE) | manifest JSON |
+/// ciphertext of the gzip-compressed database. Everything before the
+/// ciphertext is authenticated with it, so the manifest cannot be altered.
+const MAGIC: &[u8; 8] = b"GPBACKUP";
+const FORMAT_VERSION: u32 = 1;
+
+const MIN_PASSPHRASE_LEN: usize = 8;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+/// Refuse archives asking for more KDF memory than this (KiB)
+const MAX_KDF_MEMORY: u32 = 1024 * 1024;
+const MAX_MANIFEST_LEN: usize = 1024 * 1024;
+
+/// Readable without the passphrase: it describes the backup but holds no
+/// data beyond row counts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub format_version: u32,
+    pub app_version: String,
+    pub schema_version: i64,
+    pub created_at: String,
+    pub row_counts: BTreeMap<String, i64>,
+    /// Size of the database file inside, uncompressed
+    pub database_size: u64,
+    /// SHA-256 (hex) of the database file inside
+    pub checksum: String,
+    pub compression: String,
+    pub cipher: String,
+    pub kdf: KdfParams,
+    /// Base64
+    pub nonce: String,
+}
+
+/// Argon2id settings the key was derived with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KdfParams {
+    pub algorithm: String,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    /// Base64
+    pub salt: String,
+}
+
+/// Whether the file starts like an archive (otherwise it is taken to be a
+/// plain SQLite backup)
+pub fn is_archive(path: &Path) -> Result<bool, String> {
+    let mut file = fs::File::open(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+    let mut magic = [0u8; 8];
+    match file.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == MAGIC),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Write the database file at `database_path` (a backup copy, not the live
+/// file) to `path` as an encrypted archive
+pub fn write(database_path: &Path, path: &Path, passphrase: &str) -> Result<Manifest, String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(format!("Passphrase must be at least {} characters", MIN_PASSPHRASE_LEN));
+    }
+
+    let (row_counts, schema_version) = {
+        let conn = Connection::open_with_flags(database_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| e.to_string())?;
+        (row_counts(&conn)?, migrations::current_version(&conn)?)
+    };
+    let database = fs::read(database_path).map_err(|e| e.to_string())?;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&database).map_err(|e| e.to_string())?;
+    let compressed = encoder.finish().map_err(|e| e.to_string())?;
+
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let params = Params::default();
+    let kdf = KdfParams {
+        algorithm: "argon2id".to_string(),
+        memory_kib: params.m_cost(),
+        iterations: params.t_cost(),
+        parallelism: params.p_cost(),
+        salt: BASE64.encode(salt),
+    };
+
+    let manifest = Manifest {
+        format_version: FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        schema_version,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        row_counts,
+        database_size: database.len() as u64,
+        checksum: hex(&Sha256::digest(&database)),
+        compression: "gzip".to_string(),
+        cipher: "chacha20poly1305".to_string(),
+        kdf,
+        nonce: BASE64.encode(nonce),
+    };
+
+    let header = header_bytes(&manifest)?;
+    let ciphertext = ChaCha20Poly1305::new(&derive_key(passphrase, &manifest.kdf)?)
+        .encrypt(&nonce, Payload { msg: &compressed, aad: &header })
+        .map_err(|_| "Encryption failed".to_string())?;
+
+    let partial = path.with_extension(format!("{}.partial", EXTENSION));
+    fs::write(&partial, [header, ciphertext].concat())
+        .and_then(|_| fs::rename(&partial, path))
+        .map_err(|e| {
+            fs::remove_file(&partial).ok();
+            format!("Cannot write {}: {}", path.display(), e)
+        })?;
+
+    Ok(manifest)
+}
+
+/// The manifest of an archive, without decrypting it
+pub fn read_manifest(path: &Path) -> Result<Manifest, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    parse(&bytes).map(|(manifest, _, _)| manifest)
+}
+
+/// Decrypt an archive and write the database inside to `out`, after
+/// checking it against the manifest's checksum
+pub fn extract(path: &Path, passphrase: &str, out: &Path) -> Result<Manifest, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    let (manifest, header, ciphertext) = parse(&bytes)?;
+
+    let nonce_bytes = BASE64.decode(&manifest.nonce).map_err(|e| e.to_string())?;
+    if nonce_bytes.len() != NONCE_LEN {
+        return Err("Archive is damaged: bad nonce".to_string());
+    }
+    let compressed = ChaCha20Poly1305::new(&derive_key(passphrase, &manifest.kdf)?)
+        .decrypt(Nonce::from_slice(&nonce_bytes), Payload { msg: ciphertext, aad: header })
+        .map_err(|_| "Wrong passphrase, or the archive is damaged".to_string())?;
+
+    let mut database = Vec::with_capacity(manifest.database_size as usize);
+    GzDecoder::new(compressed.as_slice())
+        .read_to_end(&mut database)
+        .map_err(|e| format!("Archive is damaged: {}", e))?;
+    if hex(&Sha256::digest(&database)) != manifest.checksum {
+        return Err("Archive is damaged: checksum mismatch".to_string());
+    }
+
+    fs::write(out, &database).map_err(|e| e.to_string())?;
+    Ok(manifest)
+}
+
+/// Split an archive into manifest, authenticated header and ciphertext
+fn parse(bytes: &[u8]) -> Result<(Manifest, &[u8], &[u8]), String> {
+    if bytes.len() < MAGIC.len() + 4 || &bytes[..MAGIC.len()] != MAGIC {
+        return Err("Not a GemiPrint backup archive".to_string());
+    }
+
+    let len_bytes: [u8; 4] = bytes[MAGIC.len()..MAGIC.len() + 4].try_into().unwrap_or_default();
+    let manifest_len = u32::from_le_bytes(len_bytes) as usize;
+    let header_len = MAGIC.len() + 4 + manifest_len;
+    if manifest_len > MAX_MANIFEST_LEN || bytes.len() < header_len {
+        return Err("Archive is damaged: truncated manifest".to_string());
+    }
+
+    let manifest: Manifest = serde_json::from_slice(&bytes[MAGIC.len() + 4..header_len])
+        .map_err(|e| format!("Archive is damaged: {}", e))?;
+    if manifest.format_version > FORMAT_VERSION {
+        return Err(format!(
+            "Archive format {} is newer than this application supports. Please update GemiPrint.",
+            manifest.format_version
+        ));
+    }
+
+    Ok((manifest, &bytes[..header_len], &bytes[header_len..]))
+}
+
+fn header_bytes(manifest: &Manifest) -> Result<Vec<u8>, String> {
+    let json = serde_json::to_vec(manifest).map_err(|e| e.to_string())?;
+    let mut header = Vec::with_capacity(MAGIC.len() + 4 + json.len());
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    header.extend_from_slice(&json);
+    Ok(header)
+}
+
+fn derive_key(passphrase: &str, kdf: &KdfParams) -> Result<Key, String> {
+    if kdf.algorithm != "argon2id" {
+        return Err(format!("Unsupported key derivation: {}", kdf.algorithm));
+    }
+    if kdf.memory_kib > MAX_KDF_MEMORY {
+        return Err("Archive asks for too much memory to open".to_string());
+    }
+
+    let salt = BASE64.decode(&kdf.salt).map_err(|e| e.to_string())?;
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|e| e.to_string())?;
+    let mut key = Key::default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|e| e.to_string())?;
+
+    Ok(key)
+}
+
+/// Rows per table, for every table in the database
+fn row_counts(conn: &Connection) -> Result<BTreeMap<String, i64>, String> {
+    let tables: Vec<String> = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'")
+        .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+        .map_err(|e| e.to_string())?;
+
+    tables
+        .into_iter()
+        .map(|table| {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {}", quote_ident(&table)), [], |row| row.get(0))
+                .map(|count| (table, count))
+                .map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
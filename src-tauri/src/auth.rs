@@ -0,0 +1,253 @@
+use crate::config;
+use crate::secrets;
+use crate::settings;
+use crate::sync::SupabaseConfig;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Mutex;
+
+const MODE_KEY: &str = "sync.auth_mode";
+const SESSION_KEY: &str = "sync.auth_session";
+
+/// Refresh the user token when it expires within this many seconds
+const REFRESH_MARGIN_SECS: i64 = 120;
+
+pub const NOT_CONFIGURED: &str = "Supabase not configured (set the URL and anon key in the sync settings)";
+
+const NO_SERVICE_KEY: &str = "Service mode needs a service role key in the sync settings";
+
+/// Which credential the sync engine presents to Supabase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMode {
+    /// The public anon key only (requires open RLS policies)
+    Anon,
+    /// A signed-in user's JWT, refreshed automatically
+    User,
+    /// The service-role key, for a headless sync agent
+    Service,
+}
+
+impl AuthMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            AuthMode::Anon => "anon",
+            AuthMode::User => "user",
+            AuthMode::Service => "service",
+        }
+    }
+}
+
+/// Access/refresh token pair from GoTrue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Unix timestamp (seconds)
+    pub expires_at: i64,
+    pub user_id: Option<String>,
+    pub email: Option<String>,
+}
+
+/// What the UI needs to show the sync account
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthStatus {
+    pub mode: AuthMode,
+    pub signed_in: bool,
+    pub email: Option<String>,
+    pub user_id: Option<String>,
+    pub expires_at: Option<String>,
+    pub service_key_available: bool,
+}
+
+/// Supabase URL and anon key
+fn endpoint(conn: &Connection) -> Result<(String, String), String> {
+    config::endpoint(conn)?.ok_or_else(|| NOT_CONFIGURED.to_string())
+}
+
+pub fn load_mode(conn: &Connection) -> Result<AuthMode, String> {
+    Ok(settings::get(conn, MODE_KEY)?
+        .and_then(|m| serde_json::from_value(Value::String(m)).ok())
+        .unwrap_or(AuthMode::Anon))
+}
+
+pub fn set_mode(conn: &Connection, mode: AuthMode) -> Result<(), String> {
+    if mode == AuthMode::Service && config::service_key(conn)?.is_none() {
+        return Err(NO_SERVICE_KEY.to_string());
+    }
+    settings::set(conn, MODE_KEY, mode.as_str())
+}
+
+/// The stored session; one that can no longer be decrypted counts as signed out
+fn load_session(conn: &Connection) -> Result<Option<Session>, String> {
+    Ok(secrets::get(conn, SESSION_KEY)
+        .ok()
+        .flatten()
+        .and_then(|s| serde_json::from_str(&s).ok()))
+}
+
+fn save_session(conn: &Connection, session: &Session) -> Result<(), String> {
+    let json = serde_json::to_string(session).map_err(|e| e.to_string())?;
+    secrets::set(conn, SESSION_KEY, &json)
+}
+
+fn clear_session(conn: &Connection) -> Result<(), String> {
+    settings::remove(conn, SESSION_KEY)
+}
+
+pub fn status(conn: &Connection) -> Result<AuthStatus, String> {
+    let session = load_session(conn)?;
+    Ok(AuthStatus {
+        mode: load_mode(conn)?,
+        signed_in: session.is_some(),
+        email: session.as_ref().and_then(|s| s.email.clone()),
+        user_id: session.as_ref().and_then(|s| s.user_id.clone()),
+        expires_at: session
+            .as_ref()
+            .and_then(|s| chrono::DateTime::from_timestamp(s.expires_at, 0))
+            .map(|t| t.to_rfc3339()),
+        service_key_available: config::service_key(conn)?.is_some(),
+    })
+}
+
+/// Sign in with email and password and switch sync to user mode
+pub async fn sign_in(
+    db: &Mutex<Option<Connection>>,
+    email: &str,
+    password: &str,
+) -> Result<AuthStatus, String> {
+    let (url, anon_key) = {
+        let db_guard = db.lock().map_err(|e| e.to_string())?;
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        endpoint(conn)?
+    };
+    let body = serde_json::json!({ "email": email, "password": password });
+    let session = token_request(&url, &anon_key, "password", &body).await?;
+
+    let db_guard = db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    save_session(conn, &session)?;
+    set_mode(conn, AuthMode::User)?;
+    status(conn)
+}
+
+/// Forget the stored session (and revoke it on the server, best effort)
+pub async fn sign_out(db: &Mutex<Option<Connection>>) -> Result<AuthStatus, String> {
+    let (session, endpoint) = {
+        let db_guard = db.lock().map_err(|e| e.to_string())?;
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        (load_session(conn)?, endpoint(conn))
+    };
+
+    if let (Some(session), Ok((url, anon_key))) = (session, endpoint) {
+        reqwest::Client::new()
+            .post(format!("{}/auth/v1/logout", url))
+            .header("apikey", &anon_key)
+            .header("Authorization", format!("Bearer {}", session.access_token))
+            .send()
+            .await
+            .ok();
+    }
+
+    let db_guard = db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    clear_session(conn)?;
+    status(conn)
+}
+
+/// Credentials for the next sync requests, according to the stored mode.
+/// In user mode the access token is refreshed first when it is about to expire.
+pub async fn resolve_config(db: &Mutex<Option<Connection>>) -> Result<SupabaseConfig, String> {
+    let (mode, session, endpoint, service_key, rest_url) = {
+        let db_guard = db.lock().map_err(|e| e.to_string())?;
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        (
+            load_mode(conn)?,
+            load_session(conn)?,
+            endpoint(conn)?,
+            config::service_key(conn)?,
+            config::rest_url(conn)?,
+        )
+    };
+    let (url, anon_key) = endpoint;
+
+    let resolved = match mode {
+        AuthMode::Anon => SupabaseConfig::anon(url, anon_key),
+
+        AuthMode::Service => {
+            let key = service_key.ok_or(NO_SERVICE_KEY)?;
+            SupabaseConfig::new(url, key.clone(), key)
+        }
+
+        AuthMode::User => {
+            let mut session = session.ok_or("Not signed in to Supabase")?;
+
+            if session.expires_at - chrono::Utc::now().timestamp() < REFRESH_MARGIN_SECS {
+                let body = serde_json::json!({ "refresh_token": session.refresh_token });
+                session = token_request(&url, &anon_key, "refresh_token", &body)
+                    .await
+                    .map_err(|e| format!("Could not refresh the Supabase session: {}", e))?;
+
+                let db_guard = db.lock().map_err(|e| e.to_string())?;
+                let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+                save_session(conn, &session)?;
+            }
+
+            SupabaseConfig::new(url, anon_key, session.access_token)
+        }
+    };
+
+    Ok(resolved.with_rest_url(rest_url))
+}
+
+/// `POST /auth/v1/token` for the password and refresh_token grants
+async fn token_request(
+    url: &str,
+    anon_key: &str,
+    grant_type: &str,
+    body: &Value,
+) -> Result<Session, String> {
+    let response = reqwest::Client::new()
+        .post(format!("{}/auth/v1/token", url))
+        .query(&[("grant_type", grant_type)])
+        .header("apikey", anon_key)
+        .header("Content-Type", "application/json")
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| format!("Could not reach Supabase: {}", e))?;
+
+    if !response.status().is_success() {
+        let error: Value = response.json().await.unwrap_or(Value::Null);
+        let message = ["error_description", "msg", "message", "error"]
+            .iter()
+            .find_map(|k| error.get(*k).and_then(|v| v.as_str()))
+            .unwrap_or("Authentication failed")
+            .to_string();
+        return Err(message);
+    }
+
+    let token: Value = response.json().await.map_err(|e| e.to_string())?;
+    let field = |name: &str| token.get(name).and_then(|v| v.as_str()).map(str::to_string);
+
+    let expires_at = token
+        .get("expires_at")
+        .and_then(|v| v.as_i64())
+        .or_else(|| {
+            token
+                .get("expires_in")
+                .and_then(|v| v.as_i64())
+                .map(|secs| chrono::Utc::now().timestamp() + secs)
+        })
+        .unwrap_or_else(|| chrono::Utc::now().timestamp() + 3600);
+
+    Ok(Session {
+        access_token: field("access_token").ok_or("No access token in response")?,
+        refresh_token: field("refresh_token").ok_or("No refresh token in response")?,
+        expires_at,
+        user_id: token.pointer("/user/id").and_then(|v| v.as_str()).map(str::to_string),
+        email: token.pointer("/user/email").and_then(|v| v.as_str()).map(str::to_string),
+    })
+}
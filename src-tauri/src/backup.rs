@@ -0,0 +1,781 @@
+use crate::archive;
+use crate::migrations;
+use crate::scheduler::SyncScheduler;
+use crate::settings;
+use crate::AppState;
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::Notify;
+
+const ENABLED_KEY: &str = "backup.auto_enabled";
+const INTERVAL_KEY: &str = "backup.interval_ms";
+const DIRECTORY_KEY: &str = "backup.directory";
+const KEEP_HOURLY_KEY: &str = "backup.keep_hourly";
+const KEEP_DAILY_KEY: &str = "backup.keep_daily";
+const KEEP_WEEKLY_KEY: &str = "backup.keep_weekly";
+const LAST_BACKUP_KEY: &str = "backup.last_backup_at";
+
+/// Same bounds as the old JavaScript auto-backup (`backup-service.ts`)
+pub const MIN_INTERVAL_MS: u64 = 30_000;
+pub const MAX_INTERVAL_MS: u64 = 86_400_000;
+pub const DEFAULT_INTERVAL_MS: u64 = 600_000;
+
+const DEFAULT_KEEP_HOURLY: usize = 24;
+const DEFAULT_KEEP_DAILY: usize = 7;
+const DEFAULT_KEEP_WEEKLY: usize = 4;
+const MAX_KEEP: usize = 1000;
+
+const FILE_PREFIX: &str = "gemiprint-";
+/// Snapshots taken by `restore`, kept apart from the rotation windows
+const SNAPSHOT_PREFIX: &str = "before-restore-";
+const KEEP_SNAPSHOTS: usize = 5;
+const FILE_SUFFIX: &str = ".db";
+const FILE_TIMESTAMP: &str = "%Y%m%d-%H%M%S";
+
+/// Pages copied per backup step; the connection lock is held throughout,
+/// so there is no point pausing between steps
+const PAGES_PER_STEP: std::os::raw::c_int = 1024;
+
+/// Give the app time to start before the first backup
+const STARTUP_DELAY: Duration = Duration::from_secs(30);
+
+/// Shared state of the background backup task, managed by Tauri
+pub struct BackupService {
+    backing_up: AtomicBool,
+    initialized: AtomicBool,
+    wake: Notify,
+    /// Backups go here unless another directory was chosen
+    default_dir: PathBuf,
+    last_attempt: Mutex<Option<BackupAttempt>>,
+    next_backup_at: Mutex<Option<DateTime<Utc>>>,
+}
+
+#[derive(Debug, Clone)]
+struct BackupAttempt {
+    at: DateTime<Utc>,
+    error: Option<String>,
+}
+
+/// Marks a backup as in progress until dropped
+pub struct BackupGuard<'a>(&'a AtomicBool);
+
+impl Drop for BackupGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+impl BackupService {
+    pub fn new(default_dir: PathBuf) -> Self {
+        Self {
+            backing_up: AtomicBool::new(false),
+            initialized: AtomicBool::new(false),
+            wake: Notify::new(),
+            default_dir,
+            last_attempt: Mutex::new(None),
+            next_backup_at: Mutex::new(None),
+        }
+    }
+
+    /// Claim the right to write a backup; `None` while another one is running
+    pub fn try_begin(&self) -> Option<BackupGuard<'_>> {
+        self.backing_up
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()
+            .map(|_| BackupGuard(&self.backing_up))
+    }
+
+    /// Re-read the schedule now instead of at the end of the current wait
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+}
+
+/// Where backups go and how many are kept
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupConfig {
+    /// Empty means the default directory inside the app data folder
+    pub directory: String,
+    /// Newest backup of each of the last N hours
+    pub keep_hourly: usize,
+    /// Newest backup of each of the last N days
+    pub keep_daily: usize,
+    /// Newest backup of each of the last N weeks
+    pub keep_weekly: usize,
+}
+
+/// Latest backup file, shaped like `BackupInfo` in `backup-service.ts`
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub exists: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(rename = "sizeMB", skip_serializing_if = "Option::is_none")]
+    pub size_mb: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_modified_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Auto-backup schedule plus what the background task is doing, shaped
+/// like `BackupStatus` in `backup-service.ts`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupStatus {
+    pub is_running: bool,
+    pub is_initialized: bool,
+    /// Milliseconds
+    pub current_interval: u64,
+    pub current_interval_minutes: String,
+    pub last_backup_time: Option<String>,
+    pub next_backup_time: Option<String>,
+    pub is_backing_up: bool,
+    pub directory: String,
+    /// Error of the last attempt, if it failed
+    pub last_error: Option<String>,
+}
+
+struct Schedule {
+    enabled: bool,
+    interval_ms: u64,
+    last_backup_at: Option<DateTime<Utc>>,
+}
+
+fn load_schedule(conn: &Connection) -> Result<Schedule, String> {
+    Ok(Schedule {
+        enabled: settings::get_or(conn, ENABLED_KEY, true)?,
+        interval_ms: settings::get_or(conn, INTERVAL_KEY, DEFAULT_INTERVAL_MS)?
+            .clamp(MIN_INTERVAL_MS, MAX_INTERVAL_MS),
+        last_backup_at: settings::get(conn, LAST_BACKUP_KEY)?
+            .and_then(|value| DateTime::parse_from_rfc3339(&value).ok())
+            .map(|time| time.with_timezone(&Utc)),
+    })
+}
+
+pub fn load_config(conn: &Connection) -> Result<BackupConfig, String> {
+    Ok(BackupConfig {
+        directory: settings::get(conn, DIRECTORY_KEY)?.unwrap_or_default(),
+        keep_hourly: settings::get_or(conn, KEEP_HOURLY_KEY, DEFAULT_KEEP_HOURLY)?.min(MAX_KEEP),
+        keep_daily: settings::get_or(conn, KEEP_DAILY_KEY, DEFAULT_KEEP_DAILY)?.min(MAX_KEEP),
+        keep_weekly: settings::get_or(conn, KEEP_WEEKLY_KEY, DEFAULT_KEEP_WEEKLY)?.min(MAX_KEEP),
+    })
+}
+
+/// Validate and store the backup directory and rotation counts. The
+/// directory is created right away so a bad choice fails here, not at the
+/// next scheduled backup.
+pub fn save_config(conn: &Connection, config: &BackupConfig) -> Result<(), String> {
+    for (name, keep) in [
+        ("hourly", config.keep_hourly),
+        ("daily", config.keep_daily),
+        ("weekly", config.keep_weekly),
+    ] {
+        if keep > MAX_KEEP {
+            return Err(format!("Cannot keep more than {} {} backups", MAX_KEEP, name));
+        }
+    }
+
+    let directory = config.directory.trim();
+    if directory.is_empty() {
+        settings::remove(conn, DIRECTORY_KEY)?;
+    } else {
+        let path = Path::new(directory);
+        if !path.is_absolute() {
+            return Err("Backup directory must be an absolute path".to_string());
+        }
+        std::fs::create_dir_all(path)
+            .map_err(|e| format!("Cannot use backup directory {}: {}", directory, e))?;
+        settings::set(conn, DIRECTORY_KEY, directory)?;
+    }
+
+    settings::set(conn, KEEP_HOURLY_KEY, &config.keep_hourly.to_string())?;
+    settings::set(conn, KEEP_DAILY_KEY, &config.keep_daily.to_string())?;
+    settings::set(conn, KEEP_WEEKLY_KEY, &config.keep_weekly.to_string())
+}
+
+pub fn save_interval(conn: &Connection, interval_ms: u64) -> Result<(), String> {
+    if !(MIN_INTERVAL_MS..=MAX_INTERVAL_MS).contains(&interval_ms) {
+        return Err(format!(
+            "Interval must be between {}ms and {}ms",
+            MIN_INTERVAL_MS, MAX_INTERVAL_MS
+        ));
+    }
+
+    settings::set(conn, INTERVAL_KEY, &interval_ms.to_string())
+}
+
+/// Turn auto-backup on or off; starting also stores `interval_ms` (clamped
+/// to the allowed range) and backs up right away
+pub async fn set_auto_backup(
+    app: &AppHandle,
+    enabled: bool,
+    interval_ms: Option<u64>,
+) -> Result<BackupStatus, String> {
+    {
+        let state = app.state::<AppState>();
+        let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        if let Some(interval_ms) = interval_ms {
+            save_interval(conn, interval_ms.clamp(MIN_INTERVAL_MS, MAX_INTERVAL_MS))?;
+        }
+        settings::set(conn, ENABLED_KEY, &enabled.to_string())?;
+    }
+
+    // Like the old service, starting backs up right away; a failure shows
+    // up as `lastError` in the status rather than failing the switch
+    if enabled {
+        if let Err(e) = create(app).await {
+            println!("Backup on start failed: {}", e);
+        }
+    }
+    app.state::<BackupService>().wake();
+
+    status(app)
+}
+
+/// Directory backups are written to
+fn backup_dir(service: &BackupService, config: &BackupConfig) -> PathBuf {
+    if config.directory.is_empty() {
+        service.default_dir.clone()
+    } else {
+        PathBuf::from(&config.directory)
+    }
+}
+
+pub fn status(app: &AppHandle) -> Result<BackupStatus, String> {
+    let service = app.state::<BackupService>();
+    let state = app.state::<AppState>();
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+
+    let schedule = load_schedule(conn)?;
+    let config = load_config(conn)?;
+    let initialized = service.initialized.load(Ordering::SeqCst);
+    let last_attempt = service.last_attempt.lock().map_err(|e| e.to_string())?.clone();
+    let next_backup_at = *service.next_backup_at.lock().map_err(|e| e.to_string())?;
+
+    Ok(BackupStatus {
+        is_running: initialized && schedule.enabled,
+        is_initialized: initialized,
+        current_interval: schedule.interval_ms,
+        current_interval_minutes: format!("{:.1}", schedule.interval_ms as f64 / 60_000.0),
+        last_backup_time: schedule.last_backup_at.map(|time| time.to_rfc3339()),
+        next_backup_time: next_backup_at.map(|time| time.to_rfc3339()),
+        is_backing_up: service.backing_up.load(Ordering::SeqCst),
+        directory: backup_dir(&service, &config).to_string_lossy().into_owned(),
+        last_error: last_attempt.and_then(|attempt| attempt.error),
+    })
+}
+
+/// Spawn the background task: one backup per interval while auto-backup
+/// is enabled. Changing the schedule wakes it up early.
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(STARTUP_DELAY).await;
+        let service = app.state::<BackupService>();
+        service.initialized.store(true, Ordering::SeqCst);
+
+        loop {
+            let schedule = {
+                let state = app.state::<AppState>();
+                let db_guard = state.db.lock().ok();
+                db_guard
+                    .as_ref()
+                    .and_then(|g| g.as_ref())
+                    .and_then(|conn| load_schedule(conn).ok())
+            };
+            let wait = schedule
+                .filter(|schedule| schedule.enabled)
+                .map(|schedule| time_until_due(&service, &schedule));
+
+            if let Ok(mut next) = service.next_backup_at.lock() {
+                *next = wait.map(|wait| Utc::now() + wait);
+            }
+
+            match wait {
+                Some(wait) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep(wait) => {
+                            if let Err(e) = create(&app).await {
+                                println!("Automatic backup failed: {}", e);
+                            }
+                        }
+                        _ = service.wake.notified() => {}
+                    }
+                }
+                None => service.wake.notified().await,
+            }
+        }
+    });
+}
+
+/// Time left until the next backup: one interval after the last backup or
+/// failed attempt, whichever is later
+fn time_until_due(service: &BackupService, schedule: &Schedule) -> Duration {
+    let last_attempt = service
+        .last_attempt
+        .lock()
+        .ok()
+        .and_then(|attempt| attempt.as_ref().map(|attempt| attempt.at));
+    let last = schedule.last_backup_at.max(last_attempt);
+
+    last.map(|last| last + chrono::Duration::milliseconds(schedule.interval_ms as i64) - Utc::now())
+        .and_then(|left| left.to_std().ok())
+        .unwrap_or(Duration::ZERO)
+}
+
+/// Back up the database now and rotate old backups. Fails without doing
+/// anything if a backup is already running.
+pub async fn create(app: &AppHandle) -> Result<BackupInfo, String> {
+    let service = app.state::<BackupService>();
+    let _guard = service.try_begin().ok_or("Backup already in progress")?;
+
+    let handle = app.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || write_backup(&handle))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result);
+
+    *service.last_attempt.lock().map_err(|e| e.to_string())? = Some(BackupAttempt {
+        at: Utc::now(),
+        error: result.as_ref().err().cloned(),
+    });
+
+    result
+}
+
+fn write_backup(app: &AppHandle) -> Result<BackupInfo, String> {
+    let service = app.state::<BackupService>();
+    let state = app.state::<AppState>();
+
+    let (dir, config, path) = {
+        let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+
+        let config = load_config(conn)?;
+        let dir = backup_dir(&service, &config);
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Cannot create backup directory {}: {}", dir.display(), e))?;
+
+        let now = Local::now();
+        let path = dir.join(format!("{}{}{}", FILE_PREFIX, now.format(FILE_TIMESTAMP), FILE_SUFFIX));
+        let partial = path.with_extension("db.partial");
+        if let Err(e) = copy_database(conn, &partial) {
+            std::fs::remove_file(&partial).ok();
+            return Err(e);
+        }
+        std::fs::rename(&partial, &path).map_err(|e| e.to_string())?;
+
+        settings::set(conn, LAST_BACKUP_KEY, &now.with_timezone(&Utc).to_rfc3339())?;
+        (dir, config, path)
+    };
+
+    match rotate(&dir, &config) {
+        Ok(0) => {}
+        Ok(removed) => println!("Backup rotation removed {} old backups", removed),
+        Err(e) => println!("Backup rotation failed: {}", e),
+    }
+
+    file_info(&path)
+}
+
+/// Copy the live database with SQLite's online backup API, which reads a
+/// consistent snapshot including pages still in the WAL, then check the copy
+pub fn copy_database(conn: &Connection, path: &Path) -> Result<(), String> {
+    let mut target = Connection::open(path).map_err(|e| e.to_string())?;
+    {
+        let backup = Backup::new(conn, &mut target).map_err(|e| e.to_string())?;
+        backup
+            .run_to_completion(PAGES_PER_STEP, Duration::ZERO, None)
+            .map_err(|e| format!("Backup failed: {}", e))?;
+    }
+
+    // The copy is a standalone file; don't leave it expecting -wal/-shm companions
+    target
+        .pragma_update(None, "journal_mode", "DELETE")
+        .map_err(|e| e.to_string())?;
+
+    let check: String = target
+        .query_row("PRAGMA quick_check", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if check != "ok" {
+        return Err(format!("Backup failed verification: {}", check));
+    }
+
+    Ok(())
+}
+
+/// Backup files in `dir` with the time in their name, newest first
+fn list_backups(dir: &Path) -> Result<Vec<(NaiveDateTime, PathBuf)>, String> {
+    list_files(dir, FILE_PREFIX)
+}
+
+/// Files in `dir` named `<prefix><time>.db`, newest first
+fn list_files(dir: &Path, prefix: &str) -> Result<Vec<(NaiveDateTime, PathBuf)>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups: Vec<(NaiveDateTime, PathBuf)> = std::fs::read_dir(dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let stamp = name.strip_prefix(prefix)?.strip_suffix(FILE_SUFFIX)?;
+            let time = NaiveDateTime::parse_from_str(stamp, FILE_TIMESTAMP).ok()?;
+            Some((time, entry.path()))
+        })
+        .collect();
+    backups.sort_by_key(|(time, _)| std::cmp::Reverse(*time));
+
+    Ok(backups)
+}
+
+/// Delete backups outside the retention windows: the newest backup of each
+/// of the last `keep_hourly` hours, `keep_daily` days and `keep_weekly` ISO
+/// weeks survives, as does the newest backup overall. Returns how many
+/// files were removed.
+fn rotate(dir: &Path, config: &BackupConfig) -> Result<usize, String> {
+    let backups = list_backups(dir)?;
+    let mut keep: HashSet<&Path> = backups.first().map(|(_, path)| path.as_path()).into_iter().collect();
+
+    for (count, bucket) in [
+        (config.keep_hourly, "%Y-%m-%d %H"),
+        (config.keep_daily, "%Y-%m-%d"),
+        (config.keep_weekly, "%G-W%V"),
+    ] {
+        let mut seen = HashSet::new();
+        for (time, path) in &backups {
+            if seen.len() >= count {
+                break;
+            }
+            if seen.insert(time.format(bucket).to_string()) {
+                keep.insert(path.as_path());
+            }
+        }
+    }
+
+    let mut removed = 0;
+    for (_, path) in backups.iter().filter(|(_, path)| !keep.contains(path.as_path())) {
+        std::fs::remove_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        removed += 1;
+    }
+
+    Ok(removed)
+}
+
+/// Delete all but the newest `KEEP_SNAPSHOTS` pre-restore snapshots.
+/// Returns how many files were removed.
+fn prune_snapshots(dir: &Path) -> Result<usize, String> {
+    let snapshots = list_files(dir, SNAPSHOT_PREFIX)?;
+    for (_, path) in snapshots.iter().skip(KEEP_SNAPSHOTS) {
+        std::fs::remove_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+
+    Ok(snapshots.len().saturating_sub(KEEP_SNAPSHOTS))
+}
+
+fn file_info(path: &Path) -> Result<BackupInfo, String> {
+    let metadata = std::fs::metadata(path).map_err(|e| e.to_string())?;
+    let modified: Option<DateTime<Local>> = metadata.modified().ok().map(DateTime::from);
+
+    Ok(BackupInfo {
+        exists: true,
+        path: Some(path.to_string_lossy().into_owned()),
+        size: Some(metadata.len()),
+        size_mb: Some(format!("{:.2}", metadata.len() as f64 / (1024.0 * 1024.0))),
+        last_modified: modified.map(|time| time.with_timezone(&Utc).to_rfc3339()),
+        last_modified_formatted: modified.map(|time| time.format("%d/%m/%Y, %H.%M.%S").to_string()),
+        ..Default::default()
+    })
+}
+
+/// The newest backup in the backup directory
+pub fn latest_info(app: &AppHandle) -> Result<BackupInfo, String> {
+    let service = app.state::<BackupService>();
+    let config = {
+        let state = app.state::<AppState>();
+        let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        load_config(conn)?
+    };
+
+    let latest = list_backups(&backup_dir(&service, &config)).map(|backups| backups.into_iter().next());
+    Ok(match latest {
+        Ok(Some((_, path))) => file_info(&path).unwrap_or_else(|e| BackupInfo {
+            error: Some(e),
+            ..Default::default()
+        }),
+        Ok(None) => BackupInfo {
+            message: Some("No backup found".to_string()),
+            ..Default::default()
+        },
+        Err(e) => BackupInfo {
+            error: Some(e),
+            ..Default::default()
+        },
+    })
+}
+
+/// Every backup in the backup directory, newest first
+pub fn list(app: &AppHandle) -> Result<Vec<BackupInfo>, String> {
+    let service = app.state::<BackupService>();
+    let config = {
+        let state = app.state::<AppState>();
+        let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        load_config(conn)?
+    };
+
+    list_backups(&backup_dir(&service, &config))?
+        .iter()
+        .map(|(_, path)| file_info(path))
+        .collect()
+}
+
+/// Result of `restore_backup`, also the payload of `backup://restored`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreReport {
+    pub restored_from: String,
+    /// Copy of the database as it was before the restore
+    pub snapshot_path: String,
+    /// Schema version of the backup, before it was migrated
+    pub backup_schema_version: i64,
+    pub schema_version: i64,
+    pub restored_at: String,
+}
+
+/// Replace the database with a backup file.
+///
+/// The candidate is checked first (integrity, schema version, tables) and
+/// the current database is snapshotted next to the backups (the newest
+/// `KEEP_SNAPSHOTS` of those are kept). The connection
+/// in `AppState` is then closed, the file replaced and reopened (running any
+/// newer migrations); if any of that fails, the snapshot is put back.
+/// Waits for neither backups nor sync: fails if one is running.
+pub async fn restore(app: &AppHandle, path: &str, passphrase: Option<String>) -> Result<RestoreReport, String> {
+    let service = app.state::<BackupService>();
+    let _backup_guard = service.try_begin().ok_or("Backup already in progress")?;
+    let scheduler = app.state::<SyncScheduler>();
+    let _sync_guard = scheduler.try_begin().ok_or("Sync in progress; try again when it has finished")?;
+
+    let handle = app.clone();
+    let candidate = PathBuf::from(path);
+    let report = tauri::async_runtime::spawn_blocking(move || restore_file(&handle, &candidate, passphrase))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    // Whatever was scheduled was based on the old database
+    service.wake();
+    scheduler.wake();
+    app.emit("backup://restored", &report).ok();
+
+    Ok(report)
+}
+
+/// Unpack an archive into the app's data folder first, then restore from that
+fn restore_file(app: &AppHandle, path: &Path, passphrase: Option<String>) -> Result<RestoreReport, String> {
+    if !archive::is_archive(path)? {
+        return restore_database(app, path, path);
+    }
+
+    let passphrase = passphrase.ok_or("This backup is encrypted; its passphrase is needed")?;
+    let extracted = scratch_path(app, "restore")?;
+    let result = archive::extract(path, &passphrase, &extracted)
+        .and_then(|_| restore_database(app, &extracted, path));
+    std::fs::remove_file(&extracted).ok();
+
+    result
+}
+
+fn restore_database(app: &AppHandle, candidate_path: &Path, source: &Path) -> Result<RestoreReport, String> {
+    let service = app.state::<BackupService>();
+    let state = app.state::<AppState>();
+
+    let candidate = Connection::open_with_flags(candidate_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Cannot open {}: {}", candidate_path.display(), e))?;
+    let backup_schema_version = validate_candidate(&candidate)?;
+
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    let db_path = conn
+        .path()
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .ok_or("The database is not a file")?;
+
+    let dir = backup_dir(&service, &load_config(conn)?);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Cannot create backup directory {}: {}", dir.display(), e))?;
+    let snapshot_path = dir.join(format!("{}{}{}", SNAPSHOT_PREFIX, Local::now().format(FILE_TIMESTAMP), FILE_SUFFIX));
+    copy_database(conn, &snapshot_path).map_err(|e| format!("Snapshot of the current database failed: {}", e))?;
+
+    if let Err(e) = swap_database(&mut db_guard, &db_path, &candidate) {
+        // Failed before the current database was closed; nothing to undo
+        if db_guard.is_some() {
+            return Err(format!("Restore failed: {}", e));
+        }
+
+        let snapshot = Connection::open_with_flags(&snapshot_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| e.to_string());
+        return match snapshot.and_then(|snapshot| swap_database(&mut db_guard, &db_path, &snapshot)) {
+            Ok(()) => Err(format!("Restore failed, the previous database was put back: {}", e)),
+            Err(rollback) => Err(format!(
+                "Restore failed ({}) and the previous database could not be reopened ({}); it is saved at {}",
+                e,
+                rollback,
+                snapshot_path.display()
+            )),
+        };
+    }
+    let restored = db_guard.as_ref().ok_or("Database not initialized")?;
+
+    match prune_snapshots(&dir) {
+        Ok(0) => {}
+        Ok(removed) => println!("Removed {} old pre-restore snapshots", removed),
+        Err(e) => println!("Pruning pre-restore snapshots failed: {}", e),
+    }
+
+    Ok(RestoreReport {
+        restored_from: source.to_string_lossy().into_owned(),
+        snapshot_path: snapshot_path.to_string_lossy().into_owned(),
+        backup_schema_version,
+        schema_version: migrations::current_version(restored)?,
+        restored_at: Utc::now().to_rfc3339(),
+    })
+}
+
+/// Check that a file is an intact GemiPrint database this version can open.
+/// Returns its schema version.
+fn validate_candidate(candidate: &Connection) -> Result<i64, String> {
+    let problems: Vec<String> = candidate
+        .prepare("PRAGMA integrity_check")
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()
+        })
+        .map_err(|e| format!("Not a readable database: {}", e))?;
+    if problems != ["ok"] {
+        return Err(format!("Backup is damaged: {}", problems.iter().take(5).cloned().collect::<Vec<_>>().join("; ")));
+    }
+
+    let version = match migrations::current_version(candidate)? {
+        // Made from the old bundled template, before migrations were tracked
+        0 if migrations::table_exists(candidate, "profil")? => 1,
+        0 => return Err("Not a GemiPrint database".to_string()),
+        version => version,
+    };
+    if version > migrations::latest_version() {
+        return Err(format!(
+            "Backup schema version {} is newer than this application supports ({}). Please update GemiPrint.",
+            version,
+            migrations::latest_version()
+        ));
+    }
+
+    let mut missing = Vec::new();
+    for table in migrations::expected_tables(version)? {
+        if !migrations::table_exists(candidate, &table)? {
+            missing.push(table);
+        }
+    }
+    if !missing.is_empty() {
+        return Err(format!("Backup is missing tables: {}", missing.join(", ")));
+    }
+
+    Ok(version)
+}
+
+/// Close the connection in `slot`, replace the file at `db_path` with a copy
+/// of `source` and reopen it. `slot` is left empty only if the old
+/// connection was closed and the new one could not be opened.
+fn swap_database(slot: &mut Option<Connection>, db_path: &Path, source: &Connection) -> Result<(), String> {
+    let restoring = db_path.with_extension("db.restoring");
+    if let Err(e) = copy_database(source, &restoring) {
+        std::fs::remove_file(&restoring).ok();
+        return Err(e);
+    }
+
+    if let Some(conn) = slot.take() {
+        if let Err((conn, e)) = conn.close() {
+            *slot = Some(conn);
+            std::fs::remove_file(&restoring).ok();
+            return Err(format!("Cannot close the database: {}", e));
+        }
+    }
+
+    // Closing the last connection checkpoints the WAL, but don't let stale
+    // companions of the old file be applied to the new one
+    for suffix in ["-wal", "-shm"] {
+        let mut companion = db_path.as_os_str().to_owned();
+        companion.push(suffix);
+        let companion = PathBuf::from(companion);
+        if companion.exists() {
+            std::fs::remove_file(&companion).map_err(|e| format!("{}: {}", companion.display(), e))?;
+        }
+    }
+    std::fs::rename(&restoring, db_path).map_err(|e| e.to_string())?;
+
+    *slot = Some(crate::open_database(db_path)?);
+    Ok(())
+}
+
+/// Temporary file for a plaintext database copy, in the app's data folder
+/// next to the live database, never in the (user-chosen, possibly removable)
+/// backup directory. Copies left behind by a crash are removed first; only
+/// one backup, export or restore runs at a time.
+fn scratch_path(app: &AppHandle, purpose: &str) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?.join("scratch");
+    if let Ok(entries) = std::fs::read_dir(&dir) {
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().ends_with(".db.partial") {
+                std::fs::remove_file(entry.path()).ok();
+            }
+        }
+    }
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Cannot create scratch directory {}: {}", dir.display(), e))?;
+    Ok(dir.join(format!("{}-{}.db.partial", purpose, uuid::Uuid::new_v4())))
+}
+
+/// Export the database as an encrypted, compressed archive to `path`
+pub async fn export_archive(app: &AppHandle, path: &str, passphrase: String) -> Result<archive::Manifest, String> {
+    let service = app.state::<BackupService>();
+    let _guard = service.try_begin().ok_or("Backup already in progress")?;
+
+    let handle = app.clone();
+    let path = PathBuf::from(path);
+    tauri::async_runtime::spawn_blocking(move || {
+        let snapshot = scratch_path(&handle, "export")?;
+        let result = {
+            let state = handle.state::<AppState>();
+            let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+            let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+            copy_database(conn, &snapshot)
+        }
+        .and_then(|_| archive::write(&snapshot, &path, &passphrase));
+        std::fs::remove_file(&snapshot).ok();
+
+        result
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
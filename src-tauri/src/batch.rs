@@ -0,0 +1,151 @@
+use crate::schema::{self, DbError};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// One step of a `db_transaction` batch.
+///
+/// Any value (in `params`, `data` or `id`) may be written as `{ "$ref": "name" }`
+/// to use the id generated by an earlier `insert` that declared `"ref": "name"`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BatchOp {
+    Execute {
+        sql: String,
+        #[serde(default)]
+        params: Vec<Value>,
+    },
+    Insert {
+        table: String,
+        data: Map<String, Value>,
+        #[serde(default, rename = "ref")]
+        reference: Option<String>,
+    },
+    Update {
+        table: String,
+        id: Value,
+        data: Map<String, Value>,
+    },
+    Delete {
+        table: String,
+        id: Value,
+    },
+}
+
+/// Outcome of a single step
+#[derive(Debug, Serialize)]
+pub struct BatchOpResult {
+    pub id: Option<String>,
+    pub affected: usize,
+}
+
+/// Outcome of a committed batch
+#[derive(Debug, Serialize)]
+pub struct BatchResult {
+    pub results: Vec<BatchOpResult>,
+    pub refs: HashMap<String, String>,
+}
+
+/// Run every operation inside one transaction.
+/// The first failure rolls back the whole batch and reports its index.
+pub fn run_batch(conn: &mut Connection, ops: Vec<BatchOp>) -> Result<BatchResult, DbError> {
+    let tx = conn.transaction()?;
+    let mut refs: HashMap<String, String> = HashMap::new();
+    let mut results = Vec::with_capacity(ops.len());
+
+    for (index, op) in ops.into_iter().enumerate() {
+        let result = run_op(&tx, op, &mut refs).map_err(|error| DbError::Batch {
+            index,
+            error: Box::new(error),
+        })?;
+        results.push(result);
+    }
+
+    // Dropping `tx` on an early return above rolls everything back
+    tx.commit()?;
+
+    Ok(BatchResult { results, refs })
+}
+
+fn run_op(
+    conn: &Connection,
+    op: BatchOp,
+    refs: &mut HashMap<String, String>,
+) -> Result<BatchOpResult, DbError> {
+    match op {
+        BatchOp::Execute { sql, params } => {
+            let values: Vec<rusqlite::types::Value> = params
+                .iter()
+                .map(|v| resolve_refs(v, refs).map(|v| crate::json_to_rusqlite_value(&v)))
+                .collect::<Result<_, _>>()?;
+            let affected = conn.execute(&sql, rusqlite::params_from_iter(values.iter()))?;
+            Ok(BatchOpResult { id: None, affected })
+        }
+
+        BatchOp::Insert { table, data, reference } => {
+            let table = schema::table(&table)?;
+            let data = resolve_map(&data, refs)?;
+            let (id, sql, values) = schema::build_insert(table, &data)?;
+            let affected = conn.execute(&sql, rusqlite::params_from_iter(values.iter()))?;
+
+            if let Some(name) = reference {
+                refs.insert(name, id.clone());
+            }
+
+            Ok(BatchOpResult { id: Some(id), affected })
+        }
+
+        BatchOp::Update { table, id, data } => {
+            let table = schema::table(&table)?;
+            let id = resolve_id(&id, refs)?;
+            let data = resolve_map(&data, refs)?;
+            let (sql, values) = schema::build_update(table, &id, &data)?;
+            let affected = conn.execute(&sql, rusqlite::params_from_iter(values.iter()))?;
+            Ok(BatchOpResult { id: Some(id), affected })
+        }
+
+        BatchOp::Delete { table, id } => {
+            let table = schema::table(&table)?;
+            let id = resolve_id(&id, refs)?;
+            let affected = conn.execute(&schema::build_delete(table), [&id])?;
+            Ok(BatchOpResult { id: Some(id), affected })
+        }
+    }
+}
+
+/// Replace a `{ "$ref": "name" }` placeholder with the id it points to
+fn resolve_refs(value: &Value, refs: &HashMap<String, String>) -> Result<Value, DbError> {
+    let name = match value.as_object().and_then(|o| o.get("$ref")) {
+        Some(name) => name,
+        None => return Ok(value.clone()),
+    };
+
+    let name = name.as_str().ok_or(DbError::InvalidData {
+        message: "$ref must be a string".to_string(),
+    })?;
+
+    refs.get(name)
+        .map(|id| Value::String(id.clone()))
+        .ok_or_else(|| DbError::InvalidData {
+            message: format!("Unknown $ref: {}", name),
+        })
+}
+
+fn resolve_map(
+    data: &Map<String, Value>,
+    refs: &HashMap<String, String>,
+) -> Result<Map<String, Value>, DbError> {
+    data.iter()
+        .map(|(k, v)| resolve_refs(v, refs).map(|v| (k.clone(), v)))
+        .collect()
+}
+
+fn resolve_id(id: &Value, refs: &HashMap<String, String>) -> Result<String, DbError> {
+    match resolve_refs(id, refs)? {
+        Value::String(s) => Ok(s),
+        other => Err(DbError::InvalidData {
+            message: format!("Record id must be a string, got {}", other),
+        }),
+    }
+}
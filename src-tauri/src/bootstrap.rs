@@ -0,0 +1,251 @@
+use crate::capture;
+use crate::config;
+use crate::pull::{self, PullCursor};
+use crate::schema::{quote_ident, TableDef};
+use crate::scheduler::SyncScheduler;
+use crate::settings;
+use crate::sync::SupabaseConfig;
+use crate::{auth, AppState};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Rows requested per page; larger than the incremental pull, since a
+/// bootstrap downloads whole tables
+const PAGE_SIZE: usize = 1000;
+
+const BOOTSTRAPPED_KEY: &str = "sync.bootstrapped_at";
+
+/// Download and verification result for one table
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableBootstrap {
+    pub table: String,
+    /// Rows written locally during the download
+    pub pulled: usize,
+    pub remote_count: Option<i64>,
+    /// Local rows that came from this bootstrap
+    pub received_count: i64,
+    /// Local rows the cloud did not send (e.g. the template's admin account)
+    pub local_only_count: i64,
+    pub verified: bool,
+    pub error: Option<String>,
+}
+
+/// Result of `bootstrap_from_cloud`, also the payload of `bootstrap://finished`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BootstrapReport {
+    pub started_at: String,
+    pub finished_at: String,
+    pub tables: Vec<TableBootstrap>,
+    pub pulled: usize,
+    /// Every table downloaded completely
+    pub verified: bool,
+}
+
+/// Payload of `bootstrap://progress`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BootstrapProgress<'a> {
+    phase: &'a str,
+    table: Option<&'a str>,
+    table_index: usize,
+    table_count: usize,
+    /// Rows pulled so far for `table`
+    table_rows: usize,
+    /// Rows pulled so far in total
+    total_rows: usize,
+}
+
+/// Fill the local database from Supabase: download every synced table from
+/// the beginning, parents first, then compare row counts with the cloud.
+/// Refuses to run while local changes are waiting to be pushed.
+pub async fn run(app: &AppHandle) -> Result<BootstrapReport, String> {
+    let scheduler = app.state::<SyncScheduler>();
+    let _guard = scheduler.try_begin().ok_or("Sync already in progress")?;
+    let state = app.state::<AppState>();
+    let config = auth::resolve_config(&state.db).await?;
+    let started_at = chrono::Utc::now().to_rfc3339();
+
+    let tables = {
+        let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+        let conn = db_guard.as_mut().ok_or("Database not initialized")?;
+        let tables = config::sync_tables(conn)?;
+        prepare(conn, &tables)?;
+        tables
+    };
+
+    let client = config.client();
+    let mut progress = BootstrapProgress {
+        phase: "download",
+        table: None,
+        table_index: 0,
+        table_count: tables.len(),
+        table_rows: 0,
+        total_rows: 0,
+    };
+    let mut reports = Vec::new();
+
+    for (index, table) in tables.iter().enumerate() {
+        progress.table = Some(table.name);
+        progress.table_index = index;
+        progress.table_rows = 0;
+        app.emit("bootstrap://progress", &progress).ok();
+
+        let mut report = TableBootstrap {
+            table: table.name.to_string(),
+            ..Default::default()
+        };
+        if let Err(e) = download(&state.db, &client, &config, table, app, &mut progress).await {
+            report.error = Some(e);
+        }
+        report.pulled = progress.table_rows;
+        reports.push(report);
+    }
+
+    progress.phase = "verify";
+    for (index, (table, report)) in tables.iter().zip(reports.iter_mut()).enumerate() {
+        progress.table = Some(table.name);
+        progress.table_index = index;
+        app.emit("bootstrap://progress", &progress).ok();
+
+        if report.error.is_none() {
+            if let Err(e) = verify(&state.db, &client, &config, table, &started_at, report).await {
+                report.error = Some(e);
+            }
+        }
+    }
+
+    let verified = reports.iter().all(|r| r.verified);
+    let finished_at = chrono::Utc::now().to_rfc3339();
+    if verified {
+        let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        settings::set(conn, BOOTSTRAPPED_KEY, &finished_at)?;
+    }
+
+    let report = BootstrapReport {
+        started_at,
+        finished_at,
+        pulled: reports.iter().map(|r| r.pulled).sum(),
+        tables: reports,
+        verified,
+    };
+    app.emit("bootstrap://finished", &report).ok();
+
+    Ok(report)
+}
+
+/// Check there is nothing to push, mark the rows already present (the
+/// template's admin account) as synced and forget the pull cursors so every
+/// table is downloaded from the start
+fn prepare(conn: &mut Connection, tables: &[&TableDef]) -> Result<(), String> {
+    let unpushed: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM sync_queue
+             WHERE synced_at IS NULL AND status IN ('pending', 'dead_letter', 'conflict')",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if unpushed > 0 {
+        return Err(format!(
+            "{} local changes have not been pushed yet; sync or discard them before bootstrapping",
+            unpushed
+        ));
+    }
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    capture::pause(&tx).map_err(|e| e.to_string())?;
+
+    for table in tables {
+        tx.execute(
+            &format!(
+                "UPDATE {} SET sync_status = 'synced' WHERE sync_status IS NOT 'synced'",
+                quote_ident(table.name)
+            ),
+            [],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.execute("DELETE FROM sync_pull_state WHERE table_name = ?1", params![table.name])
+            .map_err(|e| e.to_string())?;
+    }
+
+    capture::resume(&tx).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+async fn download(
+    db: &Mutex<Option<Connection>>,
+    client: &reqwest::Client,
+    config: &SupabaseConfig,
+    table: &TableDef,
+    app: &AppHandle,
+    progress: &mut BootstrapProgress<'_>,
+) -> Result<(), String> {
+    let column = pull::cursor_column(table);
+    let mut cursor = PullCursor::default();
+
+    loop {
+        let rows = pull::fetch_page(client, config, table.name, column, &cursor, PAGE_SIZE).await?;
+        let page_len = rows.len();
+        if page_len == 0 {
+            break;
+        }
+
+        let (applied, _) = {
+            let mut db_guard = db.lock().map_err(|e| e.to_string())?;
+            let conn = db_guard.as_mut().ok_or("Database not initialized")?;
+            pull::apply_page(conn, table, &rows, &mut cursor)?
+        };
+        progress.table_rows += applied;
+        progress.total_rows += applied;
+        app.emit("bootstrap://progress", &*progress).ok();
+
+        if page_len < PAGE_SIZE {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Compare the cloud's row count with the rows this bootstrap wrote.
+/// Rows added on the cloud after the download show up as a shortfall;
+/// running the bootstrap (or a normal pull) again picks them up.
+async fn verify(
+    db: &Mutex<Option<Connection>>,
+    client: &reqwest::Client,
+    config: &SupabaseConfig,
+    table: &TableDef,
+    started_at: &str,
+    report: &mut TableBootstrap,
+) -> Result<(), String> {
+    let remote_count = pull::count_remote(client, config, table.name).await?;
+
+    let (local_count, received_count): (i64, i64) = {
+        let db_guard = db.lock().map_err(|e| e.to_string())?;
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        conn.query_row(
+            &format!(
+                "SELECT COUNT(*), COALESCE(SUM(last_synced_at >= ?1), 0) FROM {}",
+                quote_ident(table.name)
+            ),
+            params![started_at],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?
+    };
+
+    report.remote_count = Some(remote_count);
+    report.received_count = received_count;
+    report.local_only_count = local_count - received_count;
+    report.verified = received_count >= remote_count;
+    if !report.verified {
+        report.error = Some(format!("Received {} of {} rows", received_count, remote_count));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use crate::schema::{self, quote_ident, TableDef};
+use rusqlite::Connection;
+
+// SQL expression producing a random UUID v4-shaped id, so trigger-written
+// queue entries look like the ones written from Rust
+const SQL_UUID: &str = "lower(hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' || \
+    substr(hex(randomblob(2)), 2) || '-' || substr('89ab', 1 + (abs(random()) % 4), 1) || \
+    substr(hex(randomblob(2)), 2) || '-' || hex(randomblob(6)))";
+
+const SQL_NOW: &str = "strftime('%Y-%m-%dT%H:%M:%fZ', 'now')";
+
+/// (Re)create the capture triggers on every synced table.
+///
+/// Each insert/update/delete appends an entry to `sync_queue` with the row's
+/// full JSON, bumps `sync_version` and resets `sync_status` to 'pending'.
+/// Updates and deletes record the version they were based on, so the push can
+/// detect a concurrent change on the cloud.
+/// Triggers are generated from the schema registry and rebuilt on every start,
+/// so they always cover the columns the binary knows about. Install them after
+/// the migrations: 0013 drops the old template's timestamp triggers, whose
+/// own UPDATE would make every edit queue twice.
+pub fn install_triggers(conn: &mut Connection) -> Result<(), String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    for table in schema::TABLES.iter().filter(|t| t.is_synced()) {
+        tx.execute_batch(&trigger_sql(table))
+            .map_err(|e| format!("Failed to install sync triggers on {}: {}", table.name, e))?;
+    }
+
+    // A crash while paused would otherwise silently stop capture for good
+    tx.execute("DELETE FROM sync_capture_pause", [])
+        .map_err(|e| e.to_string())?;
+
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Stop capturing writes until `resume`. Call inside the same transaction
+/// as the writes, so a rollback also undoes the pause.
+pub fn pause(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute("INSERT OR IGNORE INTO sync_capture_pause (id) VALUES (1)", [])?;
+    Ok(())
+}
+
+/// Resume capturing writes after `pause`
+pub fn resume(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM sync_capture_pause", [])?;
+    Ok(())
+}
+
+fn trigger_sql(table: &TableDef) -> String {
+    let name = table.name;
+    let quoted = quote_ident(name);
+    let active = "NOT EXISTS (SELECT 1 FROM sync_capture_pause)";
+
+    // Snapshot the stored row (after the version bump) as a JSON object
+    let json_args: Vec<String> = table
+        .columns
+        .iter()
+        .map(|c| format!("'{}', {}", c.name, quote_ident(c.name)))
+        .collect();
+    let snapshot = format!(
+        "(SELECT json_object({}) FROM {} WHERE id = NEW.id)",
+        json_args.join(", "),
+        quoted
+    );
+
+    format!(
+        "DROP TRIGGER IF EXISTS sync_capture_{name}_insert;
+         DROP TRIGGER IF EXISTS sync_capture_{name}_update;
+         DROP TRIGGER IF EXISTS sync_capture_{name}_delete;
+
+         CREATE TRIGGER sync_capture_{name}_insert AFTER INSERT ON {quoted}
+         WHEN {active}
+         BEGIN
+             -- Paused so the update trigger does not queue this as a second change
+             INSERT INTO sync_capture_pause (id) VALUES (1);
+             UPDATE {quoted} SET sync_status = 'pending'
+             WHERE id = NEW.id AND sync_status IS NOT 'pending';
+             DELETE FROM sync_capture_pause;
+             INSERT INTO sync_queue (id, table_name, operation, record_id, data, created_at)
+             VALUES ({uuid}, '{name}', 'insert', NEW.id, {snapshot}, {now});
+         END;
+
+         CREATE TRIGGER sync_capture_{name}_update AFTER UPDATE ON {quoted}
+         WHEN {active}
+         BEGIN
+             UPDATE {quoted}
+             SET sync_version = COALESCE(NEW.sync_version, 0) + 1, sync_status = 'pending'
+             WHERE id = NEW.id;
+             INSERT INTO sync_queue (id, table_name, operation, record_id, data, created_at, base_version)
+             VALUES ({uuid}, '{name}', 'update', NEW.id, {snapshot}, {now}, OLD.sync_version);
+         END;
+
+         CREATE TRIGGER sync_capture_{name}_delete AFTER DELETE ON {quoted}
+         WHEN {active}
+         BEGIN
+             INSERT INTO sync_queue (id, table_name, operation, record_id, data, created_at, base_version)
+             VALUES ({uuid}, '{name}', 'delete', OLD.id, NULL, {now}, OLD.sync_version);
+         END;",
+        name = name,
+        quoted = quoted,
+        active = active,
+        snapshot = snapshot,
+        uuid = SQL_UUID,
+        now = SQL_NOW,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock_postgrest::template_db;
+
+    #[test]
+    fn one_update_on_a_template_table_is_queued_once() {
+        let db = template_db();
+        let guard = db.lock().unwrap();
+        let conn = guard.as_ref().unwrap();
+        conn.execute("INSERT INTO kategori_barang (id, nama) VALUES ('k-test', 'Banner')", [])
+            .unwrap();
+        // (sync_version, diperbarui_pada)
+        let state = |conn: &rusqlite::Connection| -> (i64, Option<String>) {
+            conn.query_row(
+                "SELECT sync_version, diperbarui_pada FROM kategori_barang WHERE id = 'k-test'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap()
+        };
+        let (version, diperbarui_pada) = state(conn);
+
+        conn.execute("UPDATE kategori_barang SET nama = 'Spanduk' WHERE id = 'k-test'", [])
+            .unwrap();
+
+        let updates: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sync_queue WHERE record_id = 'k-test' AND operation = 'update'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(updates, 1);
+        // No legacy timestamp trigger rewriting the row a second time
+        assert_eq!(state(conn), (version + 1, diperbarui_pada));
+    }
+}
@@ -0,0 +1,337 @@
+use crate::queue;
+use crate::schema::{self, TableDef};
+use crate::scheduler;
+use crate::secrets;
+use crate::settings;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::time::{Duration, Instant};
+
+const URL_KEY: &str = "sync.supabase_url";
+const ANON_KEY_KEY: &str = "sync.anon_key";
+const SERVICE_KEY_KEY: &str = "sync.service_key";
+
+/// REST base URL replacing `<url>/rest/v1`, e.g. a PostgREST on localhost
+const REST_URL_KEY: &str = "sync.rest_url";
+
+/// JSON array of table names; missing means every synced table
+pub const ENABLED_TABLES_KEY: &str = "sync.enabled_tables";
+
+const TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sync configuration as shown in the settings screen.
+/// The service key is write-only and never sent back to the webview.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncConfig {
+    pub supabase_url: Option<String>,
+    /// Only set when REST requests go somewhere other than `<supabaseUrl>/rest/v1`
+    pub rest_url: Option<String>,
+    pub anon_key: Option<String>,
+    pub service_key_set: bool,
+    pub enabled_tables: Vec<String>,
+    pub available_tables: Vec<String>,
+    pub interval_minutes: u64,
+    /// Days synced queue entries are kept
+    pub retention_days: i64,
+    /// True when nothing is stored and the values come from the
+    /// `NEXT_PUBLIC_SUPABASE_*` environment variables (development builds)
+    pub from_environment: bool,
+}
+
+/// Fields to change; `None` keeps the stored value.
+/// An empty `service_key` or `rest_url` removes the stored value.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncConfigUpdate {
+    pub supabase_url: Option<String>,
+    pub rest_url: Option<String>,
+    pub anon_key: Option<String>,
+    pub service_key: Option<String>,
+    pub enabled_tables: Option<Vec<String>>,
+    pub interval_minutes: Option<u64>,
+    pub retention_days: Option<i64>,
+}
+
+/// Result of `test_sync_config`
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionTest {
+    pub reachable: bool,
+    pub anon_key_valid: bool,
+    /// `None` when no service key is configured
+    pub service_key_valid: Option<bool>,
+    pub latency_ms: Option<u64>,
+    pub message: String,
+}
+
+/// Supabase URL and anon key: stored settings first, then the environment
+pub fn endpoint(conn: &Connection) -> Result<Option<(String, String)>, String> {
+    let url = settings::get(conn, URL_KEY)?;
+    let anon_key = secrets::get(conn, ANON_KEY_KEY)?;
+
+    if let (Some(url), Some(anon_key)) = (url, anon_key) {
+        return Ok(Some((url, anon_key)));
+    }
+
+    Ok(env_endpoint())
+}
+
+fn env_endpoint() -> Option<(String, String)> {
+    let url = env::var("NEXT_PUBLIC_SUPABASE_URL").ok().filter(|v| !v.is_empty())?;
+    let anon_key = env::var("NEXT_PUBLIC_SUPABASE_ANON_KEY").ok().filter(|v| !v.is_empty())?;
+    Some((url.trim_end_matches('/').to_string(), anon_key))
+}
+
+pub fn service_key(conn: &Connection) -> Result<Option<String>, String> {
+    if let Some(key) = secrets::get(conn, SERVICE_KEY_KEY)? {
+        return Ok(Some(key));
+    }
+    Ok(env::var("SUPABASE_SERVICE_ROLE_KEY").ok().filter(|k| !k.is_empty()))
+}
+
+/// REST base URL override: stored setting, then `SUPABASE_REST_URL`
+pub fn rest_url(conn: &Connection) -> Result<Option<String>, String> {
+    if let Some(url) = settings::get(conn, REST_URL_KEY)? {
+        return Ok(Some(url));
+    }
+    Ok(env::var("SUPABASE_REST_URL")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .map(|v| v.trim_end_matches('/').to_string()))
+}
+
+/// Tables selected for sync; `None` means all of them
+pub fn enabled_tables(conn: &Connection) -> Result<Option<Vec<String>>, String> {
+    Ok(settings::get(conn, ENABLED_TABLES_KEY)?.and_then(|v| serde_json::from_str(&v).ok()))
+}
+
+/// Synced tables selected for sync, parents first
+pub fn sync_tables(conn: &Connection) -> Result<Vec<&'static TableDef>, String> {
+    let enabled = enabled_tables(conn)?;
+    Ok(schema::dependency_order()
+        .into_iter()
+        .filter(|t| t.is_synced())
+        .filter(|t| enabled.as_ref().is_none_or(|e| e.iter().any(|name| name == t.name)))
+        .collect())
+}
+
+pub fn load(conn: &Connection) -> Result<SyncConfig, String> {
+    let stored_url = settings::get(conn, URL_KEY)?;
+    let stored_anon_key = secrets::get(conn, ANON_KEY_KEY)?;
+    let from_environment = stored_url.is_none() && stored_anon_key.is_none() && env_endpoint().is_some();
+    let (supabase_url, anon_key) = if from_environment {
+        env_endpoint().unzip()
+    } else {
+        (stored_url, stored_anon_key)
+    };
+
+    let available_tables: Vec<String> = synced_tables().map(str::to_string).collect();
+
+    Ok(SyncConfig {
+        supabase_url,
+        rest_url: rest_url(conn)?,
+        anon_key,
+        service_key_set: service_key(conn)?.is_some(),
+        enabled_tables: enabled_tables(conn)?.unwrap_or_else(|| available_tables.clone()),
+        available_tables,
+        interval_minutes: scheduler::load_schedule(conn)?.interval_minutes,
+        retention_days: settings::get_or(conn, queue::RETENTION_DAYS_KEY, queue::DEFAULT_RETENTION_DAYS)?,
+        from_environment,
+    })
+}
+
+/// Validate and store the given fields, all of them or none
+pub fn update(conn: &Connection, update: &SyncConfigUpdate) -> Result<(), String> {
+    // Rolled back on drop, so a field rejected late keeps the earlier ones unsaved
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+
+    if let Some(url) = &update.supabase_url {
+        settings::set(&tx, URL_KEY, &normalize_url(url)?)?;
+    }
+
+    match update.rest_url.as_deref().map(str::trim) {
+        Some("") => settings::remove(&tx, REST_URL_KEY)?,
+        Some(url) => settings::set(&tx, REST_URL_KEY, &normalize_url(url)?)?,
+        None => {}
+    }
+
+    if let Some(anon_key) = &update.anon_key {
+        let anon_key = anon_key.trim();
+        if anon_key.is_empty() {
+            return Err("Anon key must not be empty".to_string());
+        }
+        secrets::set(&tx, ANON_KEY_KEY, anon_key)?;
+    }
+
+    match update.service_key.as_deref().map(str::trim) {
+        Some("") => settings::remove(&tx, SERVICE_KEY_KEY)?,
+        Some(key) => secrets::set(&tx, SERVICE_KEY_KEY, key)?,
+        None => {}
+    }
+
+    if let Some(tables) = &update.enabled_tables {
+        validate_tables(tables)?;
+        let json = serde_json::to_string(tables).map_err(|e| e.to_string())?;
+        settings::set(&tx, ENABLED_TABLES_KEY, &json)?;
+    }
+
+    if let Some(interval_minutes) = update.interval_minutes {
+        let schedule = scheduler::SyncSchedule {
+            interval_minutes,
+            ..scheduler::load_schedule(&tx)?
+        };
+        scheduler::save_schedule(&tx, &schedule)?;
+    }
+
+    if let Some(days) = update.retention_days {
+        if !(1..=queue::MAX_RETENTION_DAYS).contains(&days) {
+            return Err(format!("Retention must be between 1 and {} days", queue::MAX_RETENTION_DAYS));
+        }
+        settings::set(&tx, queue::RETENTION_DAYS_KEY, &days.to_string())?;
+    }
+
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Values to test: the stored configuration with `overrides` applied.
+/// Returns the REST base URL, anon key and service key.
+pub fn test_target(
+    conn: &Connection,
+    overrides: &SyncConfigUpdate,
+) -> Result<(String, String, Option<String>), String> {
+    let stored = endpoint(conn)?;
+    let url = match &overrides.supabase_url {
+        Some(url) => normalize_url(url)?,
+        None => stored.as_ref().map(|(url, _)| url.clone()).ok_or("Supabase URL is not set")?,
+    };
+    let rest_url = match overrides.rest_url.as_deref().map(str::trim) {
+        Some("") => None,
+        Some(rest_url) => Some(normalize_url(rest_url)?),
+        None => rest_url(conn)?,
+    };
+    let anon_key = match &overrides.anon_key {
+        Some(key) => key.trim().to_string(),
+        None => stored.map(|(_, key)| key).ok_or("Anon key is not set")?,
+    };
+    let service_key = match overrides.service_key.as_deref().map(str::trim) {
+        Some("") => None,
+        Some(key) => Some(key.to_string()),
+        None => service_key(conn)?,
+    };
+    Ok((rest_url.unwrap_or_else(|| format!("{}/rest/v1", url)), anon_key, service_key))
+}
+
+/// Query one synced table with each key and report what the server says
+pub async fn test_connection(rest_url: &str, anon_key: &str, service_key: Option<&str>) -> ConnectionTest {
+    let mut result = ConnectionTest::default();
+    let client = match reqwest::Client::builder().timeout(TEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            result.message = e.to_string();
+            return result;
+        }
+    };
+
+    let started = Instant::now();
+    match probe(&client, rest_url, anon_key).await {
+        Ok(()) => {
+            result.reachable = true;
+            result.anon_key_valid = true;
+        }
+        Err(Probe::Rejected(message)) => {
+            result.reachable = true;
+            result.message = format!("Anon key rejected: {}", message);
+        }
+        Err(Probe::Failed(message)) => {
+            result.message = message;
+            return result;
+        }
+    }
+    result.latency_ms = Some(started.elapsed().as_millis() as u64);
+
+    if let Some(key) = service_key {
+        match probe(&client, rest_url, key).await {
+            Ok(()) => result.service_key_valid = Some(true),
+            Err(Probe::Rejected(message)) | Err(Probe::Failed(message)) => {
+                result.service_key_valid = Some(false);
+                if result.message.is_empty() {
+                    result.message = format!("Service key rejected: {}", message);
+                }
+            }
+        }
+    }
+
+    if result.message.is_empty() {
+        result.message = "Connected".to_string();
+    }
+    result
+}
+
+enum Probe {
+    /// The server answered but refused the key
+    Rejected(String),
+    /// No usable answer (network error, missing table, server error)
+    Failed(String),
+}
+
+async fn probe(client: &reqwest::Client, rest_url: &str, key: &str) -> Result<(), Probe> {
+    let table = synced_tables().next().unwrap_or("profil");
+    let response = client
+        .get(format!("{}/{}", rest_url, table))
+        .query(&[("select", "id"), ("limit", "1")])
+        .header("apikey", key)
+        .header("Authorization", format!("Bearer {}", key))
+        .send()
+        .await
+        .map_err(|e| Probe::Failed(format!("Could not reach Supabase: {}", e)))?;
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    match status.as_u16() {
+        401 | 403 => Err(Probe::Rejected(format!("HTTP {}", status))),
+        404 => Err(Probe::Failed(format!("Table {} not found on the server: {}", table, body))),
+        _ => Err(Probe::Failed(format!("HTTP {}: {}", status, body))),
+    }
+}
+
+/// `https://xyz.supabase.co/` -> `https://xyz.supabase.co`
+fn normalize_url(url: &str) -> Result<String, String> {
+    let trimmed = url.trim().trim_end_matches('/');
+    let parsed = reqwest::Url::parse(trimmed).map_err(|e| format!("Invalid Supabase URL: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err("Supabase URL must be an http(s) address".to_string());
+    }
+    Ok(trimmed.to_string())
+}
+
+/// Every table must be synced, and its synced parents must be enabled too,
+/// otherwise the cloud would reject rows for missing foreign keys
+fn validate_tables(tables: &[String]) -> Result<(), String> {
+    for name in tables {
+        let table = schema::table(name)
+            .ok()
+            .filter(|t| t.is_synced())
+            .ok_or_else(|| format!("{} is not a synced table", name))?;
+
+        for (_, parent) in table.references {
+            let parent_synced = schema::table(parent).map(|t| t.is_synced()).unwrap_or(false);
+            if *parent != table.name && parent_synced && !tables.iter().any(|t| t == parent) {
+                return Err(format!("{} references {}, which must be enabled too", name, parent));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn synced_tables() -> impl Iterator<Item = &'static str> {
+    schema::dependency_order()
+        .into_iter()
+        .filter(|t| t.is_synced())
+        .map(|t| t.name)
+}
@@ -0,0 +1,507 @@
+use crate::capture;
+use crate::pull;
+use crate::schema::{self, quote_ident, TableDef};
+use crate::sync::{self, SyncOperation};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+/// How a conflict on a table is settled when it is detected during push
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// The side changed most recently wins (deletions on the cloud lose)
+    LastWriterWins,
+    LocalWins,
+    RemoteWins,
+    /// Last writer wins, but manually overridden fields (`override_*`) survive from either side
+    Merge,
+    /// Leave the conflict open until someone resolves it
+    Manual,
+}
+
+impl ConflictPolicy {
+    fn as_str(self) -> &'static str {
+        match self {
+            ConflictPolicy::LastWriterWins => "last_writer_wins",
+            ConflictPolicy::LocalWins => "local_wins",
+            ConflictPolicy::RemoteWins => "remote_wins",
+            ConflictPolicy::Merge => "merge",
+            ConflictPolicy::Manual => "manual",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        serde_json::from_value(Value::String(value.to_string())).ok()
+    }
+}
+
+/// Which version a conflict was settled with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    Local,
+    Remote,
+    Merged,
+}
+
+impl Resolution {
+    fn as_str(self) -> &'static str {
+        match self {
+            Resolution::Local => "local",
+            Resolution::Remote => "remote",
+            Resolution::Merged => "merged",
+        }
+    }
+}
+
+/// Effective policy for one synced table
+#[derive(Debug, Serialize)]
+pub struct TablePolicy {
+    pub table: String,
+    pub policy: ConflictPolicy,
+    pub is_default: bool,
+}
+
+/// A row of `sync_conflicts`
+#[derive(Debug, Serialize)]
+pub struct SyncConflict {
+    pub id: String,
+    pub table_name: String,
+    pub record_id: String,
+    pub operation: String,
+    pub local_data: Option<Value>,
+    pub remote_data: Option<Value>,
+    pub remote_version: Option<i64>,
+    pub policy: String,
+    pub status: String,
+    pub resolution: Option<String>,
+    pub detected_at: String,
+    pub resolved_at: Option<String>,
+}
+
+/// Tables with manual override flags merge field by field, everything else
+/// falls back to last writer wins
+pub fn default_policy(table: &TableDef) -> ConflictPolicy {
+    if override_pairs(table).next().is_some() {
+        ConflictPolicy::Merge
+    } else {
+        ConflictPolicy::LastWriterWins
+    }
+}
+
+/// Policy configured for a table, or its default
+pub fn policy_for(conn: &Connection, table: &TableDef) -> Result<ConflictPolicy, String> {
+    let stored: Option<String> = conn
+        .query_row(
+            "SELECT policy FROM sync_conflict_policy WHERE table_name = ?1",
+            params![table.name],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    Ok(stored
+        .as_deref()
+        .and_then(ConflictPolicy::parse)
+        .unwrap_or_else(|| default_policy(table)))
+}
+
+/// Effective policy of every synced table
+pub fn list_policies(conn: &Connection) -> Result<Vec<TablePolicy>, String> {
+    schema::TABLES
+        .iter()
+        .filter(|t| t.is_synced())
+        .map(|table| {
+            let policy = policy_for(conn, table)?;
+            Ok(TablePolicy {
+                table: table.name.to_string(),
+                policy,
+                is_default: policy == default_policy(table),
+            })
+        })
+        .collect()
+}
+
+/// Set the policy for a table; `None` restores the default
+pub fn set_policy(
+    conn: &Connection,
+    table: &str,
+    policy: Option<ConflictPolicy>,
+) -> Result<(), String> {
+    let table = schema::table(table).map_err(|e| e.to_string())?;
+    if !table.is_synced() {
+        return Err(format!("Table {} is not synced", table.name));
+    }
+
+    match policy {
+        Some(policy) => conn.execute(
+            "INSERT INTO sync_conflict_policy (table_name, policy) VALUES (?1, ?2)
+             ON CONFLICT(table_name) DO UPDATE SET policy = excluded.policy",
+            params![table.name, policy.as_str()],
+        ),
+        None => conn.execute(
+            "DELETE FROM sync_conflict_policy WHERE table_name = ?1",
+            params![table.name],
+        ),
+    }
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Conflicts, newest first, optionally filtered by status ('open' / 'resolved')
+pub fn list_conflicts(conn: &Connection, status: Option<&str>) -> Result<Vec<SyncConflict>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, table_name, record_id, operation, local_data, remote_data, remote_version,
+                    policy, status, resolution, detected_at, resolved_at
+             FROM sync_conflicts
+             WHERE ?1 IS NULL OR status = ?1
+             ORDER BY detected_at DESC",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let conflicts = stmt
+        .query_map(params![status], read_conflict)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(conflicts)
+}
+
+fn read_conflict(row: &rusqlite::Row) -> rusqlite::Result<SyncConflict> {
+    let json = |text: Option<String>| text.and_then(|t| serde_json::from_str(&t).ok());
+    Ok(SyncConflict {
+        id: row.get(0)?,
+        table_name: row.get(1)?,
+        record_id: row.get(2)?,
+        operation: row.get(3)?,
+        local_data: json(row.get(4)?),
+        remote_data: json(row.get(5)?),
+        remote_version: row.get(6)?,
+        policy: row.get(7)?,
+        status: row.get(8)?,
+        resolution: row.get(9)?,
+        detected_at: row.get(10)?,
+        resolved_at: row.get(11)?,
+    })
+}
+
+/// Record a push that was rejected because the cloud row changed (or vanished)
+/// since the local change was made, then settle it with the table's policy.
+///
+/// The queue entry and any later entries for the same record are retired;
+/// whichever version wins is written locally and, if it is the local one,
+/// queued again on top of the remote version.
+pub fn handle_conflict(
+    conn: &Connection,
+    op: &SyncOperation,
+    remote: Option<Map<String, Value>>,
+) -> Result<(), String> {
+    let table = schema::table(&op.table_name).map_err(|e| e.to_string())?;
+    let record_id = op.record_id.as_deref().ok_or("Conflict without record_id")?;
+    let now = Utc::now().to_rfc3339();
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    capture::pause(&tx).map_err(|e| e.to_string())?;
+
+    let local = sync::read_row(&tx, table, record_id)?;
+    let policy = policy_for(&tx, table)?;
+    let conflict_id = Uuid::new_v4().to_string();
+
+    tx.execute(
+        "INSERT INTO sync_conflicts
+            (id, table_name, record_id, operation, local_data, remote_data, remote_version, policy, detected_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            conflict_id,
+            table.name,
+            record_id,
+            op.operation,
+            local.as_ref().map(|r| Value::Object(r.clone()).to_string()),
+            remote.as_ref().map(|r| Value::Object(r.clone()).to_string()),
+            remote.as_ref().and_then(row_version),
+            policy.as_str(),
+            now,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    tx.execute(
+        "UPDATE sync_queue SET status = 'conflict' WHERE id = ?1",
+        params![op.id],
+    )
+    .map_err(|e| e.to_string())?;
+    retire_pending(&tx, table.name, record_id)?;
+
+    if policy == ConflictPolicy::Manual {
+        tx.execute(
+            &format!("UPDATE {} SET sync_status = 'conflict' WHERE id = ?1", quote_ident(table.name)),
+            params![record_id],
+        )
+        .map_err(|e| e.to_string())?;
+    } else {
+        let (resolution, merged) = choose(policy, table, op, local.as_ref(), remote.as_ref());
+        apply_resolution(&tx, table, record_id, remote.as_ref(), resolution, merged, &now)?;
+        mark_resolved(&tx, &conflict_id, resolution, &now)?;
+    }
+
+    capture::resume(&tx).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Settle an open conflict by hand. `data` is only used for `Merged` and is
+/// applied on top of the current local row.
+pub fn resolve_conflict(
+    conn: &mut Connection,
+    conflict_id: &str,
+    resolution: Resolution,
+    data: Option<Map<String, Value>>,
+) -> Result<SyncConflict, String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let conflict = tx
+        .query_row(
+            "SELECT id, table_name, record_id, operation, local_data, remote_data, remote_version,
+                    policy, status, resolution, detected_at, resolved_at
+             FROM sync_conflicts WHERE id = ?1",
+            params![conflict_id],
+            read_conflict,
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Conflict {} not found", conflict_id))?;
+
+    if conflict.status != "open" {
+        return Err(format!("Conflict {} is already resolved", conflict_id));
+    }
+
+    let table = schema::table(&conflict.table_name).map_err(|e| e.to_string())?;
+    let remote = match &conflict.remote_data {
+        Some(Value::Object(map)) => Some(map.clone()),
+        _ => None,
+    };
+
+    let merged = match resolution {
+        Resolution::Merged => {
+            let data = data.ok_or("Merged resolution needs data")?;
+            schema::validate_data(table, &data).map_err(|e| e.to_string())?;
+            let mut row = sync::read_row(&tx, table, &conflict.record_id)?
+                .ok_or("Cannot merge into a record that was deleted locally")?;
+            row.extend(data);
+            row.insert("id".to_string(), Value::String(conflict.record_id.clone()));
+            Some(row)
+        }
+        _ => None,
+    };
+
+    let now = Utc::now().to_rfc3339();
+    capture::pause(&tx).map_err(|e| e.to_string())?;
+    apply_resolution(&tx, table, &conflict.record_id, remote.as_ref(), resolution, merged, &now)?;
+    mark_resolved(&tx, conflict_id, resolution, &now)?;
+    capture::resume(&tx).map_err(|e| e.to_string())?;
+
+    let resolved = tx
+        .query_row(
+            "SELECT id, table_name, record_id, operation, local_data, remote_data, remote_version,
+                    policy, status, resolution, detected_at, resolved_at
+             FROM sync_conflicts WHERE id = ?1",
+            params![conflict_id],
+            read_conflict,
+        )
+        .map_err(|e| e.to_string())?;
+
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(resolved)
+}
+
+/// Pick the winning side for an automatic policy
+fn choose(
+    policy: ConflictPolicy,
+    table: &TableDef,
+    op: &SyncOperation,
+    local: Option<&Map<String, Value>>,
+    remote: Option<&Map<String, Value>>,
+) -> (Resolution, Option<Map<String, Value>>) {
+    match policy {
+        ConflictPolicy::LocalWins | ConflictPolicy::Manual => (Resolution::Local, None),
+        ConflictPolicy::RemoteWins => (Resolution::Remote, None),
+        ConflictPolicy::LastWriterWins => (last_writer(op, remote), None),
+        ConflictPolicy::Merge => {
+            let winner = last_writer(op, remote);
+            match (local, remote) {
+                (Some(local), Some(remote)) => {
+                    let merged = match winner {
+                        Resolution::Remote => merge_overrides(table, remote, local),
+                        _ => merge_overrides(table, local, remote),
+                    };
+                    (Resolution::Merged, Some(merged))
+                }
+                _ => (winner, None),
+            }
+        }
+    }
+}
+
+/// The local change is timed by its queue entry, the remote one by the
+/// cloud's `diperbarui_pada` (set by its update trigger). A remote deletion
+/// carries no time, so the surviving local row wins.
+fn last_writer(op: &SyncOperation, remote: Option<&Map<String, Value>>) -> Resolution {
+    let remote_time = remote.and_then(|r| {
+        ["diperbarui_pada", "dibuat_pada"]
+            .iter()
+            .find_map(|c| r.get(*c).and_then(|v| v.as_str()).and_then(parse_timestamp))
+    });
+
+    match (parse_timestamp(&op.created_at), remote_time) {
+        (Some(local), Some(remote)) if remote > local => Resolution::Remote,
+        _ => Resolution::Local,
+    }
+}
+
+/// Start from `winner` and carry over every field the other side overrode by hand
+fn merge_overrides(
+    table: &TableDef,
+    winner: &Map<String, Value>,
+    other: &Map<String, Value>,
+) -> Map<String, Value> {
+    let mut merged = winner.clone();
+
+    for (field, flag) in override_pairs(table) {
+        if is_set(other.get(flag)) && !is_set(winner.get(flag)) {
+            merged.insert(field.to_string(), other.get(field).cloned().unwrap_or(Value::Null));
+            merged.insert(flag.to_string(), other.get(flag).cloned().unwrap_or(Value::Null));
+        }
+    }
+
+    merged
+}
+
+/// (field, override flag) pairs such as ("saldo", "override_saldo")
+fn override_pairs(table: &TableDef) -> impl Iterator<Item = (&'static str, &'static str)> + '_ {
+    table.columns.iter().filter_map(move |c| {
+        let field = c.name.strip_prefix("override_")?;
+        table.column(field).map(|f| (f.name, c.name))
+    })
+}
+
+fn is_set(value: Option<&Value>) -> bool {
+    match value {
+        Some(Value::Bool(b)) => *b,
+        Some(Value::Number(n)) => n.as_f64().map(|n| n != 0.0).unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// Write the winning version locally. Must run with capture paused.
+fn apply_resolution(
+    conn: &Connection,
+    table: &TableDef,
+    record_id: &str,
+    remote: Option<&Map<String, Value>>,
+    resolution: Resolution,
+    merged: Option<Map<String, Value>>,
+    now: &str,
+) -> Result<(), String> {
+    // Entries queued since the conflict are replaced by the re-queued row
+    retire_pending(conn, table.name, record_id)?;
+
+    match resolution {
+        Resolution::Remote => match remote {
+            Some(row) => pull::upsert_row(conn, table, row, now),
+            None => conn
+                .execute(&schema::build_delete(table), params![record_id])
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+        },
+        Resolution::Local => keep_local(conn, table, record_id, remote),
+        Resolution::Merged => {
+            let row = merged.ok_or("Merged resolution without data")?;
+            pull::upsert_row(conn, table, &row, now)?;
+            keep_local(conn, table, record_id, remote)
+        }
+    }
+}
+
+/// Re-queue the local row on top of the remote version so the next push
+/// passes the version check
+fn keep_local(
+    conn: &Connection,
+    table: &TableDef,
+    record_id: &str,
+    remote: Option<&Map<String, Value>>,
+) -> Result<(), String> {
+    let remote_version = remote.and_then(row_version);
+    let quoted = quote_ident(table.name);
+
+    match (sync::read_row(conn, table, record_id)?, remote) {
+        (Some(_), Some(_)) => {
+            conn.execute(
+                &format!(
+                    "UPDATE {} SET sync_version = ?1, sync_status = 'pending' WHERE id = ?2",
+                    quoted
+                ),
+                params![remote_version.unwrap_or(0) + 1, record_id],
+            )
+            .map_err(|e| e.to_string())?;
+            let row = sync::read_row(conn, table, record_id)?;
+            sync::enqueue(conn, table.name, "update", record_id, row.as_ref(), remote_version)
+        }
+        (Some(_), None) => {
+            conn.execute(
+                &format!("UPDATE {} SET sync_status = 'pending' WHERE id = ?1", quoted),
+                params![record_id],
+            )
+            .map_err(|e| e.to_string())?;
+            let row = sync::read_row(conn, table, record_id)?;
+            sync::enqueue(conn, table.name, "insert", record_id, row.as_ref(), None)
+        }
+        (None, Some(_)) => sync::enqueue(conn, table.name, "delete", record_id, None, remote_version),
+        (None, None) => Ok(()),
+    }
+}
+
+/// Drop queued changes for a record that has not been pushed yet
+fn retire_pending(conn: &Connection, table: &str, record_id: &str) -> Result<(), String> {
+    conn.execute(
+        "UPDATE sync_queue SET status = 'superseded'
+         WHERE table_name = ?1 AND record_id = ?2 AND status = 'pending' AND synced_at IS NULL",
+        params![table, record_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn mark_resolved(
+    conn: &Connection,
+    conflict_id: &str,
+    resolution: Resolution,
+    now: &str,
+) -> Result<(), String> {
+    conn.execute(
+        "UPDATE sync_conflicts SET status = 'resolved', resolution = ?1, resolved_at = ?2 WHERE id = ?3",
+        params![resolution.as_str(), now, conflict_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn row_version(row: &Map<String, Value>) -> Option<i64> {
+    row.get("sync_version").and_then(|v| v.as_i64())
+}
+
+/// Timestamps come as RFC 3339 from Rust and Postgres, or as SQLite's
+/// `datetime('now')` format from older rows
+pub fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+        return Some(t.with_timezone(&Utc));
+    }
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(value, f).ok())
+        .map(|t| t.and_utc())
+}
@@ -0,0 +1,378 @@
+use crate::schema::quote_ident;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+/// Result of `check_integrity`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityReport {
+    pub checked_at: String,
+    /// No SQLite errors and no violations left
+    pub ok: bool,
+    /// Messages from `PRAGMA integrity_check`; empty when the file is sound
+    pub integrity_errors: Vec<String>,
+    pub violations: Vec<Violation>,
+    /// Rows fixed in repair mode
+    pub repaired: usize,
+}
+
+/// Rows breaking one rule
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Violation {
+    pub check: String,
+    pub table: String,
+    pub description: String,
+    pub rows: Vec<ViolationRow>,
+    /// Whether repair mode can fix it (derived values only)
+    pub repairable: bool,
+    pub repaired: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViolationRow {
+    pub id: String,
+    pub actual: Option<String>,
+    pub expected: Option<String>,
+}
+
+/// A business rule on a derived column. `find` returns (id, actual,
+/// expected) of every offending row; `repair` recomputes the column for the
+/// ids in the JSON array `?1`, with `?2` as the update time.
+/// Money columns are REAL, so differences below one cent are rounding.
+struct Rule {
+    check: &'static str,
+    table: &'static str,
+    description: &'static str,
+    find: &'static str,
+    repair: Option<&'static str>,
+}
+
+/// Checked in this order; repairs of earlier rules feed later ones (paid
+/// amount before the remainder, the remainder before the status)
+const RULES: &[Rule] = &[
+    Rule {
+        check: "hutang_terbayar",
+        table: "hutang_pembelian",
+        description: "Amount paid differs from the sum of pelunasan_hutang",
+        find: "SELECT h.id, printf('%.2f', COALESCE(h.jumlah_terbayar, 0)), printf('%.2f', COALESCE(p.total, 0))
+               FROM hutang_pembelian h
+               LEFT JOIN (SELECT id_hutang, SUM(jumlah_bayar) AS total FROM pelunasan_hutang GROUP BY id_hutang) p
+                 ON p.id_hutang = h.id
+               WHERE ABS(COALESCE(h.jumlah_terbayar, 0) - COALESCE(p.total, 0)) >= 0.01",
+        repair: Some(
+            "UPDATE hutang_pembelian
+             SET jumlah_terbayar = (SELECT COALESCE(SUM(jumlah_bayar), 0) FROM pelunasan_hutang
+                                    WHERE id_hutang = hutang_pembelian.id),
+                 diperbarui_pada = ?2
+             WHERE id IN (SELECT value FROM json_each(?1))",
+        ),
+    },
+    Rule {
+        check: "hutang_sisa",
+        table: "hutang_pembelian",
+        description: "Remaining debt is not jumlah_hutang minus the amount paid",
+        find: "SELECT id, printf('%.2f', sisa_hutang), printf('%.2f', jumlah_hutang - COALESCE(jumlah_terbayar, 0))
+               FROM hutang_pembelian
+               WHERE ABS(sisa_hutang - (jumlah_hutang - COALESCE(jumlah_terbayar, 0))) >= 0.01",
+        repair: Some(
+            "UPDATE hutang_pembelian
+             SET sisa_hutang = jumlah_hutang - COALESCE(jumlah_terbayar, 0), diperbarui_pada = ?2
+             WHERE id IN (SELECT value FROM json_each(?1))",
+        ),
+    },
+    Rule {
+        check: "hutang_status",
+        table: "hutang_pembelian",
+        description: "Status does not match the remaining debt (LUNAS exactly when nothing is left)",
+        find: "SELECT id, status, CASE WHEN sisa_hutang < 0.01 THEN 'LUNAS' ELSE 'AKTIF' END
+               FROM hutang_pembelian
+               WHERE (sisa_hutang < 0.01) != (COALESCE(status, '') = 'LUNAS')",
+        repair: Some(
+            "UPDATE hutang_pembelian
+             SET status = CASE WHEN sisa_hutang < 0.01 THEN 'LUNAS' ELSE 'AKTIF' END, diperbarui_pada = ?2
+             WHERE id IN (SELECT value FROM json_each(?1))",
+        ),
+    },
+    // The payment made at the sale is kept on penjualan.jumlah_dibayar, not
+    // as a pelunasan_piutang row; later payments only add pelunasan rows
+    Rule {
+        check: "piutang_terbayar",
+        table: "piutang_penjualan",
+        description: "Amount paid differs from the payment at the sale plus pelunasan_piutang",
+        find: "SELECT pp.id, printf('%.2f', COALESCE(pp.jumlah_terbayar, 0)),
+                      printf('%.2f', MIN(pp.jumlah_piutang, COALESCE(s.jumlah_dibayar, 0) + COALESCE(p.total, 0)))
+               FROM piutang_penjualan pp
+               JOIN penjualan s ON s.id = pp.id_penjualan
+               LEFT JOIN (SELECT id_piutang, SUM(jumlah_bayar) AS total FROM pelunasan_piutang GROUP BY id_piutang) p
+                 ON p.id_piutang = pp.id
+               WHERE ABS(COALESCE(pp.jumlah_terbayar, 0)
+                         - MIN(pp.jumlah_piutang, COALESCE(s.jumlah_dibayar, 0) + COALESCE(p.total, 0))) >= 0.01",
+        repair: Some(
+            "UPDATE piutang_penjualan
+             SET jumlah_terbayar = MIN(jumlah_piutang,
+                     COALESCE((SELECT jumlah_dibayar FROM penjualan WHERE id = piutang_penjualan.id_penjualan), 0)
+                     + COALESCE((SELECT SUM(jumlah_bayar) FROM pelunasan_piutang
+                                 WHERE id_piutang = piutang_penjualan.id), 0)),
+                 diperbarui_pada = ?2
+             WHERE id IN (SELECT value FROM json_each(?1))",
+        ),
+    },
+    Rule {
+        check: "piutang_sisa",
+        table: "piutang_penjualan",
+        description: "Remaining receivable is not jumlah_piutang minus the amount paid",
+        find: "SELECT id, printf('%.2f', sisa_piutang), printf('%.2f', jumlah_piutang - COALESCE(jumlah_terbayar, 0))
+               FROM piutang_penjualan
+               WHERE ABS(sisa_piutang - (jumlah_piutang - COALESCE(jumlah_terbayar, 0))) >= 0.01",
+        repair: Some(
+            "UPDATE piutang_penjualan
+             SET sisa_piutang = jumlah_piutang - COALESCE(jumlah_terbayar, 0), diperbarui_pada = ?2
+             WHERE id IN (SELECT value FROM json_each(?1))",
+        ),
+    },
+    Rule {
+        check: "piutang_status",
+        table: "piutang_penjualan",
+        description: "Status does not match the remaining receivable (LUNAS exactly when nothing is left)",
+        find: "SELECT id, status,
+                      CASE WHEN sisa_piutang < 0.01 THEN 'LUNAS'
+                           WHEN COALESCE(jumlah_terbayar, 0) > 0 THEN 'SEBAGIAN' ELSE 'AKTIF' END
+               FROM piutang_penjualan
+               WHERE (sisa_piutang < 0.01) != (COALESCE(status, '') = 'LUNAS')",
+        repair: Some(
+            "UPDATE piutang_penjualan
+             SET status = CASE WHEN sisa_piutang < 0.01 THEN 'LUNAS'
+                               WHEN COALESCE(jumlah_terbayar, 0) > 0 THEN 'SEBAGIAN' ELSE 'AKTIF' END,
+                 diperbarui_pada = ?2
+             WHERE id IN (SELECT value FROM json_each(?1))",
+        ),
+    },
+    Rule {
+        check: "penjualan_total",
+        table: "penjualan",
+        description: "Sale total differs from the sum of its item subtotals",
+        find: "SELECT p.id, printf('%.2f', p.total_jumlah), printf('%.2f', i.total)
+               FROM penjualan p
+               JOIN (SELECT penjualan_id, SUM(subtotal) AS total FROM item_penjualan GROUP BY penjualan_id) i
+                 ON i.penjualan_id = p.id
+               WHERE ABS(p.total_jumlah - i.total) >= 0.01",
+        repair: Some(
+            "UPDATE penjualan
+             SET total_jumlah = (SELECT SUM(subtotal) FROM item_penjualan WHERE penjualan_id = penjualan.id),
+                 diperbarui_pada = ?2
+             WHERE id IN (SELECT value FROM json_each(?1))",
+        ),
+    },
+    Rule {
+        check: "penjualan_tanpa_item",
+        table: "penjualan",
+        description: "Sale has no items",
+        find: "SELECT p.id, printf('%.2f', p.total_jumlah), NULL
+               FROM penjualan p
+               WHERE NOT EXISTS (SELECT 1 FROM item_penjualan i WHERE i.penjualan_id = p.id)",
+        repair: None,
+    },
+    Rule {
+        check: "order_produksi_total_item",
+        table: "order_produksi",
+        description: "Item count differs from the number of item_produksi rows",
+        find: "SELECT o.id, COALESCE(o.total_item, 0),
+                      (SELECT COUNT(*) FROM item_produksi i WHERE i.order_produksi_id = o.id)
+               FROM order_produksi o
+               WHERE COALESCE(o.total_item, 0) != (SELECT COUNT(*) FROM item_produksi i WHERE i.order_produksi_id = o.id)",
+        repair: Some(
+            "UPDATE order_produksi
+             SET total_item = (SELECT COUNT(*) FROM item_produksi WHERE order_produksi_id = order_produksi.id),
+                 diperbarui_pada = ?2
+             WHERE id IN (SELECT value FROM json_each(?1))",
+        ),
+    },
+];
+
+/// Run SQLite's own checks and the business rules. With `repair`, derived
+/// values that drifted are recomputed in one transaction (the fixes are
+/// queued for sync like any other edit); foreign key problems and sales
+/// without items are only reported.
+pub fn check(conn: &mut Connection, repair: bool) -> Result<IntegrityReport, String> {
+    let integrity_errors: Vec<String> = conn
+        .prepare("PRAGMA integrity_check")
+        .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<_>>())
+        .map_err(|e| e.to_string())?;
+    let integrity_errors: Vec<String> = integrity_errors.into_iter().filter(|m| m != "ok").collect();
+
+    let mut violations = foreign_key_violations(conn)?;
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut repaired = 0;
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for rule in RULES {
+        let rows = find(&tx, rule.find)?;
+        if rows.is_empty() {
+            continue;
+        }
+
+        let mut fixed = false;
+        if let (true, Some(sql)) = (repair, rule.repair) {
+            let ids = serde_json::to_string(&rows.iter().map(|r| &r.id).collect::<Vec<_>>())
+                .map_err(|e| e.to_string())?;
+            repaired += tx
+                .execute(sql, params![ids, now])
+                .map_err(|e| format!("Repairing {} failed: {}", rule.check, e))?;
+            fixed = true;
+        }
+
+        violations.push(Violation {
+            check: rule.check.to_string(),
+            table: rule.table.to_string(),
+            description: rule.description.to_string(),
+            rows,
+            repairable: rule.repair.is_some(),
+            repaired: fixed,
+        });
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(IntegrityReport {
+        checked_at: now,
+        ok: integrity_errors.is_empty() && violations.iter().all(|v| v.repaired),
+        integrity_errors,
+        violations,
+        repaired,
+    })
+}
+
+fn find(conn: &Connection, sql: &str) -> Result<Vec<ViolationRow>, String> {
+    let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(ViolationRow {
+                id: row.get(0)?,
+                actual: text(row.get_ref(1)?),
+                expected: text(row.get_ref(2)?),
+            })
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(|e| e.to_string())?;
+
+    Ok(rows)
+}
+
+fn text(value: rusqlite::types::ValueRef<'_>) -> Option<String> {
+    use rusqlite::types::ValueRef;
+    match value {
+        ValueRef::Null => None,
+        ValueRef::Integer(i) => Some(i.to_string()),
+        ValueRef::Real(f) => Some(f.to_string()),
+        ValueRef::Text(t) | ValueRef::Blob(t) => Some(String::from_utf8_lossy(t).into_owned()),
+    }
+}
+
+/// `PRAGMA foreign_key_check`, one violation per table and missing parent,
+/// with the ids of the orphaned rows
+fn foreign_key_violations(conn: &Connection) -> Result<Vec<Violation>, String> {
+    let mut stmt = conn.prepare("PRAGMA foreign_key_check").map_err(|e| e.to_string())?;
+    let found: Vec<(String, Option<i64>, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(|e| e.to_string())?;
+
+    let mut violations: Vec<Violation> = Vec::new();
+    for (table, rowid, parent) in found {
+        let id = match rowid {
+            Some(rowid) => conn
+                .query_row(
+                    &format!("SELECT CAST(id AS TEXT) FROM {} WHERE rowid = ?1", quote_ident(&table)),
+                    params![rowid],
+                    |row| row.get(0),
+                )
+                .unwrap_or_else(|_| format!("rowid {}", rowid)),
+            None => "(no rowid)".to_string(),
+        };
+
+        let check = format!("foreign_key:{}", parent);
+        match violations.iter_mut().find(|v| v.table == table && v.check == check) {
+            Some(violation) => violation.rows.push(ViolationRow { id, actual: None, expected: None }),
+            None => violations.push(Violation {
+                description: format!("References a missing {} row", parent),
+                check,
+                table,
+                rows: vec![ViolationRow { id, actual: None, expected: None }],
+                repairable: false,
+                repaired: false,
+            }),
+        }
+    }
+
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_postgrest::template_db;
+    use crate::pos::{create_sale, CreateSaleRequest, SaleLine};
+
+    #[test]
+    fn down_payment_plus_one_instalment_is_consistent_on_an_upgraded_database() {
+        let db = template_db();
+        let mut guard = db.lock().unwrap();
+        let conn = guard.as_mut().unwrap();
+        conn.execute_batch(
+            "INSERT INTO barang (id, nama, satuan_dasar, jumlah_stok) VALUES ('b-test', 'Stiker', 'lembar', 10);
+             INSERT INTO harga_barang_satuan (id, barang_id, nama_satuan, faktor_konversi, harga_jual)
+             VALUES ('h-test', 'b-test', 'lembar', 1, 100000);",
+        )
+        .unwrap();
+        let sale = create_sale(
+            conn,
+            CreateSaleRequest {
+                pelanggan_id: None,
+                items: vec![SaleLine {
+                    barang_id: "b-test".to_string(),
+                    harga_satuan_id: "h-test".to_string(),
+                    jumlah: 1.0,
+                    panjang: None,
+                    lebar: None,
+                    jumlah_pcs: None,
+                    pembulatan: false,
+                    finishing: Vec::new(),
+                }],
+                jumlah_dibayar: 40_000.0,
+                metode_pembayaran: "DOWN_PAYMENT".to_string(),
+                catatan: None,
+                kasir_id: None,
+                tanggal: Some("2026-10-01".to_string()),
+                prioritas: None,
+            },
+        )
+        .unwrap();
+
+        // One instalment, recorded the way the receivable payment page does
+        conn.execute_batch(&format!(
+            "INSERT INTO pelunasan_piutang (id, id_piutang, tanggal_bayar, jumlah_bayar)
+             SELECT 'bayar-1', id, '2026-10-05', 30000 FROM piutang_penjualan WHERE id_penjualan = '{0}';
+             UPDATE piutang_penjualan
+             SET jumlah_terbayar = jumlah_terbayar + 30000, sisa_piutang = sisa_piutang - 30000
+             WHERE id_penjualan = '{0}';",
+            sale.id
+        ))
+        .unwrap();
+
+        let report = check(conn, false).unwrap();
+        let (terbayar, sisa): (f64, f64) = conn
+            .query_row(
+                "SELECT jumlah_terbayar, sisa_piutang FROM piutang_penjualan WHERE id_penjualan = ?1",
+                params![sale.id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((terbayar, sisa), (70_000.0, 30_000.0));
+        assert!(
+            report.violations.iter().all(|v| !v.check.starts_with("piutang_")),
+            "{:?}",
+            report.violations
+        );
+    }
+}
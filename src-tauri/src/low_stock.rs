@@ -0,0 +1,273 @@
+use crate::AppState;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+use tokio::sync::Notify;
+
+/// Catches stock lowered outside this PC (pulled sales, edits in the webview)
+const CHECK_INTERVAL: Duration = Duration::from_secs(30 * 60);
+const STARTUP_DELAY: Duration = Duration::from_secs(20);
+
+/// Sales window used for the velocity in `low_stock_report`
+const DEFAULT_SALES_DAYS: u32 = 30;
+/// Days of sales a suggested reorder should cover
+const DEFAULT_COVER_DAYS: u32 = 14;
+/// Names listed in one notification before "dan N lainnya"
+const MAX_NAMES: usize = 5;
+
+/// Shared state of the low-stock watcher, managed by Tauri
+#[derive(Default)]
+pub struct StockWatcher {
+    wake: Notify,
+}
+
+impl StockWatcher {
+    /// Check now, e.g. right after stock was decremented
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+}
+
+/// A tracked barang at or below its minimum; payload of `stock://low`
+#[derive(Debug, Clone, Serialize)]
+pub struct LowStockItem {
+    pub barang_id: String,
+    pub nama: String,
+    pub satuan_dasar: Option<String>,
+    pub jumlah_stok: f64,
+    pub level_stok_minimum: f64,
+}
+
+/// Result of `low_stock_report`
+#[derive(Debug, Serialize)]
+pub struct LowStockReport {
+    pub generated_at: String,
+    pub hari_penjualan: u32,
+    pub hari_persediaan: u32,
+    pub items: Vec<ReorderSuggestion>,
+}
+
+/// A low barang with a reorder quantity from its recent sales
+#[derive(Debug, Serialize)]
+pub struct ReorderSuggestion {
+    pub barang_id: String,
+    pub nama: String,
+    pub kategori_nama: Option<String>,
+    pub satuan_dasar: Option<String>,
+    pub jumlah_stok: f64,
+    pub level_stok_minimum: f64,
+    /// Taken out of stock by sales in the window (PENJUALAN movements), in
+    /// `satuan_dasar`, so dimensioned barang count their area
+    pub terjual: f64,
+    pub rata_rata_harian: f64,
+    /// Days until the stock runs out at the current pace; None without sales
+    pub hari_tersisa: Option<f64>,
+    /// In `satuan_dasar`
+    pub saran_pesan: f64,
+    /// Largest unit of the barang (usually how it is bought) and the
+    /// suggestion rounded up to whole units of it
+    pub satuan_beli: Option<String>,
+    pub saran_pesan_satuan_beli: Option<f64>,
+}
+
+/// Spawn the watcher: checks on an interval and whenever it is woken.
+/// Each barang is notified once when it drops to its minimum, and again
+/// only after it has been restocked above it.
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(STARTUP_DELAY).await;
+
+        loop {
+            if let Err(e) = check(&app) {
+                println!("Low-stock check skipped: {}", e);
+            }
+
+            let watcher = app.state::<StockWatcher>();
+            tokio::select! {
+                _ = tokio::time::sleep(CHECK_INTERVAL) => {}
+                _ = watcher.wake.notified() => {}
+            }
+        }
+    });
+}
+
+/// Find new crossings and notify about them
+fn check(app: &AppHandle) -> Result<(), String> {
+    let crossed = {
+        let state = app.state::<AppState>();
+        let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+        let conn = db_guard.as_mut().ok_or("Database not initialized")?;
+        detect_crossings(conn)?
+    };
+    if crossed.is_empty() {
+        return Ok(());
+    }
+
+    let (title, body) = match crossed.as_slice() {
+        [item] => (
+            format!("Stok menipis: {}", item.nama),
+            format!(
+                "Sisa {} {} (minimum {})",
+                quantity(item.jumlah_stok),
+                item.satuan_dasar.as_deref().unwrap_or(""),
+                quantity(item.level_stok_minimum)
+            ),
+        ),
+        items => {
+            let names: Vec<&str> = items.iter().take(MAX_NAMES).map(|i| i.nama.as_str()).collect();
+            let more = items.len().saturating_sub(MAX_NAMES);
+            let more = if more > 0 { format!(" dan {} lainnya", more) } else { String::new() };
+            (
+                format!("{} barang stok menipis", items.len()),
+                format!("{}{}", names.join(", "), more),
+            )
+        }
+    };
+
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        println!("Low-stock notification failed: {}", e);
+    }
+    app.emit("stock://low", &crossed).ok();
+    Ok(())
+}
+
+/// Tracked barang that dropped to their minimum since the last check.
+/// Barang back above it are forgotten so their next drop alerts again.
+/// A minimum of 0 means none is set.
+pub fn detect_crossings(conn: &mut Connection) -> Result<Vec<LowStockItem>, String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let low: Vec<LowStockItem> = {
+        let mut stmt = tx
+            .prepare(
+                "SELECT id, nama, satuan_dasar, COALESCE(jumlah_stok, 0), level_stok_minimum
+                 FROM barang
+                 WHERE COALESCE(lacak_inventori_status, 1) = 1
+                   AND level_stok_minimum > 0
+                   AND COALESCE(jumlah_stok, 0) <= level_stok_minimum
+                 ORDER BY nama",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(LowStockItem {
+                    barang_id: row.get(0)?,
+                    nama: row.get(1)?,
+                    satuan_dasar: row.get(2)?,
+                    jumlah_stok: row.get(3)?,
+                    level_stok_minimum: row.get(4)?,
+                })
+            })
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(|e| e.to_string())?;
+        rows
+    };
+
+    let ids = serde_json::to_string(&low.iter().map(|i| &i.barang_id).collect::<Vec<_>>())
+        .map_err(|e| e.to_string())?;
+    tx.execute(
+        "DELETE FROM low_stock_alerts WHERE barang_id NOT IN (SELECT value FROM json_each(?1))",
+        params![ids],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut crossed = Vec::new();
+    for item in low {
+        let inserted = tx
+            .execute(
+                "INSERT OR IGNORE INTO low_stock_alerts (barang_id, jumlah_stok, level_stok_minimum, alerted_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![item.barang_id, item.jumlah_stok, item.level_stok_minimum, now],
+            )
+            .map_err(|e| e.to_string())?;
+        if inserted > 0 {
+            crossed.push(item);
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(crossed)
+}
+
+/// Tracked barang at or below their minimum, lowest relative to it first,
+/// with a reorder suggestion: enough for the minimum plus `cover_days` of the
+/// average daily sales over the last `days` (at least one more minimum)
+pub fn report(conn: &Connection, days: Option<u32>, cover_days: Option<u32>) -> Result<LowStockReport, String> {
+    let days = days.unwrap_or(DEFAULT_SALES_DAYS);
+    let cover_days = cover_days.unwrap_or(DEFAULT_COVER_DAYS);
+    if days == 0 || cover_days == 0 {
+        return Err("Jumlah hari harus lebih dari 0".to_string());
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT b.id, b.nama, k.nama, b.satuan_dasar, COALESCE(b.jumlah_stok, 0), b.level_stok_minimum,
+                    COALESCE((SELECT -SUM(m.jumlah)
+                              FROM mutasi_stok m
+                              WHERE m.barang_id = b.id AND m.jenis = 'PENJUALAN'
+                                AND julianday(m.tanggal) >= julianday('now', ?1)), 0),
+                    (SELECT h.nama_satuan FROM harga_barang_satuan h
+                     WHERE h.barang_id = b.id AND h.faktor_konversi > 0
+                     ORDER BY h.faktor_konversi DESC LIMIT 1),
+                    (SELECT MAX(h.faktor_konversi) FROM harga_barang_satuan h WHERE h.barang_id = b.id)
+             FROM barang b
+             LEFT JOIN kategori_barang k ON k.id = b.kategori_id
+             WHERE COALESCE(b.lacak_inventori_status, 1) = 1
+               AND b.level_stok_minimum > 0
+               AND COALESCE(b.jumlah_stok, 0) <= b.level_stok_minimum
+             ORDER BY COALESCE(b.jumlah_stok, 0) / b.level_stok_minimum, b.nama",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let items = stmt
+        .query_map(params![format!("-{} days", days)], |row| {
+            let jumlah_stok: f64 = row.get(4)?;
+            let level_stok_minimum: f64 = row.get(5)?;
+            let terjual: f64 = row.get(6)?;
+            let satuan_beli: Option<String> = row.get(7)?;
+            let faktor_beli: Option<f64> = row.get(8)?;
+
+            let rata_rata_harian = terjual / days as f64;
+            let target = level_stok_minimum + (rata_rata_harian * cover_days as f64).max(level_stok_minimum);
+            let saran_pesan = round2((target - jumlah_stok).max(0.0));
+            let saran_pesan_satuan_beli = match faktor_beli {
+                Some(faktor) if faktor > 1.0 && satuan_beli.is_some() => Some((saran_pesan / faktor).ceil()),
+                _ => None,
+            };
+
+            Ok(ReorderSuggestion {
+                barang_id: row.get(0)?,
+                nama: row.get(1)?,
+                kategori_nama: row.get(2)?,
+                satuan_dasar: row.get(3)?,
+                jumlah_stok,
+                level_stok_minimum,
+                terjual,
+                rata_rata_harian: round2(rata_rata_harian),
+                hari_tersisa: (rata_rata_harian > 0.0).then(|| round2(jumlah_stok.max(0.0) / rata_rata_harian)),
+                saran_pesan,
+                satuan_beli: saran_pesan_satuan_beli.and(satuan_beli),
+                saran_pesan_satuan_beli,
+            })
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(|e| e.to_string())?;
+
+    Ok(LowStockReport {
+        generated_at: chrono::Utc::now().to_rfc3339(),
+        hari_penjualan: days,
+        hari_persediaan: cover_days,
+        items,
+    })
+}
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// Quantity for display: at most two decimals, no trailing zeros
+fn quantity(value: f64) -> String {
+    round2(value).to_string()
+}
@@ -1,9 +1,34 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod archive;
+mod auth;
+mod backup;
+mod batch;
+mod bootstrap;
+mod capture;
+mod config;
+mod conflict;
+mod integrity;
+mod low_stock;
+mod migrations;
+#[cfg(test)]
+mod mock_postgrest;
+mod opname;
+mod pos;
+mod pricing;
+mod pull;
+mod purchase;
+mod queue;
+mod scheduler;
+mod schema;
+mod secrets;
+mod settings;
+mod stock;
 mod sync;
 
 use rusqlite::{params, Connection, Result as SqlResult};
+use schema::DbError;
 use std::sync::Mutex;
 use tauri::{Manager, State};
 use uuid::Uuid;
@@ -14,7 +39,7 @@ struct AppState {
 }
 
 // Initialize database connection
-fn init_database(app_handle: &tauri::AppHandle) -> SqlResult<Connection> {
+fn init_database(app_handle: &tauri::AppHandle) -> Result<Connection, String> {
     let app_data_dir = app_handle
         .path()
         .app_data_dir()
@@ -23,73 +48,39 @@ fn init_database(app_handle: &tauri::AppHandle) -> SqlResult<Connection> {
     // Create directory if it doesn't exist
     std::fs::create_dir_all(&app_data_dir).expect("Failed to create app data directory");
     
+    // Key for the credentials stored encrypted in app_settings
+    secrets::init(&app_data_dir)?;
+    
     let db_path = app_data_dir.join("gemiprint.db");
     println!("Database path: {:?}", db_path);
     
-    // Check if database doesn't exist yet (first run)
-    let is_first_run = !db_path.exists();
-    
-    if is_first_run {
-        println!("First run detected - copying template database...");
-        
-        // Embedded database template (from /database/gemiprint.db)
-        let template_db = include_bytes!("../../database/gemiprint.db");
-        
-        // Write template to app data directory
-        std::fs::write(&db_path, template_db)
-            .expect("Failed to copy template database");
-        
-        println!("Template database copied successfully with admin user data");
-    } else {
+    if db_path.exists() {
         println!("Using existing database");
+    } else {
+        println!("First run detected - creating new database...");
     }
     
-    let conn = Connection::open(db_path)?;
+    open_database(&db_path)
+}
+
+// Open the database file with the app's settings, migrated and with sync capture
+// (also used to reopen it after a restore)
+fn open_database(db_path: &std::path::Path) -> Result<Connection, String> {
+    let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
     
     // Enable foreign keys (doesn't return results)
-    conn.execute("PRAGMA foreign_keys = ON", [])?;
+    conn.execute("PRAGMA foreign_keys = ON", []).map_err(|e| e.to_string())?;
     
     // Set WAL mode (returns results, need to use pragma_update or query_row)
-    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.pragma_update(None, "journal_mode", "WAL").map_err(|e| e.to_string())?;
     
-    // Initialize schema if needed
-    init_schema(&conn)?;
+    // Bring schema up to date (creates everything on a fresh database)
+    migrations::run_migrations(&mut conn)?;
     
-    Ok(conn)
-}
-
-// Initialize database schema
-fn init_schema(conn: &Connection) -> SqlResult<()> {
-    // Check if database is already initialized
-    let table_exists: bool = conn
-        .query_row(
-            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='profil'",
-            [],
-            |row| {
-                let count: i64 = row.get(0)?;
-                Ok(count > 0)
-            },
-        )
-        .unwrap_or(false);
+    // Queue every write to synced tables automatically
+    capture::install_triggers(&mut conn)?;
     
-    if !table_exists {
-        println!("Initializing database schema...");
-        
-        // For development, just skip schema initialization if file has issues
-        // Schema should be initialized manually or via migration tool
-        println!("NOTE: Skipping automatic schema initialization.");
-        println!("Please ensure database is initialized manually if needed.");
-        
-        // Uncomment below to force schema initialization:
-        /*
-        let schema = include_str!("../../database/sqlite-schema.sql");
-        conn.execute_batch(schema)?;
-        */
-    } else {
-        println!("Database already initialized");
-    }
-    
-    Ok(())
+    Ok(conn)
 }
 
 // Tauri command: Execute query and return all rows
@@ -112,7 +103,7 @@ async fn db_query(
     // Convert JSON params to rusqlite params
     let rusqlite_params: Vec<rusqlite::types::Value> = params
         .iter()
-        .map(|v| json_to_rusqlite_value(v))
+        .map(json_to_rusqlite_value)
         .collect();
     
     let rows = stmt
@@ -153,7 +144,7 @@ async fn db_query_one(
     
     let rusqlite_params: Vec<rusqlite::types::Value> = params
         .iter()
-        .map(|v| json_to_rusqlite_value(v))
+        .map(json_to_rusqlite_value)
         .collect();
     
     let result = stmt
@@ -173,97 +164,64 @@ async fn db_query_one(
     }
 }
 
-// Tauri command: Insert record
+// Tauri command: Insert record (table and columns checked against the schema registry)
 #[tauri::command]
 async fn db_insert(
     state: State<'_, AppState>,
     table: String,
     data: serde_json::Value,
-) -> Result<String, String> {
+) -> Result<String, DbError> {
     let db_guard = state.db.lock().map_err(|e| e.to_string())?;
     let conn = db_guard.as_ref().ok_or("Database not initialized")?;
     
-    let obj = data.as_object().ok_or("Data must be an object")?;
-    
-    // Get or generate ID
-    let id = if let Some(id_value) = obj.get("id") {
-        if let Some(id_str) = id_value.as_str() {
-            id_str.to_string()
-        } else {
-            Uuid::new_v4().to_string()
-        }
-    } else {
-        Uuid::new_v4().to_string()
-    };
-    
-    let columns: Vec<String> = obj.keys().cloned().collect();
-    let placeholders: Vec<String> = (0..columns.len()).map(|_| "?".to_string()).collect();
+    let table = schema::table(&table)?;
+    let obj = data.as_object().ok_or(DbError::InvalidData {
+        message: "Data must be an object".to_string(),
+    })?;
     
-    let sql = format!(
-        "INSERT INTO {} ({}) VALUES ({})",
-        table,
-        columns.join(", "),
-        placeholders.join(", ")
-    );
+    let (id, sql, values) = schema::build_insert(table, obj)?;
     
-    let values: Vec<rusqlite::types::Value> = columns
-        .iter()
-        .map(|col| json_to_rusqlite_value(obj.get(col).unwrap()))
-        .collect();
-    
-    conn.execute(&sql, rusqlite::params_from_iter(values.iter()))
-        .map_err(|e| e.to_string())?;
+    conn.execute(&sql, rusqlite::params_from_iter(values.iter()))?;
     
     Ok(id)
 }
 
-// Tauri command: Update record
+// Tauri command: Update record (table and columns checked against the schema registry)
 #[tauri::command]
 async fn db_update(
     state: State<'_, AppState>,
     table: String,
     id: String,
     data: serde_json::Value,
-) -> Result<(), String> {
+) -> Result<(), DbError> {
     let db_guard = state.db.lock().map_err(|e| e.to_string())?;
     let conn = db_guard.as_ref().ok_or("Database not initialized")?;
     
-    let obj = data.as_object().ok_or("Data must be an object")?;
+    let table = schema::table(&table)?;
+    let obj = data.as_object().ok_or(DbError::InvalidData {
+        message: "Data must be an object".to_string(),
+    })?;
     
-    let set_clauses: Vec<String> = obj.keys().map(|k| format!("{} = ?", k)).collect();
+    let (sql, values) = schema::build_update(table, &id, obj)?;
     
-    let sql = format!(
-        "UPDATE {} SET {} WHERE id = ?",
-        table,
-        set_clauses.join(", ")
-    );
-    
-    let mut values: Vec<rusqlite::types::Value> = obj
-        .values()
-        .map(|v| json_to_rusqlite_value(v))
-        .collect();
-    values.push(rusqlite::types::Value::Text(id));
-    
-    conn.execute(&sql, rusqlite::params_from_iter(values.iter()))
-        .map_err(|e| e.to_string())?;
+    conn.execute(&sql, rusqlite::params_from_iter(values.iter()))?;
     
     Ok(())
 }
 
-// Tauri command: Delete record
+// Tauri command: Delete record (table checked against the schema registry)
 #[tauri::command]
 async fn db_delete(
     state: State<'_, AppState>,
     table: String,
     id: String,
-) -> Result<(), String> {
+) -> Result<(), DbError> {
     let db_guard = state.db.lock().map_err(|e| e.to_string())?;
     let conn = db_guard.as_ref().ok_or("Database not initialized")?;
     
-    let sql = format!("DELETE FROM {} WHERE id = ?", table);
+    let table = schema::table(&table)?;
     
-    conn.execute(&sql, params![id])
-        .map_err(|e| e.to_string())?;
+    conn.execute(&schema::build_delete(table), params![id])?;
     
     Ok(())
 }
@@ -280,7 +238,7 @@ async fn db_execute(
     
     let rusqlite_params: Vec<rusqlite::types::Value> = params
         .iter()
-        .map(|v| json_to_rusqlite_value(v))
+        .map(json_to_rusqlite_value)
         .collect();
     
     let affected = conn
@@ -290,6 +248,206 @@ async fn db_execute(
     Ok(affected)
 }
 
+// Tauri command: Run several statements atomically (all or nothing)
+#[tauri::command]
+async fn db_transaction(
+    state: State<'_, AppState>,
+    operations: Vec<batch::BatchOp>,
+) -> Result<batch::BatchResult, DbError> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_mut().ok_or("Database not initialized")?;
+    
+    batch::run_batch(conn, operations)
+}
+
+// Tauri command: POS checkout (prices, stock, receivable and sync queue in one transaction)
+#[tauri::command]
+async fn create_sale(
+    state: State<'_, AppState>,
+    watcher: State<'_, low_stock::StockWatcher>,
+    sale: pos::CreateSaleRequest,
+) -> Result<pos::CreateSaleResult, String> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_mut().ok_or("Database not initialized")?;
+    
+    let result = pos::create_sale(conn, sale)?;
+    watcher.wake();
+    Ok(result)
+}
+
+// Tauri command: Record a purchase (stock-in, payable and cash book in one transaction)
+#[tauri::command]
+async fn create_purchase(
+    state: State<'_, AppState>,
+    purchase: purchase::CreatePurchaseRequest,
+) -> Result<purchase::CreatePurchaseResult, String> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_mut().ok_or("Database not initialized")?;
+    
+    purchase::create_purchase(conn, purchase)
+}
+
+// Tauri command: stock movements of one barang, newest first
+#[tauri::command]
+async fn get_stock_movements(
+    state: State<'_, AppState>,
+    barang_id: String,
+    limit: Option<i64>,
+) -> Result<Vec<stock::MutasiStok>, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    stock::history(conn, &barang_id, limit)
+}
+
+// Tauri command: manual stock correction or production waste, recorded in the ledger
+#[tauri::command]
+async fn adjust_stock(
+    state: State<'_, AppState>,
+    watcher: State<'_, low_stock::StockWatcher>,
+    adjustment: stock::AdjustStockRequest,
+) -> Result<stock::MutasiStok, String> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_mut().ok_or("Database not initialized")?;
+    
+    let movement = stock::adjust(conn, adjustment)?;
+    watcher.wake();
+    Ok(movement)
+}
+
+// Tauri command: compare jumlah_stok with the stock ledger, resetting it when overwrite is set
+#[tauri::command]
+async fn recompute_stock(
+    state: State<'_, AppState>,
+    watcher: State<'_, low_stock::StockWatcher>,
+    barang_id: Option<String>,
+    overwrite: Option<bool>,
+) -> Result<Vec<stock::StockRecompute>, String> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_mut().ok_or("Database not initialized")?;
+    
+    let results = stock::recompute(conn, barang_id.as_deref(), overwrite.unwrap_or(false))?;
+    watcher.wake();
+    Ok(results)
+}
+
+// Tauri command: open a stock opname session for the given categories
+#[tauri::command]
+async fn open_stock_opname(
+    state: State<'_, AppState>,
+    request: opname::OpenOpnameRequest,
+) -> Result<opname::OpnameSession, String> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_mut().ok_or("Database not initialized")?;
+    
+    opname::open(conn, request)
+}
+
+// Tauri command: record a counted quantity in any unit of the barang
+#[tauri::command]
+async fn record_opname_count(
+    state: State<'_, AppState>,
+    count: opname::RecordCountRequest,
+) -> Result<opname::OpnameItem, String> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_mut().ok_or("Database not initialized")?;
+    
+    opname::record_count(conn, count)
+}
+
+// Tauri command: a stock opname session with variances
+#[tauri::command]
+async fn get_stock_opname(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<opname::OpnameSession, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    opname::get(conn, &id)
+}
+
+// Tauri command: list stock opname sessions
+#[tauri::command]
+async fn list_stock_opname(state: State<'_, AppState>) -> Result<Vec<opname::OpnameSummary>, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    opname::list(conn)
+}
+
+// Tauri command: post counted variances as stock adjustments (one transaction)
+#[tauri::command]
+async fn post_stock_opname(
+    state: State<'_, AppState>,
+    watcher: State<'_, low_stock::StockWatcher>,
+    id: String,
+    dibuat_oleh: Option<String>,
+) -> Result<opname::OpnameSession, String> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_mut().ok_or("Database not initialized")?;
+    
+    let session = opname::post(conn, &id, dibuat_oleh.as_deref())?;
+    watcher.wake();
+    Ok(session)
+}
+
+// Tauri command: cancel a draft stock opname session
+#[tauri::command]
+async fn cancel_stock_opname(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_mut().ok_or("Database not initialized")?;
+    
+    opname::cancel(conn, &id)
+}
+
+// Tauri command: tracked barang at or below their minimum, with reorder suggestions
+#[tauri::command]
+async fn low_stock_report(
+    state: State<'_, AppState>,
+    days: Option<u32>,
+    cover_days: Option<u32>,
+) -> Result<low_stock::LowStockReport, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    low_stock::report(conn, days, cover_days)
+}
+
+// Tauri command: Price a print job by its dimensions (m)
+#[tauri::command]
+async fn quote_area_price(
+    state: State<'_, AppState>,
+    request: pricing::AreaQuoteRequest,
+) -> Result<pricing::AreaQuote, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    pricing::quote(conn, &request)
+}
+
+// Tauri command: Get the area pricing settings
+#[tauri::command]
+async fn get_area_pricing_config(state: State<'_, AppState>) -> Result<pricing::AreaPricingConfig, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    pricing::load_config(conn)
+}
+
+// Tauri command: Save the area pricing settings
+#[tauri::command]
+async fn update_area_pricing_config(
+    state: State<'_, AppState>,
+    config: pricing::AreaPricingConfig,
+) -> Result<pricing::AreaPricingConfig, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    pricing::save_config(conn, &config)?;
+    pricing::load_config(conn)
+}
+
 // Helper: Convert JSON value to rusqlite Value
 fn json_to_rusqlite_value(value: &serde_json::Value) -> rusqlite::types::Value {
     match value {
@@ -356,20 +514,10 @@ async fn queue_sync_operation(
     let db_guard = state.db.lock().map_err(|e| e.to_string())?;
     let conn = db_guard.as_ref().ok_or("Database not initialized")?;
     
-    // Create sync_queue table if not exists
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS sync_queue (
-            id TEXT PRIMARY KEY,
-            table_name TEXT NOT NULL,
-            operation TEXT NOT NULL,
-            record_id TEXT,
-            data TEXT,
-            created_at TEXT NOT NULL,
-            synced_at TEXT,
-            status TEXT DEFAULT 'pending'
-        )",
-        [],
-    ).map_err(|e| e.to_string())?;
+    // Writes to synced tables are already queued by the capture triggers
+    if schema::table(&table).map(|t| t.is_synced()).unwrap_or(false) {
+        return Ok(());
+    }
     
     // Insert sync operation
     let id = Uuid::new_v4().to_string();
@@ -411,7 +559,7 @@ async fn count_pending_sync(
     // Count pending operations
     let count: i64 = conn
         .query_row(
-            "SELECT COUNT(*) FROM sync_queue WHERE synced_at IS NULL",
+            "SELECT COUNT(*) FROM sync_queue WHERE synced_at IS NULL AND status = 'pending'",
             [],
             |row| row.get(0),
         )
@@ -424,73 +572,442 @@ async fn count_pending_sync(
 #[tauri::command]
 async fn sync_to_cloud(
     state: State<'_, AppState>,
+    scheduler: State<'_, scheduler::SyncScheduler>,
 ) -> Result<serde_json::Value, String> {
-    // Step 1: Get operations from DB (synchronous, with lock)
-    let operations = {
-        let db_guard = state.db.lock().map_err(|e| e.to_string())?;
-        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
-        
-        // Check if sync_queue table exists
-        let table_exists: bool = conn
-            .query_row(
-                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='sync_queue'",
-                [],
-                |row| {
-                    let count: i64 = row.get(0)?;
-                    Ok(count > 0)
-                },
-            )
-            .unwrap_or(false);
-        
-        if !table_exists {
+    // Never push the same operations twice alongside the background sync
+    let _guard = match scheduler.try_begin() {
+        Some(guard) => guard,
+        None => {
             return Ok(serde_json::json!({
                 "synced": 0,
                 "failed": 0,
-                "message": "No sync queue table found"
+                "message": "Sync already in progress"
             }));
         }
-        
-        // Get operations
-        sync::get_operations_for_sync(conn)?
-    }; // Lock released here
-    
-    if operations.is_empty() {
-        return Ok(serde_json::json!({
-            "synced": 0,
-            "failed": 0,
-            "message": "No pending operations"
-        }));
-    }
+    };
     
-    // Try to get Supabase config
-    let config = match sync::SupabaseConfig::from_env() {
-        Some(c) => c,
-        None => {
+    // Credentials for the configured auth mode (refreshes the user token if needed)
+    let config = match auth::resolve_config(&state.db).await {
+        Ok(c) => c,
+        Err(message) => {
             return Ok(serde_json::json!({
                 "synced": 0,
                 "failed": 0,
-                "message": "Supabase not configured (missing NEXT_PUBLIC_SUPABASE_URL or NEXT_PUBLIC_SUPABASE_ANON_KEY)"
+                "message": message
             }));
         }
     };
     
-    // Step 2: Process operations (async, no lock)
-    let results = sync::process_sync_operations(operations, &config).await;
+    // Push in batches; the lock is only held between requests
+    let sync_result = sync::push_pending(&state.db, &config).await?;
     
-    // Step 3: Update status in DB (synchronous, with lock)
-    let sync_result = {
-        let db_guard = state.db.lock().map_err(|e| e.to_string())?;
-        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
-        sync::update_sync_status(conn, results)?
-    }; // Lock released here
+    if sync_result.synced == 0 && sync_result.failed == 0 && sync_result.conflicts == 0 {
+        return Ok(serde_json::json!({
+            "synced": 0,
+            "failed": 0,
+            "message": "No pending operations"
+        }));
+    }
     
     Ok(serde_json::json!({
         "synced": sync_result.synced,
         "failed": sync_result.failed,
-        "message": format!("Synced {} operations, {} failed", sync_result.synced, sync_result.failed)
+        "conflicts": sync_result.conflicts,
+        "message": format!(
+            "Synced {} operations, {} failed, {} conflicts",
+            sync_result.synced, sync_result.failed, sync_result.conflicts
+        )
     }))
 }
 
+// Pull from cloud - Bring down rows changed on other branch PCs
+#[tauri::command]
+async fn pull_from_cloud(
+    state: State<'_, AppState>,
+    scheduler: State<'_, scheduler::SyncScheduler>,
+) -> Result<pull::PullReport, String> {
+    let _guard = scheduler.try_begin().ok_or("Sync already in progress")?;
+    let config = auth::resolve_config(&state.db).await?;
+    
+    Ok(pull::pull_all(&state.db, &config).await)
+}
+
+// Download everything from Supabase into this PC (new branch install),
+// reporting through bootstrap:// events
+#[tauri::command]
+async fn bootstrap_from_cloud(
+    app_handle: tauri::AppHandle,
+) -> Result<bootstrap::BootstrapReport, String> {
+    bootstrap::run(&app_handle).await
+}
+
+// Sign in to Supabase with email/password; sync then uses the user's JWT
+#[tauri::command]
+async fn sync_sign_in(
+    state: State<'_, AppState>,
+    email: String,
+    password: String,
+) -> Result<auth::AuthStatus, String> {
+    auth::sign_in(&state.db, &email, &password).await
+}
+
+// Forget the Supabase session
+#[tauri::command]
+async fn sync_sign_out(
+    state: State<'_, AppState>,
+) -> Result<auth::AuthStatus, String> {
+    auth::sign_out(&state.db).await
+}
+
+// Current sync credential mode and signed-in account
+#[tauri::command]
+async fn get_sync_auth_status(
+    state: State<'_, AppState>,
+) -> Result<auth::AuthStatus, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    auth::status(conn)
+}
+
+// Switch between anon, user and service credentials
+#[tauri::command]
+async fn set_sync_auth_mode(
+    state: State<'_, AppState>,
+    mode: auth::AuthMode,
+) -> Result<auth::AuthStatus, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    auth::set_mode(conn, mode)?;
+    auth::status(conn)
+}
+
+// Supabase connection settings, enabled tables and sync interval
+#[tauri::command]
+async fn get_sync_config(
+    state: State<'_, AppState>,
+) -> Result<config::SyncConfig, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    config::load(conn)
+}
+
+// Store new sync settings; keys are encrypted at rest
+#[tauri::command]
+async fn update_sync_config(
+    state: State<'_, AppState>,
+    scheduler: State<'_, scheduler::SyncScheduler>,
+    update: config::SyncConfigUpdate,
+) -> Result<config::SyncConfig, String> {
+    let saved = {
+        let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        config::update(conn, &update)?;
+        config::load(conn)?
+    };
+    
+    scheduler.wake();
+    Ok(saved)
+}
+
+// Check the stored settings, or unsaved values from the form, against the server
+#[tauri::command]
+async fn test_sync_config(
+    state: State<'_, AppState>,
+    overrides: Option<config::SyncConfigUpdate>,
+) -> Result<config::ConnectionTest, String> {
+    let (rest_url, anon_key, service_key) = {
+        let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        config::test_target(conn, &overrides.unwrap_or_default())?
+    };
+    
+    Ok(config::test_connection(&rest_url, &anon_key, service_key.as_deref()).await)
+}
+
+// Background sync schedule and the state of the sync task
+#[tauri::command]
+async fn get_sync_schedule(
+    app_handle: tauri::AppHandle,
+) -> Result<scheduler::SyncScheduleStatus, String> {
+    scheduler::status(&app_handle)
+}
+
+// Change the background sync schedule; takes effect immediately
+#[tauri::command]
+async fn set_sync_schedule(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    scheduler: State<'_, scheduler::SyncScheduler>,
+    schedule: scheduler::SyncSchedule,
+) -> Result<scheduler::SyncScheduleStatus, String> {
+    {
+        let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        scheduler::save_schedule(conn, &schedule)?;
+    }
+    
+    scheduler.wake();
+    scheduler::status(&app_handle)
+}
+
+// Run a push + pull cycle now, with the same events as the background sync
+#[tauri::command]
+async fn run_sync_now(
+    app_handle: tauri::AppHandle,
+) -> Result<scheduler::SyncCycleReport, String> {
+    scheduler::run_cycle(&app_handle, "manual").await
+}
+
+// Queue counts per table and operation, queue age, last sync and recent errors
+#[tauri::command]
+async fn get_sync_queue_stats(
+    state: State<'_, AppState>,
+) -> Result<queue::QueueStats, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    queue::stats(conn)
+}
+
+// Page through sync_queue entries, optionally filtered by status, table or operation
+#[tauri::command]
+async fn list_sync_queue(
+    state: State<'_, AppState>,
+    query: Option<queue::QueueQuery>,
+) -> Result<queue::QueuePage, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    queue::list_queue(conn, &query.unwrap_or_default())
+}
+
+// Compact and purge the sync queue and optimize the database now
+#[tauri::command]
+async fn run_sync_maintenance(
+    app_handle: tauri::AppHandle,
+) -> Result<Option<queue::MaintenanceReport>, String> {
+    scheduler::maintain(&app_handle, true)
+}
+
+// List operations that failed too often to be retried automatically
+#[tauri::command]
+async fn list_dead_letter_operations(
+    state: State<'_, AppState>,
+) -> Result<Vec<sync::QueuedOperation>, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    sync::list_dead_letter(conn)
+}
+
+// Requeue dead-lettered operations (all of them when ids is omitted)
+#[tauri::command]
+async fn retry_dead_letter_operations(
+    state: State<'_, AppState>,
+    ids: Option<Vec<String>>,
+) -> Result<usize, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    sync::retry_dead_letter(conn, ids.as_deref())
+}
+
+// Stop pushing dead-lettered operations (all of them when ids is omitted)
+#[tauri::command]
+async fn discard_dead_letter_operations(
+    state: State<'_, AppState>,
+    ids: Option<Vec<String>>,
+) -> Result<usize, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    sync::discard_dead_letter(conn, ids.as_deref())
+}
+
+// List sync conflicts, optionally only 'open' or 'resolved' ones
+#[tauri::command]
+async fn list_sync_conflicts(
+    state: State<'_, AppState>,
+    status: Option<String>,
+) -> Result<Vec<conflict::SyncConflict>, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    conflict::list_conflicts(conn, status.as_deref())
+}
+
+// Resolve an open conflict with the local, remote or a merged version
+#[tauri::command]
+async fn resolve_sync_conflict(
+    state: State<'_, AppState>,
+    id: String,
+    resolution: conflict::Resolution,
+    data: Option<serde_json::Map<String, serde_json::Value>>,
+) -> Result<conflict::SyncConflict, String> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_mut().ok_or("Database not initialized")?;
+    
+    conflict::resolve_conflict(conn, &id, resolution, data)
+}
+
+// Conflict policy of every synced table
+#[tauri::command]
+async fn get_conflict_policies(
+    state: State<'_, AppState>,
+) -> Result<Vec<conflict::TablePolicy>, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    conflict::list_policies(conn)
+}
+
+// Change a table's conflict policy (null restores the default)
+#[tauri::command]
+async fn set_conflict_policy(
+    state: State<'_, AppState>,
+    table: String,
+    policy: Option<conflict::ConflictPolicy>,
+) -> Result<(), String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    conflict::set_policy(conn, &table, policy)
+}
+
+// Back up the database now (SQLite online backup) and rotate old backups
+#[tauri::command]
+async fn create_database_backup(
+    app_handle: tauri::AppHandle,
+) -> Result<backup::BackupInfo, String> {
+    backup::create(&app_handle).await
+}
+
+// Size and time of the newest backup file
+#[tauri::command]
+async fn get_backup_info(
+    app_handle: tauri::AppHandle,
+) -> Result<backup::BackupInfo, String> {
+    backup::latest_info(&app_handle)
+}
+
+// Auto-backup schedule, last and next backup
+#[tauri::command]
+async fn get_backup_status(
+    app_handle: tauri::AppHandle,
+) -> Result<backup::BackupStatus, String> {
+    backup::status(&app_handle)
+}
+
+// Change the auto-backup interval (milliseconds); takes effect immediately
+#[tauri::command]
+async fn update_backup_interval(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    backup_service: State<'_, backup::BackupService>,
+    interval: u64,
+) -> Result<backup::BackupStatus, String> {
+    {
+        let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        backup::save_interval(conn, interval)?;
+    }
+    
+    backup_service.wake();
+    backup::status(&app_handle)
+}
+
+// Turn auto-backup on (optionally with a new interval) and back up now
+#[tauri::command]
+async fn start_auto_backup(
+    app_handle: tauri::AppHandle,
+    interval: Option<u64>,
+) -> Result<backup::BackupStatus, String> {
+    backup::set_auto_backup(&app_handle, true, interval).await
+}
+
+// Turn auto-backup off; manual backups still work
+#[tauri::command]
+async fn stop_auto_backup(
+    app_handle: tauri::AppHandle,
+) -> Result<backup::BackupStatus, String> {
+    backup::set_auto_backup(&app_handle, false, None).await
+}
+
+// Backup directory and how many hourly/daily/weekly backups are kept
+#[tauri::command]
+async fn get_backup_config(
+    state: State<'_, AppState>,
+) -> Result<backup::BackupConfig, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    backup::load_config(conn)
+}
+
+// Change the backup directory and rotation; applies from the next backup
+#[tauri::command]
+async fn update_backup_config(
+    state: State<'_, AppState>,
+    config: backup::BackupConfig,
+) -> Result<backup::BackupConfig, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    backup::save_config(conn, &config)?;
+    backup::load_config(conn)
+}
+
+// Every backup file, newest first
+#[tauri::command]
+async fn list_database_backups(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<backup::BackupInfo>, String> {
+    backup::list(&app_handle)
+}
+
+// Replace the database with a verified backup or archive, keeping a snapshot of the current one
+#[tauri::command]
+async fn restore_backup(
+    app_handle: tauri::AppHandle,
+    path: String,
+    passphrase: Option<String>,
+) -> Result<backup::RestoreReport, String> {
+    backup::restore(&app_handle, &path, passphrase).await
+}
+
+// Export the database as an encrypted, compressed archive (e.g. to a USB stick)
+#[tauri::command]
+async fn export_backup_archive(
+    app_handle: tauri::AppHandle,
+    path: String,
+    passphrase: String,
+) -> Result<archive::Manifest, String> {
+    backup::export_archive(&app_handle, &path, passphrase).await
+}
+
+// Manifest of an archive (versions, row counts), readable without the passphrase
+#[tauri::command]
+async fn read_backup_archive(
+    path: String,
+) -> Result<archive::Manifest, String> {
+    archive::read_manifest(std::path::Path::new(&path))
+}
+
+// SQLite integrity/foreign key checks plus drift in derived totals; optionally repairs the totals
+#[tauri::command]
+async fn check_integrity(
+    state: State<'_, AppState>,
+    repair: Option<bool>,
+) -> Result<integrity::IntegrityReport, String> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_mut().ok_or("Database not initialized")?;
+    
+    integrity::check(conn, repair.unwrap_or(false))
+}
+
 // Start Next.js server in background
 fn start_nextjs_server(app_handle: &tauri::AppHandle) -> Result<(), Box<dyn std::error::Error>> {
     use std::process::Command;
@@ -553,12 +1070,19 @@ fn start_nextjs_server(app_handle: &tauri::AppHandle) -> Result<(), Box<dyn std:
     println!("   Server: {:?}", server_js);
     
     // Start Node.js server as detached process
-    Command::new(node_exe)
+    let mut server = Command::new(node_exe)
         .arg(server_js)
         .current_dir(&server_dir)
         .spawn()
         .expect("Failed to start Next.js server");
     
+    // Reap it if it exits, so it does not linger as a zombie
+    std::thread::spawn(move || {
+        if let Ok(status) = server.wait() {
+            println!("⚠️  Next.js server exited: {}", status);
+        }
+    });
+    
     println!("✅ Next.js server started!");
     
     // Wait a bit for server to initialize
@@ -590,6 +1114,18 @@ fn main() {
             app.manage(AppState {
                 db: Mutex::new(Some(conn)),
             });
+            
+            // Push and pull in the background on the stored schedule
+            app.manage(scheduler::SyncScheduler::default());
+            scheduler::start(app.handle().clone());
+            
+            // Back up the database on the stored schedule
+            app.manage(backup::BackupService::new(app.path().app_data_dir()?.join("backups")));
+            backup::start(app.handle().clone());
+            
+            // Notify when tracked stock drops to its minimum
+            app.manage(low_stock::StockWatcher::default());
+            low_stock::start(app.handle().clone());
 
             // Handle window close event to clear localStorage
             let main_window = app.get_webview_window("main").unwrap();
@@ -609,9 +1145,60 @@ fn main() {
             db_update,
             db_delete,
             db_execute,
+            db_transaction,
+            create_sale,
+            create_purchase,
+            get_stock_movements,
+            adjust_stock,
+            recompute_stock,
+            open_stock_opname,
+            record_opname_count,
+            get_stock_opname,
+            list_stock_opname,
+            post_stock_opname,
+            cancel_stock_opname,
+            low_stock_report,
+            quote_area_price,
+            get_area_pricing_config,
+            update_area_pricing_config,
             queue_sync_operation,
             count_pending_sync,
             sync_to_cloud,
+            pull_from_cloud,
+            bootstrap_from_cloud,
+            sync_sign_in,
+            sync_sign_out,
+            get_sync_auth_status,
+            set_sync_auth_mode,
+            get_sync_config,
+            update_sync_config,
+            test_sync_config,
+            get_sync_schedule,
+            set_sync_schedule,
+            run_sync_now,
+            get_sync_queue_stats,
+            list_sync_queue,
+            run_sync_maintenance,
+            list_dead_letter_operations,
+            retry_dead_letter_operations,
+            discard_dead_letter_operations,
+            list_sync_conflicts,
+            resolve_sync_conflict,
+            get_conflict_policies,
+            set_conflict_policy,
+            create_database_backup,
+            get_backup_info,
+            get_backup_status,
+            update_backup_interval,
+            start_auto_backup,
+            stop_auto_backup,
+            get_backup_config,
+            update_backup_config,
+            list_database_backups,
+            restore_backup,
+            export_backup_archive,
+            read_backup_archive,
+            check_integrity,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
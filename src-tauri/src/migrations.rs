@@ -0,0 +1,298 @@
+use rusqlite::{params, Connection, Transaction};
+
+/// A single step that brings the schema from `version - 1` to `version`
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub step: MigrationStep,
+}
+
+/// How a migration is applied
+pub enum MigrationStep {
+    /// Plain SQL script, executed as a batch
+    Sql(&'static str),
+    /// Rust function, for changes that depend on what is already on disk
+    Function(fn(&Transaction) -> rusqlite::Result<()>),
+}
+
+/// All migrations embedded in the binary, ordered by version.
+/// Never edit or renumber an entry once it has shipped; add a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        step: MigrationStep::Sql(include_str!("../../database/sqlite-schema.sql")),
+    },
+    Migration {
+        version: 2,
+        name: "sync_queue",
+        step: MigrationStep::Function(migrate_sync_queue),
+    },
+    Migration {
+        version: 3,
+        name: "default_admin",
+        step: MigrationStep::Sql(include_str!("../migrations/0003_default_admin.sql")),
+    },
+    Migration {
+        version: 4,
+        name: "sync_capture",
+        step: MigrationStep::Sql(include_str!("../migrations/0004_sync_capture.sql")),
+    },
+    Migration {
+        version: 5,
+        name: "sync_pull_state",
+        step: MigrationStep::Sql(include_str!("../migrations/0005_sync_pull_state.sql")),
+    },
+    Migration {
+        version: 6,
+        name: "sync_conflicts",
+        step: MigrationStep::Sql(include_str!("../migrations/0006_sync_conflicts.sql")),
+    },
+    Migration {
+        version: 7,
+        name: "sync_retry",
+        step: MigrationStep::Sql(include_str!("../migrations/0007_sync_retry.sql")),
+    },
+    Migration {
+        version: 8,
+        name: "app_settings",
+        step: MigrationStep::Sql(include_str!("../migrations/0008_app_settings.sql")),
+    },
+    Migration {
+        version: 9,
+        name: "sync_attempt_time",
+        step: MigrationStep::Sql(include_str!("../migrations/0009_sync_attempt_time.sql")),
+    },
+    Migration {
+        version: 10,
+        name: "mutasi_stok",
+        step: MigrationStep::Sql(include_str!("../migrations/0010_mutasi_stok.sql")),
+    },
+    Migration {
+        version: 11,
+        name: "stok_opname",
+        step: MigrationStep::Sql(include_str!("../migrations/0011_stok_opname.sql")),
+    },
+    Migration {
+        version: 12,
+        name: "low_stock_alerts",
+        step: MigrationStep::Sql(include_str!("../migrations/0012_low_stock_alerts.sql")),
+    },
+    Migration {
+        version: 13,
+        name: "drop_legacy_triggers",
+        step: MigrationStep::Sql(include_str!("../migrations/0013_drop_legacy_triggers.sql")),
+    },
+];
+
+/// Highest schema version this binary knows about
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Highest schema version recorded in the database (0 if none)
+pub fn current_version(conn: &Connection) -> Result<i64, String> {
+    if !table_exists(conn, "schema_migrations")? {
+        return Ok(0);
+    }
+
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Tables a database at schema `version` is expected to contain, found by
+/// replaying the migrations up to that version on an empty in-memory database
+pub fn expected_tables(version: i64) -> Result<Vec<String>, String> {
+    let mut conn = Connection::open_in_memory().map_err(|e| e.to_string())?;
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version <= version) {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        match migration.step {
+            MigrationStep::Sql(sql) => tx.execute_batch(sql),
+            MigrationStep::Function(f) => f(&tx),
+        }
+        .and_then(|_| tx.commit())
+        .map_err(|e| format!("Migration {:04}_{} failed: {}", migration.version, migration.name, e))?;
+    }
+
+    let mut stmt = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")
+        .map_err(|e| e.to_string())?;
+    let tables = stmt
+        .query_map([], |row| row.get(0))
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>())
+        .map_err(|e| e.to_string())?;
+
+    Ok(tables)
+}
+
+/// Apply every pending migration, each inside its own transaction.
+/// Refuses to continue if the database was written by a newer binary.
+pub fn run_migrations(conn: &mut Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    baseline_legacy_database(conn)?;
+
+    let current = current_version(conn)?;
+    let latest = latest_version();
+
+    if current > latest {
+        return Err(format!(
+            "Database schema version {} is newer than this application supports ({}). Please update GemiPrint.",
+            current, latest
+        ));
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        println!("Applying migration {:04}_{}...", migration.version, migration.name);
+        apply_migration(conn, migration)
+            .map_err(|e| format!("Migration {:04}_{} failed: {}", migration.version, migration.name, e))?;
+    }
+
+    println!("Database schema at version {}", latest);
+
+    Ok(())
+}
+
+/// Run one migration and record it, committing both or neither
+fn apply_migration(conn: &mut Connection, migration: &Migration) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+
+    match migration.step {
+        MigrationStep::Sql(sql) => tx.execute_batch(sql)?,
+        MigrationStep::Function(f) => f(&tx)?,
+    }
+
+    tx.execute(
+        "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, ?2, ?3)",
+        params![migration.version, migration.name, chrono::Utc::now().to_rfc3339()],
+    )?;
+
+    tx.commit()
+}
+
+/// Databases created from the old bundled template already contain the
+/// initial schema but have no migration history. Mark 0001 as applied for them;
+/// 0013 later drops the triggers the template has on top of it.
+fn baseline_legacy_database(conn: &Connection) -> Result<(), String> {
+    if current_version(conn)? > 0 || !table_exists(conn, "profil")? {
+        return Ok(());
+    }
+
+    println!("Existing database without migration history - baselining at version 1");
+
+    conn.execute(
+        "INSERT INTO schema_migrations (version, name, applied_at) VALUES (1, 'initial_schema', ?1)",
+        params![chrono::Utc::now().to_rfc3339()],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Check whether a table exists in the main schema
+pub fn table_exists(conn: &Connection, name: &str) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name=?1",
+        params![name],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count > 0)
+    .map_err(|e| e.to_string())
+}
+
+/// Column names of a table, in declaration order
+pub fn table_columns(conn: &Connection, table: &str) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info(\"{}\")", table.replace('"', "\"\"")))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(columns)
+}
+
+// 0002: sync_queue has existed in several shapes (`dibuat_pada` in the bundled
+// template, `created_at` when created by `queue_sync_operation`). Rebuild it
+// into a single canonical layout, keeping any rows that are already queued.
+fn migrate_sync_queue(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE sync_queue_new (
+            id TEXT PRIMARY KEY,
+            table_name TEXT NOT NULL,
+            operation TEXT NOT NULL,
+            record_id TEXT,
+            data TEXT,
+            created_at TEXT NOT NULL,
+            synced_at TEXT,
+            status TEXT DEFAULT 'pending'
+        )",
+    )?;
+
+    let existing = table_columns(tx, "sync_queue")?;
+    if !existing.is_empty() {
+        let has = |c: &str| existing.iter().any(|e| e == c);
+        let created_at = if has("created_at") {
+            "created_at"
+        } else if has("dibuat_pada") {
+            "dibuat_pada"
+        } else {
+            "datetime('now')"
+        };
+        let synced_at = if has("synced_at") { "synced_at" } else { "NULL" };
+        let status = if has("status") { "COALESCE(status, 'pending')" } else { "'pending'" };
+
+        tx.execute_batch(&format!(
+            "INSERT INTO sync_queue_new (id, table_name, operation, record_id, data, created_at, synced_at, status)
+             SELECT id, table_name, operation, record_id, data, {}, {}, {} FROM sync_queue;
+             DROP TABLE sync_queue;",
+            created_at, synced_at, status
+        ))?;
+    }
+
+    tx.execute_batch(
+        "ALTER TABLE sync_queue_new RENAME TO sync_queue;
+         CREATE INDEX idx_sync_queue_status ON sync_queue(status, created_at);",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema(conn: &Connection) -> Vec<(String, String, String, Option<String>)> {
+        let mut stmt = conn
+            .prepare("SELECT type, name, tbl_name, sql FROM sqlite_master ORDER BY type, name")
+            .unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .unwrap()
+    }
+
+    #[test]
+    fn template_database_migrates_to_the_fresh_schema() {
+        let mut fresh = Connection::open_in_memory().unwrap();
+        run_migrations(&mut fresh).unwrap();
+
+        // The file the installer ships and copies on first run
+        let template = concat!(env!("CARGO_MANIFEST_DIR"), "/../database/gemiprint.db");
+        let path = std::env::temp_dir().join(format!("gemiprint-template-{}.db", uuid::Uuid::new_v4()));
+        std::fs::copy(template, &path).unwrap();
+        let mut upgraded = Connection::open(&path).unwrap();
+        let result = run_migrations(&mut upgraded).map(|_| schema(&upgraded));
+        drop(upgraded);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(result.unwrap(), schema(&fresh));
+    }
+}
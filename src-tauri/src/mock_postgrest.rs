@@ -0,0 +1,524 @@
+//! In-process stand-in for the PostgREST API behind Supabase, for tests.
+//!
+//! Serves the subset of PostgREST the sync engine uses (`GET` with `id`
+//! filters, upserting `POST`, `PATCH` and `DELETE` with `id` and
+//! `sync_version` filters, `HEAD` counts) from tables seeded with
+//! a JSON fixture under `tests/fixtures/postgrest`. Like the cloud schema it
+//! bumps `sync_version` on every update and rejects rows whose parent is
+//! missing. Failures, slow responses and edits by another client can be
+//! scripted per request.
+
+use crate::schema;
+use crate::sync::SupabaseConfig;
+use crate::{capture, migrations};
+use rusqlite::backup::Progress;
+use rusqlite::{Connection, DatabaseName};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+type Row = Map<String, Value>;
+
+/// A request as the mock received it
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub table: String,
+    pub query: Vec<(String, String)>,
+    pub body: String,
+}
+
+/// A response returned instead of the normal one
+struct ScriptedFailure {
+    method: String,
+    status: u16,
+    body: String,
+}
+
+/// A cloud edit applied just before a request is handled
+struct ScriptedEdit {
+    method: String,
+    table: String,
+    id: String,
+    changes: Row,
+}
+
+#[derive(Default)]
+struct MockState {
+    /// Rows by table, ordered by id
+    tables: BTreeMap<String, BTreeMap<String, Row>>,
+    failures: VecDeque<ScriptedFailure>,
+    edits: VecDeque<ScriptedEdit>,
+    delay: Option<Duration>,
+    requests: Vec<RecordedRequest>,
+}
+
+pub struct MockPostgrest {
+    addr: std::net::SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    server: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for MockPostgrest {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+impl MockPostgrest {
+    /// Start a server with empty tables
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind mock server");
+        let addr = listener.local_addr().expect("mock server address");
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let server = tokio::spawn(serve(listener, state.clone()));
+        Self { addr, state, server }
+    }
+
+    /// Start a server seeded from `tests/fixtures/postgrest/<name>.json`,
+    /// an object of table name to array of rows
+    pub async fn with_fixture(name: &str) -> Self {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/postgrest")
+            .join(format!("{}.json", name));
+        let text = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("read fixture {}: {}", path.display(), e));
+        let fixture: BTreeMap<String, Vec<Row>> = serde_json::from_str(&text).expect("parse fixture");
+
+        let mock = Self::start().await;
+        {
+            let mut state = mock.state.lock().unwrap();
+            for (table, rows) in fixture {
+                let stored = state.tables.entry(table).or_default();
+                for row in rows {
+                    let id = row["id"].as_str().expect("fixture row id").to_string();
+                    stored.insert(id, row);
+                }
+            }
+        }
+        mock
+    }
+
+    /// Base URL, served at the root like a standalone PostgREST
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Anon credentials pointed at this server
+    pub fn config(&self) -> SupabaseConfig {
+        SupabaseConfig::anon(self.url(), "test-anon-key".to_string()).with_rest_url(Some(self.url()))
+    }
+
+    /// Answer the next `times` requests with this method with `status` and `body`
+    pub fn fail_next(&self, method: &str, status: u16, body: &str, times: usize) {
+        let mut state = self.state.lock().unwrap();
+        for _ in 0..times {
+            state.failures.push_back(ScriptedFailure {
+                method: method.to_string(),
+                status,
+                body: body.to_string(),
+            });
+        }
+    }
+
+    /// When the next request with this method arrives, first apply `changes`
+    /// to the row as another client's update would (bumping `sync_version`)
+    pub fn edit_before_next(&self, method: &str, table: &str, id: &str, changes: Value) {
+        let Value::Object(changes) = changes else {
+            panic!("edit must be a JSON object");
+        };
+        self.state.lock().unwrap().edits.push_back(ScriptedEdit {
+            method: method.to_string(),
+            table: table.to_string(),
+            id: id.to_string(),
+            changes,
+        });
+    }
+
+    /// Wait this long before answering every request
+    pub fn set_delay(&self, delay: Duration) {
+        self.state.lock().unwrap().delay = Some(delay);
+    }
+
+    pub fn row(&self, table: &str, id: &str) -> Option<Row> {
+        let state = self.state.lock().unwrap();
+        state.tables.get(table).and_then(|rows| rows.get(id)).cloned()
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+/// A migrated in-memory database with capture triggers, shaped like `AppState.db`
+pub fn test_db() -> Mutex<Option<Connection>> {
+    let mut conn = Connection::open_in_memory().expect("open in-memory database");
+    conn.execute("PRAGMA foreign_keys = ON", []).unwrap();
+    migrations::run_migrations(&mut conn).expect("migrate test database");
+    capture::install_triggers(&mut conn).expect("install capture triggers");
+    Mutex::new(Some(conn))
+}
+
+/// The bundled `database/gemiprint.db` template as a first run copies it,
+/// migrated and with sync capture, in memory
+pub fn template_db() -> Mutex<Option<Connection>> {
+    // Copied first: opening the template in place would checkpoint its WAL
+    let template = concat!(env!("CARGO_MANIFEST_DIR"), "/../database/gemiprint.db");
+    let path = std::env::temp_dir().join(format!("gemiprint-template-{}.db", uuid::Uuid::new_v4()));
+    std::fs::copy(template, &path).expect("copy template database");
+    let mut conn = Connection::open_in_memory().expect("open in-memory database");
+    let loaded = conn.restore(DatabaseName::Main, &path, None::<fn(Progress)>);
+    std::fs::remove_file(&path).expect("remove template copy");
+    loaded.expect("load template database");
+
+    conn.execute("PRAGMA foreign_keys = ON", []).unwrap();
+    migrations::run_migrations(&mut conn).expect("migrate template database");
+    capture::install_triggers(&mut conn).expect("install capture triggers");
+    Mutex::new(Some(conn))
+}
+
+async fn serve(listener: TcpListener, state: Arc<Mutex<MockState>>) {
+    while let Ok((socket, _)) = listener.accept().await {
+        tokio::spawn(handle_connection(socket, state.clone()));
+    }
+}
+
+async fn handle_connection(mut socket: TcpStream, state: Arc<Mutex<MockState>>) {
+    let Some((method, target, prefer, body)) = read_request(&mut socket).await else {
+        return;
+    };
+
+    let delay = state.lock().unwrap().delay;
+    if let Some(delay) = delay {
+        tokio::time::sleep(delay).await;
+    }
+
+    let (status, headers, body) = {
+        let mut state = state.lock().unwrap();
+        respond(&mut state, &method, &target, &prefer, &body)
+    };
+
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        409 => "Conflict",
+        _ => "Error",
+    };
+    let mut response = format!("HTTP/1.1 {} {}\r\nContent-Type: application/json\r\n", status, reason);
+    for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", body.len()));
+    if method != "HEAD" {
+        response.push_str(&body);
+    }
+    socket.write_all(response.as_bytes()).await.ok();
+}
+
+/// Read one HTTP/1.1 request: (method, request target, `Prefer` header, body)
+async fn read_request(socket: &mut TcpStream) -> Option<(String, String, String, String)> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 16 * 1024];
+
+    let (head_len, content_length) = loop {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&buffer[..pos]).to_string();
+            let content_length = head
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+                .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+            break (pos + 4, content_length);
+        }
+    };
+
+    while buffer.len() < head_len + content_length {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+
+    let head = String::from_utf8_lossy(&buffer[..head_len]).to_string();
+    let mut request_line = head.lines().next()?.split(' ');
+    let method = request_line.next()?.to_string();
+    let target = request_line.next()?.to_string();
+    let prefer = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("prefer"))
+        .map(|(_, value)| value.trim().to_string())
+        .unwrap_or_default();
+    let body = String::from_utf8_lossy(&buffer[head_len..]).to_string();
+    Some((method, target, prefer, body))
+}
+
+fn respond(
+    state: &mut MockState,
+    method: &str,
+    target: &str,
+    prefer: &str,
+    body: &str,
+) -> (u16, Vec<(String, String)>, String) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let table = path.trim_start_matches('/').trim_start_matches("rest/v1/").to_string();
+    let query = parse_query(query);
+
+    state.requests.push(RecordedRequest {
+        method: method.to_string(),
+        table: table.clone(),
+        query: query.clone(),
+        body: body.to_string(),
+    });
+
+    if let Some(index) = state.edits.iter().position(|e| e.method == method) {
+        let edit = state.edits.remove(index).unwrap();
+        if let Some(row) = state.tables.get_mut(&edit.table).and_then(|rows| rows.get_mut(&edit.id)) {
+            let version = row.get("sync_version").and_then(|v| v.as_i64()).unwrap_or(0) + 1;
+            row.extend(edit.changes);
+            row.insert("sync_version".to_string(), version.into());
+        }
+    }
+
+    if let Some(index) = state.failures.iter().position(|f| f.method == method) {
+        let failure = state.failures.remove(index).unwrap();
+        return (failure.status, Vec::new(), failure.body);
+    }
+
+    if schema::table(&table).is_err() {
+        return error(404, "42P01", &format!("relation \"public.{}\" does not exist", table));
+    }
+
+    let param = |name: &str| query.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+    let ids = param("id").and_then(parse_id_filter);
+    let version = param("sync_version").and_then(|v| v.strip_prefix("eq.")).and_then(|v| v.parse::<i64>().ok());
+    let select = param("select").unwrap_or("*").to_string();
+    let representation = prefer.contains("return=representation");
+    let matches = |row: &Row| {
+        ids.as_ref().is_none_or(|ids| ids.iter().any(|id| Some(id.as_str()) == row["id"].as_str()))
+            && version.is_none_or(|v| row.get("sync_version").and_then(|r| r.as_i64()) == Some(v))
+    };
+
+    match method {
+        "GET" | "HEAD" => {
+            let rows = state.tables.get(&table).cloned().unwrap_or_default();
+            let limit = param("limit").and_then(|l| l.parse().ok()).unwrap_or(usize::MAX);
+            let matching: Vec<Value> = rows
+                .values()
+                .filter(|row| matches(row))
+                .take(limit)
+                .map(|row| project(row, &select))
+                .collect();
+
+            let range = if matching.is_empty() {
+                "*/0".to_string()
+            } else {
+                format!("0-{}/{}", matching.len() - 1, matching.len())
+            };
+            (200, vec![("Content-Range".to_string(), range)], Value::Array(matching).to_string())
+        }
+
+        "POST" => {
+            let rows: Vec<Row> = match serde_json::from_str::<Value>(body) {
+                Ok(Value::Array(rows)) => rows.into_iter().filter_map(|r| r.as_object().cloned()).collect(),
+                Ok(Value::Object(row)) => vec![row],
+                _ => return error(400, "PGRST102", "Empty or invalid json"),
+            };
+
+            if let Some(message) = missing_parent(state, &table, &rows) {
+                return error(409, "23503", &message);
+            }
+
+            let stored = state.tables.entry(table).or_default();
+            let mut written = Vec::new();
+            for mut row in rows {
+                let Some(id) = row.get("id").and_then(|v| v.as_str()).map(str::to_string) else {
+                    return error(400, "23502", "null value in column \"id\"");
+                };
+                // BEFORE UPDATE trigger on the cloud: the server owns the version
+                if let Some(existing) = stored.get(&id) {
+                    let version = existing.get("sync_version").and_then(|v| v.as_i64()).unwrap_or(0) + 1;
+                    let mut merged = existing.clone();
+                    merged.extend(row);
+                    merged.insert("sync_version".to_string(), version.into());
+                    row = merged;
+                } else if !row.contains_key("sync_version") {
+                    row.insert("sync_version".to_string(), 1.into());
+                }
+                written.push(project(&row, &select));
+                stored.insert(id, row);
+            }
+
+            (201, Vec::new(), Value::Array(written).to_string())
+        }
+
+        "PATCH" => {
+            let Ok(Value::Object(changes)) = serde_json::from_str::<Value>(body) else {
+                return error(400, "PGRST102", "Empty or invalid json");
+            };
+            if ids.is_none() {
+                return error(400, "21000", "UPDATE requires a WHERE clause");
+            }
+
+            let updated: Vec<Row> = state
+                .tables
+                .get(&table)
+                .map(|rows| rows.values().filter(|row| matches(row)).cloned().collect::<Vec<_>>())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|existing| {
+                    // BEFORE UPDATE trigger on the cloud: the server owns the version
+                    let version = existing.get("sync_version").and_then(|v| v.as_i64()).unwrap_or(0) + 1;
+                    let mut row = existing;
+                    row.extend(changes.clone());
+                    row.insert("sync_version".to_string(), version.into());
+                    row
+                })
+                .collect();
+            if let Some(message) = missing_parent(state, &table, &updated) {
+                return error(409, "23503", &message);
+            }
+
+            let stored = state.tables.entry(table).or_default();
+            let mut written = Vec::new();
+            for row in updated {
+                written.push(project(&row, &select));
+                stored.insert(row["id"].as_str().unwrap_or_default().to_string(), row);
+            }
+
+            if representation {
+                (200, Vec::new(), Value::Array(written).to_string())
+            } else {
+                (204, Vec::new(), String::new())
+            }
+        }
+
+        "DELETE" => {
+            if ids.is_none() {
+                return error(400, "21000", "DELETE requires a WHERE clause");
+            }
+            let mut deleted = Vec::new();
+            if let Some(rows) = state.tables.get_mut(&table) {
+                let matching: Vec<String> = rows.iter().filter(|(_, row)| matches(row)).map(|(id, _)| id.clone()).collect();
+                for id in matching {
+                    if let Some(row) = rows.remove(&id) {
+                        deleted.push(project(&row, &select));
+                    }
+                }
+            }
+
+            if representation {
+                (200, Vec::new(), Value::Array(deleted).to_string())
+            } else {
+                (204, Vec::new(), String::new())
+            }
+        }
+
+        _ => error(405, "PGRST117", "Unsupported HTTP method"),
+    }
+}
+
+fn error(status: u16, code: &str, message: &str) -> (u16, Vec<(String, String)>, String) {
+    let body = serde_json::json!({ "code": code, "message": message, "details": null, "hint": null });
+    (status, Vec::new(), body.to_string())
+}
+
+/// Foreign key check against the registry, like the cloud's constraints
+fn missing_parent(state: &MockState, table: &str, rows: &[Row]) -> Option<String> {
+    let def = schema::table(table).ok()?;
+    for row in rows {
+        for (column, parent) in def.references {
+            let Some(parent_id) = row.get(*column).and_then(|v| v.as_str()) else {
+                continue;
+            };
+            let in_batch = *parent == table && rows.iter().any(|r| r["id"].as_str() == Some(parent_id));
+            let stored = state.tables.get(*parent).is_some_and(|p| p.contains_key(parent_id));
+            if !in_batch && !stored {
+                return Some(format!(
+                    "insert or update on table \"{}\" violates foreign key constraint on \"{}\"",
+                    table, column
+                ));
+            }
+        }
+    }
+    None
+}
+
+fn project(row: &Row, select: &str) -> Value {
+    if select == "*" {
+        return Value::Object(row.clone());
+    }
+    let columns: Vec<&str> = select.split(',').map(str::trim).collect();
+    Value::Object(
+        row.iter()
+            .filter(|(k, _)| columns.contains(&k.as_str()))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
+    )
+}
+
+/// `in.("a","b")` or `eq.a`
+fn parse_id_filter(filter: &str) -> Option<Vec<String>> {
+    if let Some(list) = filter.strip_prefix("in.(").and_then(|f| f.strip_suffix(')')) {
+        return Some(list.split(',').map(|id| id.trim_matches('"').to_string()).collect());
+    }
+    filter.strip_prefix("eq.").map(|id| vec![id.to_string()])
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 3;
+                    }
+                    None => {
+                        out.push(b'%');
+                        i += 1;
+                    }
+                }
+            }
+            b'+' => {
+                out.push(b' ');
+                i += 1;
+            }
+            byte => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
@@ -0,0 +1,539 @@
+use crate::pos::{parse_tanggal, today_jakarta};
+use crate::stock;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Payload of `open_stock_opname`
+#[derive(Debug, Deserialize)]
+pub struct OpenOpnameRequest {
+    /// Categories to count; every inventory-tracked barang in them is snapshotted
+    pub kategori_ids: Vec<String>,
+    pub catatan: Option<String>,
+    pub dibuat_oleh: Option<String>,
+    /// Count date (YYYY-MM-DD), defaults to today in Asia/Jakarta
+    pub tanggal: Option<String>,
+}
+
+/// Payload of `record_opname_count`
+#[derive(Debug, Deserialize)]
+pub struct RecordCountRequest {
+    pub stok_opname_id: String,
+    pub barang_id: String,
+    /// Counted quantity, in the unit of `harga_satuan_id`
+    pub jumlah: f64,
+    /// Unit the count was taken in; `satuan_dasar` when absent
+    pub harga_satuan_id: Option<String>,
+    pub catatan: Option<String>,
+}
+
+/// A session with its lines and variance totals
+#[derive(Debug, Serialize)]
+pub struct OpnameSession {
+    pub id: String,
+    pub nomor_opname: String,
+    pub tanggal: String,
+    pub status: String,
+    pub kategori_ids: Vec<String>,
+    pub catatan: Option<String>,
+    pub dibuat_oleh: Option<String>,
+    pub diposting_pada: Option<String>,
+    pub items: Vec<OpnameItem>,
+    pub total_barang: usize,
+    pub total_dihitung: usize,
+    /// Sum of `nilai_selisih` over counted lines
+    pub total_nilai_selisih: f64,
+}
+
+/// One barang in a session; quantities in `satuan_dasar`
+#[derive(Debug, Serialize)]
+pub struct OpnameItem {
+    pub id: String,
+    pub barang_id: String,
+    pub barang_nama: String,
+    pub kategori_nama: Option<String>,
+    pub satuan_dasar: Option<String>,
+    /// Stock when the session was opened, then when the barang was counted
+    pub stok_sistem: f64,
+    pub jumlah_hitung: Option<f64>,
+    pub jumlah_input: Option<f64>,
+    pub satuan_input: Option<String>,
+    pub harga_beli: f64,
+    /// Counted minus expected; None until counted
+    pub selisih: Option<f64>,
+    /// `selisih` valued at `harga_beli`
+    pub nilai_selisih: Option<f64>,
+    pub catatan: Option<String>,
+    pub dihitung_pada: Option<String>,
+}
+
+/// Session list entry for `list_stock_opname`
+#[derive(Debug, Serialize)]
+pub struct OpnameSummary {
+    pub id: String,
+    pub nomor_opname: String,
+    pub tanggal: String,
+    pub status: String,
+    pub total_barang: i64,
+    pub total_dihitung: i64,
+    pub dibuat_pada: Option<String>,
+}
+
+/// Open a session and snapshot the current stock of every inventory-tracked
+/// barang in the selected categories
+pub fn open(conn: &mut Connection, req: OpenOpnameRequest) -> Result<OpnameSession, String> {
+    if req.kategori_ids.is_empty() {
+        return Err("Pilih minimal satu kategori barang".to_string());
+    }
+
+    let tanggal = req.tanggal.clone().unwrap_or_else(today_jakarta);
+    parse_tanggal(&tanggal)?;
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().to_rfc3339();
+    let kategori_ids = serde_json::to_string(&req.kategori_ids).map_err(|e| e.to_string())?;
+    let catatan = req.catatan.as_deref().map(str::trim).filter(|c| !c.is_empty());
+
+    let id = Uuid::new_v4().to_string();
+    let nomor_opname = next_opname_number(&tx, &tanggal)?;
+    tx.execute(
+        "INSERT INTO stok_opname (id, nomor_opname, tanggal, status, kategori_ids, catatan, dibuat_oleh,
+            dibuat_pada, diperbarui_pada)
+         VALUES (?1, ?2, ?3, 'DRAFT', ?4, ?5, ?6, ?7, ?7)",
+        params![id, nomor_opname, tanggal, kategori_ids, catatan, req.dibuat_oleh, now],
+    )
+    .map_err(|e| e.to_string())?;
+
+    // harga_beli per satuan_dasar: the default unit's purchase price, else the
+    // smallest unit that has one
+    let snapshot: Vec<(String, f64, f64)> = {
+        let mut stmt = tx
+            .prepare(
+                "SELECT b.id, COALESCE(b.jumlah_stok, 0),
+                        COALESCE((SELECT h.harga_beli / h.faktor_konversi FROM harga_barang_satuan h
+                                  WHERE h.barang_id = b.id AND h.harga_beli > 0 AND h.faktor_konversi > 0
+                                  ORDER BY COALESCE(h.default_status, 0) DESC, h.faktor_konversi
+                                  LIMIT 1), 0)
+                 FROM barang b
+                 WHERE b.kategori_id IN (SELECT value FROM json_each(?1))
+                   AND COALESCE(b.lacak_inventori_status, 1) = 1",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![kategori_ids], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(|e| e.to_string())?;
+        rows
+    };
+    if snapshot.is_empty() {
+        return Err("Tidak ada barang dengan lacak inventori pada kategori yang dipilih".to_string());
+    }
+
+    for (barang_id, stok_sistem, harga_beli) in snapshot {
+        tx.execute(
+            "INSERT INTO item_stok_opname (id, stok_opname_id, barang_id, stok_sistem, harga_beli,
+                dibuat_pada, diperbarui_pada)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+            params![Uuid::new_v4().to_string(), id, barang_id, stok_sistem, harga_beli, now],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    tx.commit().map_err(|e| e.to_string())?;
+    get(conn, &id)
+}
+
+/// Record (or correct) the counted quantity of one barang, converting it to
+/// `satuan_dasar` with the unit's `faktor_konversi`. `stok_sistem` is taken
+/// again at this moment, so movements between opening the session and the
+/// count are already in both figures and do not show up as a variance.
+pub fn record_count(conn: &mut Connection, req: RecordCountRequest) -> Result<OpnameItem, String> {
+    if !req.jumlah.is_finite() || req.jumlah < 0.0 {
+        return Err("Jumlah hitung tidak boleh negatif".to_string());
+    }
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    ensure_draft(&tx, &req.stok_opname_id)?;
+
+    let (satuan_input, faktor_konversi) = match &req.harga_satuan_id {
+        Some(harga_satuan_id) => tx
+            .query_row(
+                "SELECT nama_satuan, faktor_konversi FROM harga_barang_satuan WHERE id = ?1 AND barang_id = ?2",
+                params![harga_satuan_id, req.barang_id],
+                |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, f64>(1)?)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Satuan {} tidak ditemukan untuk barang {}", harga_satuan_id, req.barang_id))?,
+        None => tx
+            .query_row(
+                "SELECT satuan_dasar FROM barang WHERE id = ?1",
+                params![req.barang_id],
+                |row| Ok((row.get::<_, Option<String>>(0)?, 1.0)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Barang tidak ditemukan: {}", req.barang_id))?,
+    };
+    if !faktor_konversi.is_finite() || faktor_konversi <= 0.0 {
+        return Err(format!("Faktor konversi satuan {} tidak valid", satuan_input.unwrap_or_default()));
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let catatan = req.catatan.as_deref().map(str::trim).filter(|c| !c.is_empty());
+    let updated = tx
+        .execute(
+            "UPDATE item_stok_opname
+             SET jumlah_hitung = ?1, jumlah_input = ?2, satuan_input = ?3, faktor_konversi = ?4,
+                 catatan = ?5, dihitung_pada = ?6, diperbarui_pada = ?6,
+                 stok_sistem = (SELECT COALESCE(jumlah_stok, 0) FROM barang WHERE id = ?8)
+             WHERE stok_opname_id = ?7 AND barang_id = ?8",
+            params![
+                req.jumlah * faktor_konversi,
+                req.jumlah,
+                satuan_input,
+                faktor_konversi,
+                catatan,
+                now,
+                req.stok_opname_id,
+                req.barang_id
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Barang {} tidak termasuk dalam stok opname ini", req.barang_id));
+    }
+
+    tx.commit().map_err(|e| e.to_string())?;
+    items(conn, &req.stok_opname_id, Some(&req.barang_id))?
+        .pop()
+        .ok_or_else(|| "Item stok opname tidak ditemukan".to_string())
+}
+
+/// A session with its lines and variances
+pub fn get(conn: &Connection, id: &str) -> Result<OpnameSession, String> {
+    let (nomor_opname, tanggal, status, kategori_ids, catatan, dibuat_oleh, diposting_pada) = conn
+        .query_row(
+            "SELECT nomor_opname, tanggal, status, kategori_ids, catatan, dibuat_oleh, diposting_pada
+             FROM stok_opname WHERE id = ?1",
+            params![id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                    row.get::<_, Option<String>>(6)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Stok opname tidak ditemukan: {}", id))?;
+
+    let items = items(conn, id, None)?;
+    let counted = items.iter().filter(|i| i.jumlah_hitung.is_some());
+
+    Ok(OpnameSession {
+        id: id.to_string(),
+        nomor_opname,
+        tanggal,
+        status,
+        kategori_ids: kategori_ids
+            .and_then(|k| serde_json::from_str(&k).ok())
+            .unwrap_or_default(),
+        catatan,
+        dibuat_oleh,
+        diposting_pada,
+        total_barang: items.len(),
+        total_dihitung: counted.clone().count(),
+        total_nilai_selisih: counted.filter_map(|i| i.nilai_selisih).sum(),
+        items,
+    })
+}
+
+/// Sessions, newest first
+pub fn list(conn: &Connection) -> Result<Vec<OpnameSummary>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT o.id, o.nomor_opname, o.tanggal, o.status,
+                    COUNT(i.id), COUNT(i.jumlah_hitung), o.dibuat_pada
+             FROM stok_opname o
+             LEFT JOIN item_stok_opname i ON i.stok_opname_id = o.id
+             GROUP BY o.id
+             ORDER BY o.tanggal DESC, o.nomor_opname DESC",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(OpnameSummary {
+                id: row.get(0)?,
+                nomor_opname: row.get(1)?,
+                tanggal: row.get(2)?,
+                status: row.get(3)?,
+                total_barang: row.get(4)?,
+                total_dihitung: row.get(5)?,
+                dibuat_pada: row.get(6)?,
+            })
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(|e| e.to_string())?;
+
+    Ok(rows)
+}
+
+/// Post every counted line as a STOK_OPNAME movement in one transaction.
+/// Each variance is against the stock at the time of its count and is applied
+/// to the current stock, so movements after the count carry over. Lines never
+/// counted are left alone.
+pub fn post(conn: &mut Connection, id: &str, dibuat_oleh: Option<&str>) -> Result<OpnameSession, String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let nomor_opname = ensure_draft(&tx, id)?;
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let variances: Vec<(String, String, f64)> = {
+        let mut stmt = tx
+            .prepare(
+                "SELECT id, barang_id, jumlah_hitung - stok_sistem FROM item_stok_opname
+                 WHERE stok_opname_id = ?1 AND jumlah_hitung IS NOT NULL",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(|e| e.to_string())?;
+        rows
+    };
+    if variances.is_empty() {
+        return Err("Belum ada barang yang dihitung".to_string());
+    }
+
+    for (item_id, barang_id, selisih) in &variances {
+        // Float noise from unit conversion is not a variance
+        if selisih.abs() < 1e-9 {
+            continue;
+        }
+        stock::apply(
+            &tx,
+            &stock::Mutasi {
+                barang_id,
+                jenis: stock::JenisMutasi::StokOpname,
+                jumlah: *selisih,
+                referensi: Some(("item_stok_opname", item_id)),
+                catatan: Some(&nomor_opname),
+                dibuat_oleh,
+            },
+            &now,
+        )?;
+    }
+
+    tx.execute(
+        "UPDATE stok_opname SET status = 'DIPOSTING', diposting_pada = ?1, diperbarui_pada = ?1 WHERE id = ?2",
+        params![now, id],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    get(conn, id)
+}
+
+/// Abandon a draft session; stock is not touched
+pub fn cancel(conn: &mut Connection, id: &str) -> Result<(), String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    ensure_draft(&tx, id)?;
+    tx.execute(
+        "UPDATE stok_opname SET status = 'DIBATALKAN', diperbarui_pada = ?1 WHERE id = ?2",
+        params![chrono::Utc::now().to_rfc3339(), id],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Lines of a session (or one barang of it), by category and name
+fn items(conn: &Connection, id: &str, barang_id: Option<&str>) -> Result<Vec<OpnameItem>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT i.id, i.barang_id, b.nama, k.nama, b.satuan_dasar, i.stok_sistem, i.jumlah_hitung,
+                    i.jumlah_input, i.satuan_input, COALESCE(i.harga_beli, 0), i.catatan, i.dihitung_pada
+             FROM item_stok_opname i
+             JOIN barang b ON b.id = i.barang_id
+             LEFT JOIN kategori_barang k ON k.id = b.kategori_id
+             WHERE i.stok_opname_id = ?1 AND (?2 IS NULL OR i.barang_id = ?2)
+             ORDER BY k.nama, b.nama",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map(params![id, barang_id], |row| {
+            let stok_sistem: f64 = row.get(5)?;
+            let jumlah_hitung: Option<f64> = row.get(6)?;
+            let harga_beli: f64 = row.get(9)?;
+            let selisih = jumlah_hitung.map(|hitung| hitung - stok_sistem);
+            Ok(OpnameItem {
+                id: row.get(0)?,
+                barang_id: row.get(1)?,
+                barang_nama: row.get(2)?,
+                kategori_nama: row.get(3)?,
+                satuan_dasar: row.get(4)?,
+                stok_sistem,
+                jumlah_hitung,
+                jumlah_input: row.get(7)?,
+                satuan_input: row.get(8)?,
+                harga_beli,
+                selisih,
+                nilai_selisih: selisih.map(|s| s * harga_beli),
+                catatan: row.get(10)?,
+                dihitung_pada: row.get(11)?,
+            })
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(|e| e.to_string())?;
+
+    Ok(rows)
+}
+
+/// Fail unless the session exists and is still a draft; returns its number
+fn ensure_draft(tx: &Transaction, id: &str) -> Result<String, String> {
+    let (nomor_opname, status): (String, String) = tx
+        .query_row(
+            "SELECT nomor_opname, status FROM stok_opname WHERE id = ?1",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Stok opname tidak ditemukan: {}", id))?;
+
+    if status != "DRAFT" {
+        return Err(format!("Stok opname {} sudah berstatus {}", nomor_opname, status));
+    }
+    Ok(nomor_opname)
+}
+
+/// Next session number for the day: OPN-YYYYMMDD-NNN
+fn next_opname_number(tx: &Transaction, tanggal: &str) -> Result<String, String> {
+    let prefix = format!("OPN-{}-", tanggal.replace('-', ""));
+    let last: Option<i64> = tx
+        .query_row(
+            "SELECT MAX(CAST(SUBSTR(nomor_opname, ?2) AS INTEGER)) FROM stok_opname
+             WHERE nomor_opname LIKE ?1 || '%'",
+            params![prefix, prefix.len() as i64 + 1],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    Ok(format!("{}{:03}", prefix, last.unwrap_or(0) + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_postgrest::test_db;
+
+    fn sell(conn: &Connection, jumlah: f64) {
+        stock::apply(
+            conn,
+            &stock::Mutasi {
+                barang_id: "b-vinyl",
+                jenis: stock::JenisMutasi::Penjualan,
+                jumlah: -jumlah,
+                referensi: None,
+                catatan: None,
+                dibuat_oleh: None,
+            },
+            &chrono::Utc::now().to_rfc3339(),
+        )
+        .unwrap();
+    }
+
+    fn count(conn: &mut Connection, stok_opname_id: &str, jumlah: f64, harga_satuan_id: Option<&str>) -> OpnameItem {
+        record_count(
+            conn,
+            RecordCountRequest {
+                stok_opname_id: stok_opname_id.to_string(),
+                barang_id: "b-vinyl".to_string(),
+                jumlah,
+                harga_satuan_id: harga_satuan_id.map(str::to_string),
+                catatan: None,
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn sales_before_and_after_the_count_are_each_subtracted_once() {
+        let db = test_db();
+        let mut guard = db.lock().unwrap();
+        let conn = guard.as_mut().unwrap();
+        conn.execute_batch(
+            "INSERT INTO kategori_barang (id, nama) VALUES ('k-bahan', 'Bahan');
+             INSERT INTO barang (id, nama, satuan_dasar, jumlah_stok, kategori_id)
+             VALUES ('b-vinyl', 'Vinyl', 'm2', 10, 'k-bahan');
+             INSERT INTO harga_barang_satuan (id, barang_id, nama_satuan, faktor_konversi, harga_beli)
+             VALUES ('h-m2', 'b-vinyl', 'm2', 1, 20000);",
+        )
+        .unwrap();
+        let session = open(
+            conn,
+            OpenOpnameRequest {
+                kategori_ids: vec!["k-bahan".to_string()],
+                catatan: None,
+                dibuat_oleh: None,
+                tanggal: Some("2026-05-02".to_string()),
+            },
+        )
+        .unwrap();
+        assert_eq!(session.nomor_opname, "OPN-20260502-001");
+
+        // Sold while the shelves were being counted, then 1 m² found missing
+        sell(conn, 2.0);
+        let item = count(conn, &session.id, 7.0, None);
+        assert_eq!((item.stok_sistem, item.selisih, item.nilai_selisih), (8.0, Some(-1.0), Some(-20_000.0)));
+        // Sold after the count, before posting
+        sell(conn, 1.0);
+
+        let posted = post(conn, &session.id, None).unwrap();
+        assert_eq!(posted.status, "DIPOSTING");
+        let (stok, opname): (f64, f64) = conn
+            .query_row(
+                "SELECT jumlah_stok, (SELECT SUM(jumlah) FROM mutasi_stok WHERE jenis = 'STOK_OPNAME')
+                 FROM barang WHERE id = 'b-vinyl'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((stok, opname), (6.0, -1.0));
+        assert!(post(conn, &session.id, None).is_err());
+    }
+
+    #[test]
+    fn counts_are_converted_to_satuan_dasar() {
+        let db = test_db();
+        let mut guard = db.lock().unwrap();
+        let conn = guard.as_mut().unwrap();
+        conn.execute_batch(
+            "INSERT INTO kategori_barang (id, nama) VALUES ('k-bahan', 'Bahan');
+             INSERT INTO barang (id, nama, satuan_dasar, jumlah_stok, kategori_id)
+             VALUES ('b-vinyl', 'Vinyl', 'm2', 100, 'k-bahan');
+             INSERT INTO harga_barang_satuan (id, barang_id, nama_satuan, faktor_konversi)
+             VALUES ('h-roll', 'b-vinyl', 'roll', 50);",
+        )
+        .unwrap();
+        let session = open(
+            conn,
+            OpenOpnameRequest {
+                kategori_ids: vec!["k-bahan".to_string()],
+                catatan: None,
+                dibuat_oleh: None,
+                tanggal: None,
+            },
+        )
+        .unwrap();
+
+        let item = count(conn, &session.id, 1.5, Some("h-roll"));
+        assert_eq!((item.jumlah_hitung, item.selisih), (Some(75.0), Some(-25.0)));
+    }
+}
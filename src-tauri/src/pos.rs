@@ -0,0 +1,720 @@
+use crate::pricing;
+use crate::stock;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Payment methods that are expected to settle the sale immediately
+const FULL_PAYMENT_METHODS: &[&str] = &["CASH", "TRANSFER", "QRIS", "DEBIT"];
+
+/// Payment methods that always leave a receivable
+const CREDIT_PAYMENT_METHODS: &[&str] = &["DOWN_PAYMENT", "NET30"];
+
+/// One cart line sent by the POS page. For barang priced by area
+/// (`butuh_dimensi_status = 1`), `panjang` and `lebar` (m) are required and
+/// the area pricing replaces `jumlah`, price and stock use.
+#[derive(Debug, Deserialize)]
+pub struct SaleLine {
+    pub barang_id: String,
+    pub harga_satuan_id: String,
+    pub jumlah: f64,
+    pub panjang: Option<f64>,
+    pub lebar: Option<f64>,
+    /// Pieces of `panjang` x `lebar`, 1 when absent; `jumlah` is then the
+    /// area the page calculated
+    pub jumlah_pcs: Option<f64>,
+    /// Round the smaller dimension up to a roll size
+    #[serde(default)]
+    pub pembulatan: bool,
+    #[serde(default)]
+    pub finishing: Vec<FinishingLine>,
+}
+
+/// Finishing requested for a cart line (laminating, cutting, ...)
+#[derive(Debug, Deserialize)]
+pub struct FinishingLine {
+    pub jenis_finishing: String,
+    pub keterangan: Option<String>,
+}
+
+/// Checkout payload for `create_sale`
+#[derive(Debug, Deserialize)]
+pub struct CreateSaleRequest {
+    pub pelanggan_id: Option<String>,
+    pub items: Vec<SaleLine>,
+    #[serde(default)]
+    pub jumlah_dibayar: f64,
+    pub metode_pembayaran: String,
+    pub catatan: Option<String>,
+    pub kasir_id: Option<String>,
+    /// Sale date (YYYY-MM-DD), defaults to today in Asia/Jakarta
+    pub tanggal: Option<String>,
+    pub prioritas: Option<String>,
+}
+
+/// Summary returned to the POS page after checkout
+#[derive(Debug, Serialize)]
+pub struct CreateSaleResult {
+    pub id: String,
+    pub nomor_invoice: String,
+    pub spk_number: String,
+    pub total_jumlah: f64,
+    pub jumlah_kembalian: f64,
+    pub status_pembayaran: String,
+}
+
+/// Unit price and amounts resolved for a cart line
+struct LinePrice {
+    nama_satuan: String,
+    faktor_konversi: f64,
+    harga_satuan: f64,
+    barang_nama: String,
+    lacak_inventori: bool,
+    /// Quantity in the price unit
+    jumlah: f64,
+    subtotal: f64,
+    /// In `satuan_dasar`
+    stok_keluar: f64,
+    keterangan_dimensi: Option<String>,
+}
+
+/// Today's date in Asia/Jakarta (UTC+7), as YYYY-MM-DD
+pub fn today_jakarta() -> String {
+    let offset = chrono::FixedOffset::east_opt(7 * 3600).unwrap();
+    chrono::Utc::now().with_timezone(&offset).format("%Y-%m-%d").to_string()
+}
+
+/// Parse a YYYY-MM-DD date sent by the webview
+pub fn parse_tanggal(value: &str) -> Result<chrono::NaiveDate, String> {
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("Tanggal tidak valid (YYYY-MM-DD): {}", value))
+}
+
+/// Format an amount the way the cash book does (Rp 1.250.000)
+pub fn format_rupiah(amount: f64) -> String {
+    let digits = format!("{:.0}", amount.abs());
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push('.');
+        }
+        grouped.push(c);
+    }
+    if amount < 0.0 {
+        format!("-{}", grouped)
+    } else {
+        grouped
+    }
+}
+
+/// Record a complete POS sale in one transaction: penjualan, item_penjualan,
+/// stock movements and frequency updates, receivable, cash book entry and production order.
+/// The sync capture triggers queue every written row for upload.
+///
+/// Prices always come from `harga_barang_satuan`; whatever the webview
+/// computed is ignored. Cash book balances (`saldo`, `laba_bersih`) are still
+/// recalculated by the frontend afterwards.
+pub fn create_sale(conn: &mut Connection, req: CreateSaleRequest) -> Result<CreateSaleResult, String> {
+    if req.items.is_empty() {
+        return Err("Items tidak boleh kosong".to_string());
+    }
+    if let Some(line) = req.items.iter().find(|l| !l.jumlah.is_finite() || l.jumlah <= 0.0) {
+        return Err(format!("Jumlah untuk barang {} harus lebih dari 0", line.barang_id));
+    }
+    if !req.jumlah_dibayar.is_finite() || req.jumlah_dibayar < 0.0 {
+        return Err("Jumlah dibayar tidak boleh negatif".to_string());
+    }
+    let tanggal = req.tanggal.clone().unwrap_or_else(today_jakarta);
+    let tanggal_jual = parse_tanggal(&tanggal)?;
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let (pelanggan_nama, is_member) = match &req.pelanggan_id {
+        Some(id) => tx
+            .query_row(
+                "SELECT nama, COALESCE(member_status, 0) FROM pelanggan WHERE id = ?1",
+                params![id],
+                |row| Ok((Some(row.get::<_, String>(0)?), row.get::<_, i64>(1)? != 0)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Pelanggan tidak ditemukan: {}", id))?,
+        None => (None, false),
+    };
+
+    // Resolve every price before writing anything
+    let area_config = pricing::load_config(&tx)?;
+    let mut prices = Vec::with_capacity(req.items.len());
+    let mut total_jumlah = 0.0;
+    for line in &req.items {
+        let price = resolve_price(&tx, line, is_member, &area_config)?;
+        total_jumlah += price.subtotal;
+        prices.push(price);
+    }
+
+    if total_jumlah <= 0.0 {
+        return Err("Total jumlah harus lebih dari 0".to_string());
+    }
+
+    let metode = req.metode_pembayaran.as_str();
+    let is_full_method = FULL_PAYMENT_METHODS.contains(&metode);
+    let is_lunas = is_full_method && req.jumlah_dibayar >= total_jumlah;
+    let is_piutang = CREDIT_PAYMENT_METHODS.contains(&metode)
+        || (is_full_method && req.jumlah_dibayar > 0.0 && req.jumlah_dibayar < total_jumlah);
+
+    // Paying it all up front is a cash sale, not a receivable
+    if CREDIT_PAYMENT_METHODS.contains(&metode) && req.jumlah_dibayar >= total_jumlah {
+        return Err(format!(
+            "Pembayaran {} harus kurang dari total Rp {}, gunakan pembayaran lunas",
+            metode,
+            format_rupiah(total_jumlah)
+        ));
+    }
+    if !is_lunas && !is_piutang {
+        return Err(format!(
+            "Pembayaran {} tidak valid untuk total Rp {}",
+            metode,
+            format_rupiah(total_jumlah)
+        ));
+    }
+
+    let jumlah_terbayar = req.jumlah_dibayar.min(total_jumlah);
+    let jumlah_kembalian = if is_lunas { req.jumlah_dibayar - total_jumlah } else { 0.0 };
+    let catatan = req.catatan.as_deref().map(str::trim).filter(|c| !c.is_empty());
+
+    // penjualan
+    let sale_id = Uuid::new_v4().to_string();
+    let nomor_invoice = next_invoice_number(&tx, &tanggal)?;
+    tx.execute(
+        "INSERT INTO penjualan (id, nomor_invoice, pelanggan_id, total_jumlah, jumlah_dibayar,
+            jumlah_kembalian, metode_pembayaran, kasir_id, catatan, dibuat_pada, diperbarui_pada)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?10)",
+        params![
+            sale_id,
+            nomor_invoice,
+            req.pelanggan_id,
+            total_jumlah,
+            req.jumlah_dibayar,
+            jumlah_kembalian,
+            metode,
+            req.kasir_id,
+            catatan,
+            now
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    // item_penjualan + stock
+    let mut item_ids = Vec::with_capacity(req.items.len());
+    for (line, price) in req.items.iter().zip(&prices) {
+        let item_id = Uuid::new_v4().to_string();
+        tx.execute(
+            "INSERT INTO item_penjualan (id, penjualan_id, barang_id, harga_satuan_id, jumlah,
+                nama_satuan, faktor_konversi, harga_satuan, subtotal, dibuat_pada)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                item_id,
+                sale_id,
+                line.barang_id,
+                line.harga_satuan_id,
+                price.jumlah,
+                price.nama_satuan,
+                price.faktor_konversi,
+                price.harga_satuan,
+                price.subtotal,
+                now
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+
+        tx.execute(
+            "UPDATE barang
+             SET frekuensi_terjual = COALESCE(frekuensi_terjual, 0) + 1,
+                 diperbarui_pada = ?1
+             WHERE id = ?2",
+            params![now, line.barang_id],
+        )
+        .map_err(|e| e.to_string())?;
+
+        if price.lacak_inventori {
+            stock::apply(
+                &tx,
+                &stock::Mutasi {
+                    barang_id: &line.barang_id,
+                    jenis: stock::JenisMutasi::Penjualan,
+                    jumlah: -price.stok_keluar,
+                    referensi: Some(("item_penjualan", &item_id)),
+                    catatan: Some(&nomor_invoice),
+                    dibuat_oleh: req.kasir_id.as_deref(),
+                },
+                &now,
+            )?;
+        }
+
+        item_ids.push(item_id);
+    }
+
+    // piutang_penjualan
+    let status_pembayaran = if is_lunas {
+        "LUNAS".to_string()
+    } else {
+        let sisa_piutang = total_jumlah - jumlah_terbayar;
+        let (status, catatan_piutang, jatuh_tempo) = match metode {
+            "NET30" => (
+                "AKTIF",
+                "Piutang dengan jatuh tempo 30 hari".to_string(),
+                Some((tanggal_jual + chrono::Duration::days(30)).format("%Y-%m-%d").to_string()),
+            ),
+            "DOWN_PAYMENT" => (
+                if jumlah_terbayar > 0.0 { "SEBAGIAN" } else { "AKTIF" },
+                "Down Payment - pembayaran sebagian".to_string(),
+                None,
+            ),
+            _ => (
+                "SEBAGIAN",
+                format!("Pembayaran {} tidak mencukupi", metode),
+                None,
+            ),
+        };
+
+        let piutang_id = Uuid::new_v4().to_string();
+        tx.execute(
+            "INSERT INTO piutang_penjualan (id, id_penjualan, jumlah_piutang, jumlah_terbayar,
+                sisa_piutang, jatuh_tempo, status, catatan, dibuat_pada, diperbarui_pada)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9)",
+            params![
+                piutang_id,
+                sale_id,
+                total_jumlah,
+                jumlah_terbayar,
+                sisa_piutang,
+                jatuh_tempo,
+                status,
+                catatan_piutang,
+                now
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+
+        status.to_string()
+    };
+
+    // keuangan (only for money actually received)
+    let pelanggan_label = pelanggan_nama.clone().unwrap_or_else(|| "Walk-in".to_string());
+    if is_lunas {
+        let mut keperluan = format!("Penjualan {} - {}", nomor_invoice, pelanggan_label);
+        if let Some(c) = catatan {
+            let excerpt: String = c.chars().take(25).collect();
+            let ellipsis = if c.chars().count() > 25 { "..." } else { "" };
+            keperluan.push_str(&format!(" ({}{})", excerpt, ellipsis));
+        }
+        keperluan.push_str(&format!(" [REF:{}]", sale_id));
+        insert_finance_entry(&tx, &tanggal, "OMZET", total_jumlah, &keperluan, catatan, req.kasir_id.as_deref(), &now)?;
+    } else if jumlah_terbayar > 0.0 {
+        let prefix = if metode == "DOWN_PAYMENT" { "DP" } else { "Pembayaran Sebagian" };
+        let keperluan = format!(
+            "{} {} - {} (Rp {} dari Rp {}) [REF:{}]",
+            prefix,
+            nomor_invoice,
+            pelanggan_label,
+            format_rupiah(jumlah_terbayar),
+            format_rupiah(total_jumlah),
+            sale_id
+        );
+        insert_finance_entry(&tx, &tanggal, "PIUTANG", jumlah_terbayar, &keperluan, catatan, req.kasir_id.as_deref(), &now)?;
+    }
+
+    // order_produksi + item_produksi + item_finishing
+    let order_id = Uuid::new_v4().to_string();
+    let spk_number = next_spk_number(&tx)?;
+    tx.execute(
+        "INSERT INTO order_produksi (id, penjualan_id, nomor_spk, pelanggan_nama, total_item,
+            status, prioritas, catatan, dibuat_oleh, dibuat_pada, diperbarui_pada)
+         VALUES (?1, ?2, ?3, ?4, ?5, 'MENUNGGU', ?6, ?7, ?8, ?9, ?9)",
+        params![
+            order_id,
+            sale_id,
+            spk_number,
+            pelanggan_nama,
+            req.items.len() as i64,
+            req.prioritas.as_deref().unwrap_or("NORMAL"),
+            catatan,
+            req.kasir_id,
+            now
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    for ((line, price), item_id) in req.items.iter().zip(&prices).zip(&item_ids) {
+        let produksi_id = Uuid::new_v4().to_string();
+        tx.execute(
+            "INSERT INTO item_produksi (id, order_produksi_id, item_penjualan_id, barang_nama,
+                jumlah, nama_satuan, panjang, lebar, keterangan_dimensi, status, dibuat_pada, diperbarui_pada)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, 'MENUNGGU', ?10, ?10)",
+            params![
+                produksi_id,
+                order_id,
+                item_id,
+                price.barang_nama,
+                price.jumlah,
+                price.nama_satuan,
+                line.panjang,
+                line.lebar,
+                price.keterangan_dimensi,
+                now
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+
+        for finishing in &line.finishing {
+            let finishing_id = Uuid::new_v4().to_string();
+            let keterangan = finishing.keterangan.as_deref().map(str::trim).filter(|k| !k.is_empty());
+            tx.execute(
+                "INSERT INTO item_finishing (id, item_produksi_id, jenis_finishing, keterangan,
+                    status, dibuat_pada, diperbarui_pada)
+                 VALUES (?1, ?2, ?3, ?4, 'MENUNGGU', ?5, ?5)",
+                params![finishing_id, produksi_id, finishing.jenis_finishing, keterangan, now],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(CreateSaleResult {
+        id: sale_id,
+        nomor_invoice,
+        spk_number,
+        total_jumlah,
+        jumlah_kembalian,
+        status_pembayaran,
+    })
+}
+
+/// Look up the unit price for a cart line, using the member price when the
+/// customer is a member and one is set. Lines with dimensions for barang
+/// priced by area go through the area pricing.
+fn resolve_price(
+    tx: &Transaction,
+    line: &SaleLine,
+    is_member: bool,
+    area_config: &pricing::AreaPricingConfig,
+) -> Result<LinePrice, String> {
+    let (nama_satuan, faktor_konversi, harga_jual, harga_member, barang_nama, lacak, butuh_dimensi, satuan_dasar, spesifikasi) = tx
+        .query_row(
+            "SELECT h.nama_satuan, h.faktor_konversi, COALESCE(h.harga_jual, 0),
+                    COALESCE(h.harga_member, 0), b.nama, COALESCE(b.lacak_inventori_status, 1),
+                    COALESCE(b.butuh_dimensi_status, 0), b.satuan_dasar, b.spesifikasi
+             FROM harga_barang_satuan h
+             JOIN barang b ON b.id = h.barang_id
+             WHERE h.id = ?1 AND h.barang_id = ?2",
+            params![line.harga_satuan_id, line.barang_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, f64>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, i64>(5)?,
+                    row.get::<_, i64>(6)?,
+                    row.get::<_, Option<String>>(7)?,
+                    row.get::<_, Option<String>>(8)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| {
+            format!(
+                "Satuan {} tidak ditemukan untuk barang {}",
+                line.harga_satuan_id, line.barang_id
+            )
+        })?;
+
+    let harga_satuan = if is_member && harga_member > 0.0 {
+        harga_member
+    } else {
+        harga_jual
+    };
+
+    let mut price = LinePrice {
+        nama_satuan,
+        faktor_konversi,
+        harga_satuan,
+        barang_nama,
+        lacak_inventori: lacak != 0,
+        jumlah: line.jumlah,
+        subtotal: line.jumlah * harga_satuan,
+        stok_keluar: line.jumlah * faktor_konversi,
+        keterangan_dimensi: None,
+    };
+
+    if butuh_dimensi == 1 {
+        let (Some(panjang), Some(lebar)) = (line.panjang, line.lebar) else {
+            return Err(format!("Panjang dan lebar harus diisi untuk {}", price.barang_nama));
+        };
+        let jumlah_pcs = line.jumlah_pcs.unwrap_or(1.0);
+        let job = pricing::AreaJob {
+            panjang,
+            lebar,
+            jumlah_pcs,
+            pembulatan: line.pembulatan,
+        };
+        let unit = pricing::UnitPrice {
+            faktor_konversi,
+            harga_satuan,
+            satuan_dasar: satuan_dasar.as_deref(),
+            spesifikasi: spesifikasi.as_deref(),
+        };
+        let area = pricing::calculate(&job, &unit, area_config)
+            .map_err(|e| format!("{}: {}", price.barang_nama, e))?;
+
+        price.jumlah = area.jumlah;
+        price.subtotal = area.subtotal;
+        price.stok_keluar = area.stok_keluar;
+        price.keterangan_dimensi = Some(format!(
+            "{} pcs @ {} x {} m (tagih {} x {} m)",
+            jumlah_pcs, panjang, lebar, area.panjang_tagih, area.lebar_tagih
+        ));
+    }
+
+    Ok(price)
+}
+
+/// Next invoice number for the day: INV-YYYYMMDD-NNN
+fn next_invoice_number(tx: &Transaction, tanggal: &str) -> Result<String, String> {
+    let prefix = format!("INV-{}-", tanggal.replace('-', ""));
+    // Numeric, so -1000 follows -999
+    let last: Option<i64> = tx
+        .query_row(
+            "SELECT MAX(CAST(SUBSTR(nomor_invoice, ?2) AS INTEGER)) FROM penjualan
+             WHERE nomor_invoice LIKE ?1 || '%'",
+            params![prefix, prefix.len() as i64 + 1],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    Ok(format!("{}{:03}", prefix, last.unwrap_or(0) + 1))
+}
+
+/// Next production order number: SPK-NNNN
+fn next_spk_number(tx: &Transaction) -> Result<String, String> {
+    let last: Option<i64> = tx
+        .query_row(
+            "SELECT MAX(CAST(SUBSTR(nomor_spk, 5) AS INTEGER)) FROM order_produksi
+             WHERE nomor_spk LIKE 'SPK-%'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    Ok(format!("SPK-{:04}", last.unwrap_or(0) + 1))
+}
+
+/// Append an income row to the cash book
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn insert_finance_entry(
+    tx: &Transaction,
+    tanggal: &str,
+    kategori_transaksi: &str,
+    debit: f64,
+    keperluan: &str,
+    catatan: Option<&str>,
+    dibuat_oleh: Option<&str>,
+    now: &str,
+) -> Result<(), String> {
+    let urutan = next_finance_order(tx)?;
+    let id = Uuid::new_v4().to_string();
+    tx.execute(
+        "INSERT INTO keuangan (id, tanggal, kategori_transaksi, debit, kredit, keperluan, omzet,
+            catatan, dibuat_oleh, urutan_tampilan, dibuat_pada, diperbarui_pada)
+         VALUES (?1, ?2, ?3, ?4, 0, ?5, ?4, ?6, ?7, ?8, ?9, ?9)",
+        params![id, tanggal, kategori_transaksi, debit, keperluan, catatan, dibuat_oleh, urutan, now],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Append a material expense row (SUPPLY, HUTANG) to the cash book
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn insert_expense_entry(
+    tx: &Transaction,
+    tanggal: &str,
+    kategori_transaksi: &str,
+    kredit: f64,
+    keperluan: &str,
+    catatan: Option<&str>,
+    dibuat_oleh: Option<&str>,
+    now: &str,
+) -> Result<(), String> {
+    let urutan = next_finance_order(tx)?;
+    let id = Uuid::new_v4().to_string();
+    tx.execute(
+        "INSERT INTO keuangan (id, tanggal, kategori_transaksi, debit, kredit, keperluan, biaya_bahan,
+            catatan, dibuat_oleh, urutan_tampilan, dibuat_pada, diperbarui_pada)
+         VALUES (?1, ?2, ?3, 0, ?4, ?5, ?4, ?6, ?7, ?8, ?9, ?9)",
+        params![id, tanggal, kategori_transaksi, kredit, keperluan, catatan, dibuat_oleh, urutan, now],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+fn next_finance_order(tx: &Transaction) -> Result<i64, String> {
+    tx.query_row(
+        "SELECT COALESCE(MAX(urutan_tampilan), 0) + 1 FROM keuangan",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_postgrest::test_db;
+
+    /// Stiker: lembar at 2.000 (member 1.500) and pak of 10 at 18.000 (no
+    /// member price), 100 lembar in stock; one member and one regular customer
+    fn seed(conn: &Connection) {
+        conn.execute_batch(
+            "INSERT INTO barang (id, nama, satuan_dasar, jumlah_stok) VALUES ('b-stiker', 'Stiker', 'lembar', 100);
+             INSERT INTO harga_barang_satuan (id, barang_id, nama_satuan, faktor_konversi, harga_jual, harga_member)
+             VALUES ('h-lembar', 'b-stiker', 'lembar', 1, 2000, 1500),
+                    ('h-pak', 'b-stiker', 'pak', 10, 18000, 0);
+             INSERT INTO pelanggan (id, nama, member_status) VALUES ('p-member', 'Toko Member', 1),
+                                                                    ('p-umum', 'Budi', 0);",
+        )
+        .unwrap();
+    }
+
+    fn line(harga_satuan_id: &str, jumlah: f64) -> SaleLine {
+        SaleLine {
+            barang_id: "b-stiker".to_string(),
+            harga_satuan_id: harga_satuan_id.to_string(),
+            jumlah,
+            panjang: None,
+            lebar: None,
+            jumlah_pcs: None,
+            pembulatan: false,
+            finishing: Vec::new(),
+        }
+    }
+
+    fn sale(pelanggan_id: Option<&str>, metode: &str, jumlah_dibayar: f64) -> CreateSaleRequest {
+        CreateSaleRequest {
+            pelanggan_id: pelanggan_id.map(str::to_string),
+            // 3 lembar and 1 pak: 24.000 at normal prices
+            items: vec![line("h-lembar", 3.0), line("h-pak", 1.0)],
+            jumlah_dibayar,
+            metode_pembayaran: metode.to_string(),
+            catatan: None,
+            kasir_id: None,
+            tanggal: Some("2026-01-15".to_string()),
+            prioritas: None,
+        }
+    }
+
+    /// (kategori_transaksi, debit, keperluan) of the sale's cash book rows
+    fn keuangan(conn: &Connection, sale_id: &str) -> Vec<(String, f64, String)> {
+        let mut stmt = conn
+            .prepare("SELECT kategori_transaksi, debit, keperluan FROM keuangan WHERE keperluan LIKE '%' || ?1 || '%'")
+            .unwrap();
+        stmt.query_map(params![sale_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .unwrap()
+    }
+
+    /// (jumlah_terbayar, sisa_piutang, status, jatuh_tempo) of the sale's receivable
+    fn piutang(conn: &Connection, sale_id: &str) -> (f64, f64, String, Option<String>) {
+        conn.query_row(
+            "SELECT jumlah_terbayar, sisa_piutang, status, jatuh_tempo FROM piutang_penjualan WHERE id_penjualan = ?1",
+            params![sale_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn prices_and_stock_come_from_the_price_units() {
+        let db = test_db();
+        let mut guard = db.lock().unwrap();
+        let conn = guard.as_mut().unwrap();
+        seed(conn);
+
+        let result = create_sale(conn, sale(Some("p-umum"), "CASH", 30_000.0)).unwrap();
+        assert_eq!(result.total_jumlah, 24_000.0);
+        assert_eq!(result.jumlah_kembalian, 6_000.0);
+        assert_eq!(result.status_pembayaran, "LUNAS");
+
+        let stok: f64 = conn
+            .query_row("SELECT jumlah_stok FROM barang WHERE id = 'b-stiker'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stok, 87.0);
+        let rows = keuangan(conn, &result.id);
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].0.as_str(), rows[0].1), ("OMZET", 24_000.0));
+    }
+
+    #[test]
+    fn members_pay_the_member_price_where_one_is_set() {
+        let db = test_db();
+        let mut guard = db.lock().unwrap();
+        let conn = guard.as_mut().unwrap();
+        seed(conn);
+
+        let result = create_sale(conn, sale(Some("p-member"), "CASH", 30_000.0)).unwrap();
+        // 3 x 1.500 + 18.000 (the pak has no member price)
+        assert_eq!(result.total_jumlah, 22_500.0);
+    }
+
+    #[test]
+    fn net30_is_due_thirty_days_after_the_sale_date() {
+        let db = test_db();
+        let mut guard = db.lock().unwrap();
+        let conn = guard.as_mut().unwrap();
+        seed(conn);
+
+        let result = create_sale(conn, sale(Some("p-umum"), "NET30", 0.0)).unwrap();
+        assert_eq!(result.status_pembayaran, "AKTIF");
+        assert_eq!(
+            piutang(conn, &result.id),
+            (0.0, 24_000.0, "AKTIF".to_string(), Some("2026-02-14".to_string()))
+        );
+        assert!(keuangan(conn, &result.id).is_empty());
+    }
+
+    #[test]
+    fn down_payment_is_booked_and_the_rest_is_receivable() {
+        let db = test_db();
+        let mut guard = db.lock().unwrap();
+        let conn = guard.as_mut().unwrap();
+        seed(conn);
+
+        let result = create_sale(conn, sale(Some("p-umum"), "DOWN_PAYMENT", 10_000.0)).unwrap();
+        assert_eq!(result.status_pembayaran, "SEBAGIAN");
+        assert_eq!(piutang(conn, &result.id), (10_000.0, 14_000.0, "SEBAGIAN".to_string(), None));
+
+        let rows = keuangan(conn, &result.id);
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].0.as_str(), rows[0].1), ("PIUTANG", 10_000.0));
+        assert!(rows[0].2.starts_with("DP INV-20260115-001"), "{}", rows[0].2);
+    }
+
+    #[test]
+    fn down_payment_covering_the_total_is_rejected() {
+        let db = test_db();
+        let mut guard = db.lock().unwrap();
+        let conn = guard.as_mut().unwrap();
+        seed(conn);
+
+        assert!(create_sale(conn, sale(Some("p-umum"), "DOWN_PAYMENT", 24_000.0)).is_err());
+        let sales: i64 = conn
+            .query_row("SELECT COUNT(*) FROM penjualan", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(sales, 0);
+    }
+}
@@ -0,0 +1,550 @@
+use crate::settings;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+const ROLL_SIZES_KEY: &str = "pricing.roll_sizes";
+const MINIMUM_AREA_KEY: &str = "pricing.minimum_area";
+const MINIMUM_CHARGE_KEY: &str = "pricing.minimum_charge";
+const AREA_STEP_KEY: &str = "pricing.area_step";
+const PRICE_ROUNDING_KEY: &str = "pricing.price_rounding";
+
+/// Same defaults as the POS page's roll size setting
+const DEFAULT_ROLL_SIZES: [f64; 6] = [0.5, 1.0, 1.5, 2.0, 2.5, 3.0];
+const DEFAULT_AREA_STEP: f64 = 0.01;
+const MAX_DIMENSION: f64 = 100.0;
+
+/// Tolerance for comparing lengths in meters
+const EPSILON: f64 = 1e-9;
+
+/// Area pricing rules stored in `app_settings`. Minimums and price rounding
+/// are off (0) until configured.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AreaPricingConfig {
+    /// Sizes (m) the smaller dimension is rounded up to when rounding is asked for
+    pub roll_sizes: Vec<f64>,
+    /// Billable area per piece is at least this (m²)
+    pub minimum_area: f64,
+    /// A line costs at least this (Rp)
+    pub minimum_charge: f64,
+    /// Billable area per piece is rounded up to a multiple of this (m²)
+    pub area_step: f64,
+    /// Line prices are rounded up to a multiple of this (Rp)
+    pub price_rounding: f64,
+}
+
+/// Payload of `quote_area_price`
+#[derive(Debug, Deserialize)]
+pub struct AreaQuoteRequest {
+    pub barang_id: String,
+    /// Price unit; the barang's default unit when absent
+    pub harga_satuan_id: Option<String>,
+    /// Meters
+    pub panjang: f64,
+    pub lebar: f64,
+    #[serde(default = "one")]
+    pub jumlah_pcs: f64,
+    /// Round the smaller dimension up to a roll size
+    #[serde(default)]
+    pub pembulatan: bool,
+    /// Members get `harga_member` when one is set
+    pub pelanggan_id: Option<String>,
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+/// A dimensioned print job
+#[derive(Debug, Clone, Copy)]
+pub struct AreaJob {
+    pub panjang: f64,
+    pub lebar: f64,
+    pub jumlah_pcs: f64,
+    pub pembulatan: bool,
+}
+
+/// Price unit chosen for the job
+#[derive(Debug)]
+pub struct UnitPrice<'a> {
+    pub faktor_konversi: f64,
+    pub harga_satuan: f64,
+    pub satuan_dasar: Option<&'a str>,
+    pub spesifikasi: Option<&'a str>,
+}
+
+/// Price and material use of a job; areas in m², lengths in meters
+#[derive(Debug, Clone, Serialize)]
+pub struct AreaCalculation {
+    /// Dimensions billed, after roll size rounding
+    pub panjang_tagih: f64,
+    pub lebar_tagih: f64,
+    /// Area actually printed, all pieces
+    pub luas_cetak: f64,
+    /// Area billed, all pieces
+    pub luas_tagih: f64,
+    pub minimum_luas_diterapkan: bool,
+    pub minimum_harga_diterapkan: bool,
+    /// Quantity in the price unit (`luas_tagih / faktor_konversi`)
+    pub jumlah: f64,
+    pub subtotal: f64,
+    /// Parsed from `spesifikasi`; None when it does not state one
+    pub lebar_roll: Option<f64>,
+    /// Length of roll used, pieces nested across the width
+    pub panjang_roll: Option<f64>,
+    /// Material used including roll waste
+    pub konsumsi_m2: f64,
+    pub sisa_m2: f64,
+    /// Amount to deduct from stock, in `satuan_dasar`: running meters for
+    /// barang stocked by the meter with a known roll width, else m²
+    pub stok_keluar: f64,
+}
+
+/// Result of `quote_area_price`
+#[derive(Debug, Serialize)]
+pub struct AreaQuote {
+    pub barang_id: String,
+    pub harga_satuan_id: String,
+    pub nama_satuan: String,
+    pub faktor_konversi: f64,
+    pub harga_satuan: f64,
+    pub harga_member: bool,
+    pub satuan_dasar: Option<String>,
+    pub panjang: f64,
+    pub lebar: f64,
+    pub jumlah_pcs: f64,
+    #[serde(flatten)]
+    pub calculation: AreaCalculation,
+}
+
+/// Stored rules, with defaults for anything not configured
+pub fn load_config(conn: &Connection) -> Result<AreaPricingConfig, String> {
+    let roll_sizes = settings::get(conn, ROLL_SIZES_KEY)?
+        .and_then(|v| serde_json::from_str::<Vec<f64>>(&v).ok())
+        .filter(|sizes| !sizes.is_empty())
+        .unwrap_or_else(|| DEFAULT_ROLL_SIZES.to_vec());
+
+    Ok(AreaPricingConfig {
+        roll_sizes,
+        minimum_area: settings::get_or(conn, MINIMUM_AREA_KEY, 0.0)?,
+        minimum_charge: settings::get_or(conn, MINIMUM_CHARGE_KEY, 0.0)?,
+        area_step: settings::get_or(conn, AREA_STEP_KEY, DEFAULT_AREA_STEP)?,
+        price_rounding: settings::get_or(conn, PRICE_ROUNDING_KEY, 0.0)?,
+    })
+}
+
+/// Validate and store the rules; roll sizes are kept sorted
+pub fn save_config(conn: &Connection, config: &AreaPricingConfig) -> Result<(), String> {
+    if config.roll_sizes.is_empty() {
+        return Err("Masukkan minimal satu ukuran roll".to_string());
+    }
+    if config.roll_sizes.iter().any(|s| !s.is_finite() || *s <= 0.0 || *s > MAX_DIMENSION) {
+        return Err(format!("Ukuran roll harus antara 0 dan {} m", MAX_DIMENSION));
+    }
+    for (name, value) in [
+        ("Luas minimum", config.minimum_area),
+        ("Harga minimum", config.minimum_charge),
+        ("Kelipatan luas", config.area_step),
+        ("Pembulatan harga", config.price_rounding),
+    ] {
+        if !value.is_finite() || value < 0.0 {
+            return Err(format!("{} tidak boleh negatif", name));
+        }
+    }
+
+    let mut roll_sizes = config.roll_sizes.clone();
+    roll_sizes.sort_by(f64::total_cmp);
+    roll_sizes.dedup();
+    let roll_sizes = serde_json::to_string(&roll_sizes).map_err(|e| e.to_string())?;
+
+    settings::set(conn, ROLL_SIZES_KEY, &roll_sizes)?;
+    settings::set(conn, MINIMUM_AREA_KEY, &config.minimum_area.to_string())?;
+    settings::set(conn, MINIMUM_CHARGE_KEY, &config.minimum_charge.to_string())?;
+    settings::set(conn, AREA_STEP_KEY, &config.area_step.to_string())?;
+    settings::set(conn, PRICE_ROUNDING_KEY, &config.price_rounding.to_string())
+}
+
+/// Price a job for a barang priced by area, with the stored rules
+pub fn quote(conn: &Connection, req: &AreaQuoteRequest) -> Result<AreaQuote, String> {
+    let is_member = match &req.pelanggan_id {
+        Some(id) => conn
+            .query_row(
+                "SELECT COALESCE(member_status, 0) FROM pelanggan WHERE id = ?1",
+                params![id],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Pelanggan tidak ditemukan: {}", id))?
+            != 0,
+        None => false,
+    };
+
+    let (butuh_dimensi, satuan_dasar, spesifikasi): (i64, Option<String>, Option<String>) = conn
+        .query_row(
+            "SELECT COALESCE(butuh_dimensi_status, 0), satuan_dasar, spesifikasi FROM barang WHERE id = ?1",
+            params![req.barang_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Barang tidak ditemukan: {}", req.barang_id))?;
+    if butuh_dimensi != 1 {
+        return Err("Barang ini tidak dihitung per luas".to_string());
+    }
+
+    // The chosen unit, else the default one, else the smallest
+    let (harga_satuan_id, nama_satuan, faktor_konversi, harga_jual, harga_member): (String, String, f64, f64, f64) = conn
+        .query_row(
+            "SELECT id, nama_satuan, faktor_konversi, COALESCE(harga_jual, 0), COALESCE(harga_member, 0)
+             FROM harga_barang_satuan
+             WHERE barang_id = ?1 AND (?2 IS NULL OR id = ?2)
+             ORDER BY COALESCE(default_status, 0) DESC, faktor_konversi, COALESCE(urutan_tampilan, 0)
+             LIMIT 1",
+            params![req.barang_id, req.harga_satuan_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Satuan harga tidak ditemukan untuk barang {}", req.barang_id))?;
+
+    let use_member = is_member && harga_member > 0.0;
+    let harga_satuan = if use_member { harga_member } else { harga_jual };
+    let job = AreaJob {
+        panjang: req.panjang,
+        lebar: req.lebar,
+        jumlah_pcs: req.jumlah_pcs,
+        pembulatan: req.pembulatan,
+    };
+    let unit = UnitPrice {
+        faktor_konversi,
+        harga_satuan,
+        satuan_dasar: satuan_dasar.as_deref(),
+        spesifikasi: spesifikasi.as_deref(),
+    };
+    let calculation = calculate(&job, &unit, &load_config(conn)?)?;
+
+    Ok(AreaQuote {
+        barang_id: req.barang_id.clone(),
+        harga_satuan_id,
+        nama_satuan,
+        faktor_konversi,
+        harga_satuan,
+        harga_member: use_member,
+        satuan_dasar,
+        panjang: req.panjang,
+        lebar: req.lebar,
+        jumlah_pcs: req.jumlah_pcs,
+        calculation,
+    })
+}
+
+/// Billable area, price and material use of a job
+pub fn calculate(job: &AreaJob, unit: &UnitPrice, config: &AreaPricingConfig) -> Result<AreaCalculation, String> {
+    for dimension in [job.panjang, job.lebar] {
+        if !dimension.is_finite() || dimension <= 0.0 || dimension > MAX_DIMENSION {
+            return Err(format!("Panjang dan lebar harus antara 0 dan {} m", MAX_DIMENSION));
+        }
+    }
+    if !job.jumlah_pcs.is_finite() || job.jumlah_pcs < 1.0 || job.jumlah_pcs.fract() != 0.0 {
+        return Err("Jumlah harus bilangan bulat minimal 1".to_string());
+    }
+    if !unit.faktor_konversi.is_finite() || unit.faktor_konversi <= 0.0 {
+        return Err("Faktor konversi satuan tidak valid".to_string());
+    }
+
+    let (panjang_tagih, lebar_tagih) = if job.pembulatan {
+        round_to_roll_size(job.panjang, job.lebar, &config.roll_sizes)
+    } else {
+        (job.panjang, job.lebar)
+    };
+
+    let mut luas_per_pcs = panjang_tagih * lebar_tagih;
+    let minimum_luas_diterapkan = luas_per_pcs < config.minimum_area;
+    if minimum_luas_diterapkan {
+        luas_per_pcs = config.minimum_area;
+    }
+    luas_per_pcs = round_up(luas_per_pcs, config.area_step);
+    let luas_tagih = luas_per_pcs * job.jumlah_pcs;
+
+    let jumlah = luas_tagih / unit.faktor_konversi;
+    let mut subtotal = jumlah * unit.harga_satuan;
+    let minimum_harga_diterapkan = subtotal < config.minimum_charge;
+    if minimum_harga_diterapkan {
+        subtotal = config.minimum_charge;
+    }
+    subtotal = round_up(subtotal, config.price_rounding);
+
+    let luas_cetak = job.panjang * job.lebar * job.jumlah_pcs;
+    let lebar_roll = unit.spesifikasi.and_then(parse_roll_width);
+    let panjang_roll = lebar_roll.map(|width| roll_length(job.panjang, job.lebar, job.jumlah_pcs, width));
+    let konsumsi_m2 = match (lebar_roll, panjang_roll) {
+        (Some(width), Some(length)) => width * length,
+        _ => luas_cetak,
+    };
+    let stok_keluar = match panjang_roll {
+        Some(length) if unit.satuan_dasar.is_some_and(is_linear_unit) => length,
+        _ => konsumsi_m2,
+    };
+
+    Ok(AreaCalculation {
+        panjang_tagih,
+        lebar_tagih,
+        luas_cetak: round4(luas_cetak),
+        luas_tagih: round4(luas_tagih),
+        minimum_luas_diterapkan,
+        minimum_harga_diterapkan,
+        jumlah: round4(jumlah),
+        subtotal: round4(subtotal),
+        lebar_roll,
+        panjang_roll: panjang_roll.map(round4),
+        konsumsi_m2: round4(konsumsi_m2),
+        sisa_m2: round4(konsumsi_m2 - luas_cetak),
+        stok_keluar: round4(stok_keluar),
+    })
+}
+
+/// Round the smaller dimension up to the next roll size, as the POS page
+/// does. A dimension beyond the largest size is kept as is (the page used
+/// to shrink it to the largest size, billing less than was printed).
+fn round_to_roll_size(panjang: f64, lebar: f64, roll_sizes: &[f64]) -> (f64, f64) {
+    let smaller = panjang.min(lebar);
+    let rounded = roll_sizes
+        .iter()
+        .copied()
+        .filter(|size| *size >= smaller - EPSILON)
+        .min_by(f64::total_cmp)
+        .unwrap_or(smaller);
+
+    if panjang < lebar {
+        (rounded, lebar)
+    } else {
+        (panjang, rounded)
+    }
+}
+
+/// Length of roll used by `pcs` pieces of `panjang` x `lebar`, in the
+/// orientation that uses less. Pieces narrower than the roll are placed
+/// side by side; wider ones are printed in strips.
+fn roll_length(panjang: f64, lebar: f64, pcs: f64, width: f64) -> f64 {
+    let layout = |across: f64, along: f64| {
+        if across <= width + EPSILON {
+            let per_row = ((width + EPSILON) / across).floor();
+            (pcs / per_row).ceil() * along
+        } else {
+            let strips = (across / width - EPSILON).ceil();
+            pcs * strips * along
+        }
+    };
+
+    layout(panjang, lebar).min(layout(lebar, panjang))
+}
+
+/// Roll width in meters from a specification such as "280 gsm | Lebar
+/// 1.37m" or "Lebar roll 320 cm"
+fn parse_roll_width(spesifikasi: &str) -> Option<f64> {
+    let lower = spesifikasi.to_lowercase();
+    let start = lower.find("lebar")? + "lebar".len();
+    let rest = lower[start..].trim_start();
+    let rest = rest.strip_prefix("roll").unwrap_or(rest);
+    let rest = rest.trim_start_matches(|c: char| c == ':' || c.is_whitespace());
+
+    let number: String = rest
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+        .collect();
+    let value: f64 = number.replace(',', ".").parse().ok()?;
+    let unit = rest[number.len()..].trim_start();
+
+    let meters = if unit.starts_with("mm") {
+        value / 1000.0
+    } else if unit.starts_with("cm") {
+        value / 100.0
+    } else {
+        value
+    };
+    (meters > 0.0 && meters <= MAX_DIMENSION).then_some(meters)
+}
+
+/// Whether stock is counted in running meters of roll rather than m²
+fn is_linear_unit(satuan: &str) -> bool {
+    let satuan = satuan.trim().to_lowercase();
+    if satuan.contains('2') || satuan.contains('²') || satuan.contains("persegi") {
+        return false;
+    }
+    matches!(satuan.as_str(), "m" | "meter" | "mtr" | "meter lari" | "m lari")
+}
+
+/// Round up to a multiple of `step`; 0 leaves the value as is
+fn round_up(value: f64, step: f64) -> f64 {
+    if step <= 0.0 {
+        return value;
+    }
+    (value / step - EPSILON).ceil() * step
+}
+
+fn round4(value: f64) -> f64 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AreaPricingConfig {
+        AreaPricingConfig {
+            roll_sizes: DEFAULT_ROLL_SIZES.to_vec(),
+            minimum_area: 0.0,
+            minimum_charge: 0.0,
+            area_step: DEFAULT_AREA_STEP,
+            price_rounding: 0.0,
+        }
+    }
+
+    fn job(panjang: f64, lebar: f64, jumlah_pcs: f64) -> AreaJob {
+        AreaJob {
+            panjang,
+            lebar,
+            jumlah_pcs,
+            pembulatan: false,
+        }
+    }
+
+    fn unit<'a>(harga_satuan: f64, satuan_dasar: Option<&'a str>, spesifikasi: Option<&'a str>) -> UnitPrice<'a> {
+        UnitPrice {
+            faktor_konversi: 1.0,
+            harga_satuan,
+            satuan_dasar,
+            spesifikasi,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn small_pieces_are_billed_at_the_minimum_area() {
+        let config = AreaPricingConfig {
+            minimum_area: 1.0,
+            ..config()
+        };
+        let area = calculate(&job(0.5, 0.5, 2.0), &unit(20_000.0, None, None), &config).unwrap();
+
+        assert!(area.minimum_luas_diterapkan);
+        assert_close(area.luas_cetak, 0.5);
+        assert_close(area.luas_tagih, 2.0);
+        assert_close(area.subtotal, 40_000.0);
+        assert!(!area.minimum_harga_diterapkan);
+    }
+
+    #[test]
+    fn cheap_lines_are_billed_at_the_minimum_charge() {
+        let config = AreaPricingConfig {
+            minimum_charge: 50_000.0,
+            ..config()
+        };
+        let area = calculate(&job(1.0, 1.0, 1.0), &unit(20_000.0, None, None), &config).unwrap();
+        assert!(area.minimum_harga_diterapkan);
+        assert_close(area.subtotal, 50_000.0);
+
+        let area = calculate(&job(2.0, 2.0, 1.0), &unit(20_000.0, None, None), &config).unwrap();
+        assert!(!area.minimum_harga_diterapkan);
+        assert_close(area.subtotal, 80_000.0);
+    }
+
+    #[test]
+    fn area_and_price_are_rounded_up_to_their_steps() {
+        let config = AreaPricingConfig {
+            area_step: 0.5,
+            price_rounding: 1_000.0,
+            ..config()
+        };
+
+        let area = calculate(&job(1.1, 1.0, 1.0), &unit(12_345.0, None, None), &config).unwrap();
+        assert_close(area.luas_tagih, 1.5);
+        assert_close(area.subtotal, 19_000.0);
+
+        // Exact multiples stay put despite float noise
+        let area = calculate(&job(0.5, 3.0, 1.0), &unit(12_000.0, None, None), &config).unwrap();
+        assert_close(area.luas_tagih, 1.5);
+        assert_close(area.subtotal, 18_000.0);
+    }
+
+    #[test]
+    fn rounding_raises_the_smaller_dimension_to_a_roll_size() {
+        let job = AreaJob {
+            pembulatan: true,
+            ..job(2.0, 0.8, 1.0)
+        };
+        let area = calculate(&job, &unit(10_000.0, None, None), &config()).unwrap();
+        assert_close(area.panjang_tagih, 2.0);
+        assert_close(area.lebar_tagih, 1.0);
+        assert_close(area.luas_tagih, 2.0);
+        assert_close(area.luas_cetak, 1.6);
+
+        // Beyond the largest roll size the dimension is kept
+        assert_eq!(round_to_roll_size(3.4, 5.0, &DEFAULT_ROLL_SIZES), (3.4, 5.0));
+    }
+
+    #[test]
+    fn pieces_narrower_than_the_roll_are_nested_across_it() {
+        // 3 across in the 0.3 m orientation: 2 rows of 0.4 m
+        assert_close(roll_length(0.3, 0.4, 6.0, 1.0), 0.8);
+        assert_close(roll_length(0.4, 0.3, 6.0, 1.0), 0.8);
+
+        let area = calculate(&job(0.3, 0.4, 6.0), &unit(10_000.0, Some("m2"), Some("Lebar 1 m")), &config()).unwrap();
+        assert_eq!(area.lebar_roll, Some(1.0));
+        assert_close(area.luas_cetak, 0.72);
+        assert_close(area.konsumsi_m2, 0.8);
+        assert_close(area.sisa_m2, 0.08);
+    }
+
+    #[test]
+    fn pieces_wider_than_the_roll_are_printed_in_strips() {
+        // 2.5 m across needs 3 strips of 1.5 m; 1.5 m across needs 2 of 2.5 m
+        assert_close(roll_length(2.5, 1.5, 1.0, 1.0), 4.5);
+        assert_close(roll_length(2.5, 1.5, 2.0, 1.0), 9.0);
+    }
+
+    #[test]
+    fn unusable_conversion_factors_are_rejected() {
+        for faktor_konversi in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let unit = UnitPrice {
+                faktor_konversi,
+                ..unit(10_000.0, None, None)
+            };
+            assert!(calculate(&job(1.0, 1.0, 1.0), &unit, &config()).is_err(), "{}", faktor_konversi);
+        }
+    }
+
+    #[test]
+    fn roll_width_is_read_from_the_specification() {
+        assert_eq!(parse_roll_width("280 gsm | Lebar 1.37m"), Some(1.37));
+        assert_eq!(parse_roll_width("Lebar roll 320 cm"), Some(3.2));
+        assert_eq!(parse_roll_width("Flexi, lebar: 1,6 m"), Some(1.6));
+        assert_eq!(parse_roll_width("LEBAR 1520mm"), Some(1.52));
+        assert_eq!(parse_roll_width("Flexi 280 gsm"), None);
+        assert_eq!(parse_roll_width("Lebar 0 m"), None);
+    }
+
+    #[test]
+    fn stock_is_deducted_in_meters_only_for_linear_units() {
+        let spesifikasi = Some("Lebar 1.37m");
+        let stok_keluar = |satuan_dasar| {
+            calculate(&job(1.2, 0.5, 2.0), &unit(10_000.0, satuan_dasar, spesifikasi), &config())
+                .unwrap()
+                .stok_keluar
+        };
+
+        // Two 1.2 m pieces side by side on a 1.37 m roll: 1 m of roll
+        assert_close(stok_keluar(Some("Meter")), 1.0);
+        assert_close(stok_keluar(Some("m²")), 1.37);
+        assert_close(stok_keluar(Some("meter persegi")), 1.37);
+        assert_close(stok_keluar(None), 1.37);
+
+        // Without a roll width the printed area is deducted
+        let area = calculate(&job(1.2, 0.5, 2.0), &unit(10_000.0, Some("meter"), None), &config()).unwrap();
+        assert_close(area.stok_keluar, 1.2);
+    }
+}
@@ -0,0 +1,333 @@
+use crate::capture;
+use crate::config;
+use crate::schema::{self, quote_ident, TableDef};
+use crate::sync::SupabaseConfig;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::sync::Mutex;
+
+/// Rows requested from PostgREST per page
+const PAGE_SIZE: usize = 500;
+
+/// Position of the last row applied for a table
+#[derive(Debug, Clone, Default)]
+pub struct PullCursor {
+    pub value: Option<String>,
+    pub id: Option<String>,
+}
+
+/// Pull outcome for one table
+#[derive(Debug, Default, Serialize)]
+pub struct TablePullReport {
+    pub table: String,
+    /// Rows inserted or updated locally
+    pub pulled: usize,
+    /// Remote rows ignored because the local copy has unsynced changes
+    pub skipped: usize,
+    pub error: Option<String>,
+}
+
+/// Pull outcome for all tables
+#[derive(Debug, Serialize)]
+pub struct PullReport {
+    pub tables: Vec<TablePullReport>,
+    pub pulled: usize,
+    pub skipped: usize,
+    pub failed_tables: usize,
+}
+
+/// Column used as the high-water mark: `diperbarui_pada` where the table
+/// has it, otherwise `dibuat_pada` (line items are never edited in place)
+pub fn cursor_column(table: &TableDef) -> &'static str {
+    if table.column("diperbarui_pada").is_some() {
+        "diperbarui_pada"
+    } else {
+        "dibuat_pada"
+    }
+}
+
+/// Pull every synced table from Supabase, parents first.
+/// The database lock is only held while reading cursors and applying pages,
+/// never across a network request.
+pub async fn pull_all(db: &Mutex<Option<Connection>>, config: &SupabaseConfig) -> PullReport {
+    let client = config.client();
+    let mut tables = Vec::new();
+
+    let sync_tables = match db.lock() {
+        Ok(guard) => guard.as_ref().and_then(|conn| config::sync_tables(conn).ok()),
+        Err(_) => None,
+    };
+    let sync_tables = sync_tables.unwrap_or_else(|| {
+        schema::dependency_order().into_iter().filter(|t| t.is_synced()).collect()
+    });
+
+    for table in sync_tables {
+        let mut report = TablePullReport {
+            table: table.name.to_string(),
+            ..Default::default()
+        };
+        if let Err(e) = pull_table(db, &client, config, table, &mut report).await {
+            report.error = Some(e);
+        }
+        tables.push(report);
+    }
+
+    PullReport {
+        pulled: tables.iter().map(|t| t.pulled).sum(),
+        skipped: tables.iter().map(|t| t.skipped).sum(),
+        failed_tables: tables.iter().filter(|t| t.error.is_some()).count(),
+        tables,
+    }
+}
+
+async fn pull_table(
+    db: &Mutex<Option<Connection>>,
+    client: &reqwest::Client,
+    config: &SupabaseConfig,
+    table: &TableDef,
+    report: &mut TablePullReport,
+) -> Result<(), String> {
+    let column = cursor_column(table);
+    let mut cursor = {
+        let guard = db.lock().map_err(|e| e.to_string())?;
+        let conn = guard.as_ref().ok_or("Database not initialized")?;
+        load_cursor(conn, table.name)?
+    };
+
+    loop {
+        let rows = fetch_page(client, config, table.name, column, &cursor, PAGE_SIZE).await?;
+        let page_len = rows.len();
+        if page_len == 0 {
+            break;
+        }
+
+        let (pulled, skipped) = {
+            let mut guard = db.lock().map_err(|e| e.to_string())?;
+            let conn = guard.as_mut().ok_or("Database not initialized")?;
+            apply_page(conn, table, &rows, &mut cursor)?
+        };
+        report.pulled += pulled;
+        report.skipped += skipped;
+
+        if page_len < PAGE_SIZE {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Read the stored high-water mark for a table
+pub fn load_cursor(conn: &Connection, table: &str) -> Result<PullCursor, String> {
+    conn.query_row(
+        "SELECT cursor_value, cursor_id FROM sync_pull_state WHERE table_name = ?1",
+        params![table],
+        |row| {
+            Ok(PullCursor {
+                value: row.get(0)?,
+                id: row.get(1)?,
+            })
+        },
+    )
+    .optional()
+    .map(|c| c.unwrap_or_default())
+    .map_err(|e| e.to_string())
+}
+
+/// Fetch the next page of rows changed after `cursor`, ordered by (cursor column, id)
+pub async fn fetch_page(
+    client: &reqwest::Client,
+    config: &SupabaseConfig,
+    table: &str,
+    column: &str,
+    cursor: &PullCursor,
+    limit: usize,
+) -> Result<Vec<Map<String, Value>>, String> {
+    let url = config.table_url(table);
+    let mut query: Vec<(&str, String)> = vec![
+        ("select", "*".to_string()),
+        ("order", format!("{}.asc.nullsfirst,id.asc", column)),
+        ("limit", limit.to_string()),
+    ];
+
+    // Keyset pagination: strictly after the last (timestamp, id) seen
+    if let (Some(value), Some(id)) = (&cursor.value, &cursor.id) {
+        query.push((
+            "or",
+            format!(
+                "({col}.gt.\"{v}\",and({col}.eq.\"{v}\",id.gt.\"{id}\"))",
+                col = column,
+                v = value,
+                id = id
+            ),
+        ));
+    } else if let Some(id) = &cursor.id {
+        query.push((
+            "or",
+            format!("({col}.not.is.null,and({col}.is.null,id.gt.\"{id}\"))", col = column, id = id),
+        ));
+    }
+
+    let response = config
+        .authorize(client.get(&url))
+        .query(&query)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Pull failed: {}", error_text));
+    }
+
+    let rows: Vec<Value> = response.json().await.map_err(|e| e.to_string())?;
+    rows.into_iter()
+        .map(|row| match row {
+            Value::Object(map) => Ok(map),
+            other => Err(format!("Unexpected row in {}: {}", table, other)),
+        })
+        .collect()
+}
+
+/// Number of rows in a cloud table (`Prefer: count=exact`)
+pub async fn count_remote(
+    client: &reqwest::Client,
+    config: &SupabaseConfig,
+    table: &str,
+) -> Result<i64, String> {
+    let response = config
+        .authorize(client.head(config.table_url(table)))
+        .query(&[("select", "id")])
+        .header("Prefer", "count=exact")
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !response.status().is_success() {
+        return Err(format!("Counting {} failed: HTTP {}", table, response.status()));
+    }
+
+    // "0-24/3573", or "*/0" for an empty table
+    response
+        .headers()
+        .get("Content-Range")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.rsplit('/').next())
+        .and_then(|total| total.parse().ok())
+        .ok_or_else(|| format!("No row count for {}", table))
+}
+
+/// Upsert one page of remote rows without queueing them for push, and advance
+/// the cursor in the same transaction. Returns (applied, skipped).
+pub fn apply_page(
+    conn: &mut Connection,
+    table: &TableDef,
+    rows: &[Map<String, Value>],
+    cursor: &mut PullCursor,
+) -> Result<(usize, usize), String> {
+    let column = cursor_column(table);
+    let now = chrono::Utc::now().to_rfc3339();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    capture::pause(&tx).map_err(|e| e.to_string())?;
+
+    let mut applied = 0;
+    let mut skipped = 0;
+    let mut next = cursor.clone();
+
+    for row in rows {
+        let id = row
+            .get("id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| format!("Row without id in {}", table.name))?;
+
+        next = PullCursor {
+            value: row.get(column).and_then(|v| v.as_str()).map(str::to_string),
+            id: Some(id.to_string()),
+        };
+
+        if has_local_changes(&tx, table, id)? {
+            skipped += 1;
+            continue;
+        }
+
+        upsert_row(&tx, table, row, &now)?;
+        applied += 1;
+    }
+
+    tx.execute(
+        "INSERT INTO sync_pull_state (table_name, cursor_column, cursor_value, cursor_id, last_pulled_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(table_name) DO UPDATE SET
+            cursor_column = excluded.cursor_column,
+            cursor_value = excluded.cursor_value,
+            cursor_id = excluded.cursor_id,
+            last_pulled_at = excluded.last_pulled_at",
+        params![table.name, column, next.value, next.id, now],
+    )
+    .map_err(|e| e.to_string())?;
+
+    capture::resume(&tx).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    *cursor = next;
+    Ok((applied, skipped))
+}
+
+/// Local rows still waiting to be pushed (or in conflict) must not be overwritten
+fn has_local_changes(conn: &Connection, table: &TableDef, id: &str) -> Result<bool, String> {
+    let status: Option<Option<String>> = conn
+        .query_row(
+            &format!("SELECT sync_status FROM {} WHERE id = ?1", quote_ident(table.name)),
+            params![id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    Ok(matches!(status, Some(Some(s)) if s != "synced"))
+}
+
+/// Insert or update a remote row, keeping only columns the registry knows
+pub fn upsert_row(
+    conn: &Connection,
+    table: &TableDef,
+    row: &Map<String, Value>,
+    now: &str,
+) -> Result<(), String> {
+    let mut columns = Vec::new();
+    let mut values = Vec::new();
+
+    for (key, value) in row {
+        if table.column(key).is_none() || key == "sync_status" || key == "last_synced_at" {
+            continue;
+        }
+        columns.push(quote_ident(key));
+        values.push(crate::json_to_rusqlite_value(value));
+    }
+
+    columns.push(quote_ident("sync_status"));
+    values.push(rusqlite::types::Value::Text("synced".to_string()));
+    columns.push(quote_ident("last_synced_at"));
+    values.push(rusqlite::types::Value::Text(now.to_string()));
+
+    let placeholders: Vec<&str> = columns.iter().map(|_| "?").collect();
+    let updates: Vec<String> = columns
+        .iter()
+        .filter(|c| c.as_str() != "\"id\"")
+        .map(|c| format!("{} = excluded.{}", c, c))
+        .collect();
+
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT(\"id\") DO UPDATE SET {}",
+        quote_ident(table.name),
+        columns.join(", "),
+        placeholders.join(", "),
+        updates.join(", ")
+    );
+
+    conn.execute(&sql, rusqlite::params_from_iter(values.iter()))
+        .map_err(|e| format!("Failed to apply {} {}: {}", table.name, row.get("id").unwrap_or(&Value::Null), e))?;
+
+    Ok(())
+}
@@ -0,0 +1,446 @@
+use crate::pos::{format_rupiah, insert_expense_entry, parse_tanggal, today_jakarta};
+use crate::stock;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Payload of `create_purchase`
+#[derive(Debug, Deserialize)]
+pub struct CreatePurchaseRequest {
+    /// Defaults to `nomor_faktur`, as the purchase form does
+    pub nomor_pembelian: Option<String>,
+    /// Supplier invoice number
+    pub nomor_faktur: String,
+    pub vendor_id: Option<String>,
+    /// Purchase date (YYYY-MM-DD), defaults to today in Asia/Jakarta
+    pub tanggal: Option<String>,
+    pub metode_pembayaran: Option<String>,
+    /// LUNAS, HUTANG or SEBAGIAN
+    pub status_pembayaran: String,
+    /// Paid now; required for SEBAGIAN, ignored otherwise
+    pub jumlah_dibayar: Option<f64>,
+    /// Due date of the payable (YYYY-MM-DD)
+    pub jatuh_tempo: Option<String>,
+    pub catatan: Option<String>,
+    pub dibuat_oleh: Option<String>,
+    /// Store each line's price as the latest `harga_beli` of every unit of the
+    /// barang, converted by `faktor_konversi`
+    #[serde(default)]
+    pub perbarui_harga_beli: bool,
+    pub items: Vec<PurchaseLine>,
+}
+
+/// One purchased line; `harga_satuan` is the price per `harga_satuan_id` unit
+#[derive(Debug, Deserialize)]
+pub struct PurchaseLine {
+    pub barang_id: String,
+    pub harga_satuan_id: String,
+    pub jumlah: f64,
+    pub harga_satuan: f64,
+}
+
+/// Summary returned after a purchase is recorded
+#[derive(Debug, Serialize)]
+pub struct CreatePurchaseResult {
+    pub id: String,
+    pub nomor_pembelian: String,
+    pub total_jumlah: f64,
+    pub jumlah_dibayar: f64,
+    pub status_pembayaran: String,
+    /// Set when part of the purchase is owed to the vendor
+    pub hutang_id: Option<String>,
+    pub sisa_hutang: f64,
+}
+
+/// Unit of a purchase line, from `harga_barang_satuan`
+struct LineUnit {
+    nama_satuan: String,
+    faktor_konversi: f64,
+    lacak_inventori: bool,
+}
+
+/// Record a purchase in one transaction: pembelian, item_pembelian, stock
+/// movements, latest purchase prices, the payable for what is still owed and
+/// a SUPPLY cash book entry for what was paid now.
+///
+/// Units and conversion factors come from `harga_barang_satuan`. Cash book
+/// balances are recalculated by the frontend afterwards, as for sales.
+pub fn create_purchase(conn: &mut Connection, req: CreatePurchaseRequest) -> Result<CreatePurchaseResult, String> {
+    let nomor_faktur = req.nomor_faktur.trim();
+    if nomor_faktur.is_empty() {
+        return Err("Nomor faktur harus diisi".to_string());
+    }
+    if req.items.is_empty() {
+        return Err("Minimal harus ada 1 item pembelian".to_string());
+    }
+    if let Some(line) = req.items.iter().find(|l| !l.jumlah.is_finite() || l.jumlah <= 0.0) {
+        return Err(format!("Jumlah untuk barang {} harus lebih dari 0", line.barang_id));
+    }
+    if let Some(line) = req.items.iter().find(|l| !l.harga_satuan.is_finite() || l.harga_satuan < 0.0) {
+        return Err(format!("Harga untuk barang {} tidak boleh negatif", line.barang_id));
+    }
+
+    let nomor_pembelian = req
+        .nomor_pembelian
+        .as_deref()
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .unwrap_or(nomor_faktur)
+        .to_string();
+    let total_jumlah: f64 = req.items.iter().map(|l| l.jumlah * l.harga_satuan).sum();
+
+    let status = req.status_pembayaran.as_str();
+    let jumlah_dibayar = match status {
+        "LUNAS" => total_jumlah,
+        "HUTANG" => 0.0,
+        "SEBAGIAN" => match req.jumlah_dibayar {
+            Some(dibayar) if dibayar > 0.0 && dibayar < total_jumlah => dibayar,
+            _ => {
+                return Err(format!(
+                    "Pembayaran sebagian harus lebih dari 0 dan kurang dari total Rp {}",
+                    format_rupiah(total_jumlah)
+                ));
+            }
+        },
+        other => return Err(format!("Status pembayaran tidak valid: {}", other)),
+    };
+    let sisa_hutang = total_jumlah - jumlah_dibayar;
+
+    let tanggal = req.tanggal.clone().unwrap_or_else(today_jakarta);
+    let tanggal_beli = parse_tanggal(&tanggal)?;
+    if let Some(jatuh_tempo) = &req.jatuh_tempo {
+        if parse_tanggal(jatuh_tempo)? < tanggal_beli {
+            return Err("Jatuh tempo tidak boleh sebelum tanggal pembelian".to_string());
+        }
+    }
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().to_rfc3339();
+    let catatan = req.catatan.as_deref().map(str::trim).filter(|c| !c.is_empty());
+
+    let exists: bool = tx
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM pembelian WHERE nomor_pembelian = ?1)",
+            params![nomor_pembelian],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if exists {
+        return Err(format!("Nomor pembelian {} sudah digunakan", nomor_pembelian));
+    }
+
+    let vendor_nama: Option<String> = match &req.vendor_id {
+        Some(id) => Some(
+            tx.query_row(
+                "SELECT nama_perusahaan FROM vendor WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Vendor tidak ditemukan: {}", id))?,
+        ),
+        None => None,
+    };
+
+    // Resolve every unit before writing anything
+    let mut units = Vec::with_capacity(req.items.len());
+    for line in &req.items {
+        let unit = tx
+            .query_row(
+                "SELECT h.nama_satuan, h.faktor_konversi, COALESCE(b.lacak_inventori_status, 1)
+                 FROM harga_barang_satuan h
+                 JOIN barang b ON b.id = h.barang_id
+                 WHERE h.id = ?1 AND h.barang_id = ?2",
+                params![line.harga_satuan_id, line.barang_id],
+                |row| {
+                    Ok(LineUnit {
+                        nama_satuan: row.get(0)?,
+                        faktor_konversi: row.get(1)?,
+                        lacak_inventori: row.get::<_, i64>(2)? != 0,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| {
+                format!(
+                    "Satuan {} tidak ditemukan untuk barang {}",
+                    line.harga_satuan_id, line.barang_id
+                )
+            })?;
+        if !unit.faktor_konversi.is_finite() || unit.faktor_konversi <= 0.0 {
+            return Err(format!("Faktor konversi satuan {} tidak valid", unit.nama_satuan));
+        }
+        units.push(unit);
+    }
+
+    // pembelian
+    let purchase_id = Uuid::new_v4().to_string();
+    tx.execute(
+        "INSERT INTO pembelian (id, nomor_pembelian, nomor_faktur, vendor_id, tanggal, total_jumlah,
+            jumlah_dibayar, metode_pembayaran, status_pembayaran, catatan, dibuat_oleh, dibuat_pada, diperbarui_pada)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?12)",
+        params![
+            purchase_id,
+            nomor_pembelian,
+            nomor_faktur,
+            req.vendor_id,
+            tanggal,
+            total_jumlah,
+            jumlah_dibayar,
+            req.metode_pembayaran,
+            status,
+            catatan,
+            req.dibuat_oleh,
+            now
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    // item_pembelian + stock + harga_beli
+    for (line, unit) in req.items.iter().zip(&units) {
+        let item_id = Uuid::new_v4().to_string();
+        tx.execute(
+            "INSERT INTO item_pembelian (id, pembelian_id, barang_id, harga_satuan_id, jumlah,
+                nama_satuan, faktor_konversi, harga_satuan, subtotal, dibuat_pada)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                item_id,
+                purchase_id,
+                line.barang_id,
+                line.harga_satuan_id,
+                line.jumlah,
+                unit.nama_satuan,
+                unit.faktor_konversi,
+                line.harga_satuan,
+                line.jumlah * line.harga_satuan,
+                now
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+
+        if unit.lacak_inventori {
+            stock::apply(
+                &tx,
+                &stock::Mutasi {
+                    barang_id: &line.barang_id,
+                    jenis: stock::JenisMutasi::Pembelian,
+                    jumlah: line.jumlah * unit.faktor_konversi,
+                    referensi: Some(("item_pembelian", &item_id)),
+                    catatan: Some(&nomor_pembelian),
+                    dibuat_oleh: req.dibuat_oleh.as_deref(),
+                },
+                &now,
+            )?;
+        }
+
+        // Every unit of the barang, scaled from the price per satuan_dasar
+        if req.perbarui_harga_beli {
+            tx.execute(
+                "UPDATE harga_barang_satuan SET harga_beli = ?1 * faktor_konversi, diperbarui_pada = ?2
+                 WHERE barang_id = ?3",
+                params![line.harga_satuan / unit.faktor_konversi, now, line.barang_id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+
+    // hutang_pembelian for the part still owed; later payments are
+    // pelunasan_hutang rows, so nothing counts as paid yet
+    let hutang_id = if sisa_hutang > 0.0 {
+        let id = Uuid::new_v4().to_string();
+        tx.execute(
+            "INSERT INTO hutang_pembelian (id, id_pembelian, jumlah_hutang, jumlah_terbayar, sisa_hutang,
+                jatuh_tempo, status, catatan, dibuat_pada, diperbarui_pada)
+             VALUES (?1, ?2, ?3, 0, ?3, ?4, 'AKTIF', ?5, ?6, ?6)",
+            params![id, purchase_id, sisa_hutang, req.jatuh_tempo, catatan, now],
+        )
+        .map_err(|e| e.to_string())?;
+        Some(id)
+    } else {
+        None
+    };
+
+    // keuangan (only for money actually paid)
+    if jumlah_dibayar > 0.0 {
+        let mut keperluan = format!("Pembelian {} ({})", nomor_pembelian, nomor_faktur);
+        if let Some(nama) = &vendor_nama {
+            keperluan.push_str(&format!(" - {}", nama));
+        }
+        if sisa_hutang > 0.0 {
+            keperluan.push_str(&format!(
+                " (Rp {} dari Rp {})",
+                format_rupiah(jumlah_dibayar),
+                format_rupiah(total_jumlah)
+            ));
+        }
+        keperluan.push_str(&format!(" [REF:{}]", purchase_id));
+        insert_expense_entry(
+            &tx,
+            &tanggal,
+            "SUPPLY",
+            jumlah_dibayar,
+            &keperluan,
+            catatan,
+            req.dibuat_oleh.as_deref(),
+            &now,
+        )?;
+    }
+
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(CreatePurchaseResult {
+        id: purchase_id,
+        nomor_pembelian,
+        total_jumlah,
+        jumlah_dibayar,
+        status_pembayaran: status.to_string(),
+        hutang_id,
+        sisa_hutang,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_postgrest::test_db;
+
+    /// Kertas A3 sold by the lembar, the rim (500) and the box (5 rim)
+    fn seed(conn: &Connection) {
+        conn.execute_batch(
+            "INSERT INTO vendor (id, nama_perusahaan) VALUES ('v-kertas', 'CV Kertas Jaya');
+             INSERT INTO barang (id, nama, satuan_dasar, jumlah_stok) VALUES ('b-a3', 'Kertas A3', 'lembar', 0);
+             INSERT INTO harga_barang_satuan (id, barang_id, nama_satuan, faktor_konversi, harga_beli)
+             VALUES ('h-lembar', 'b-a3', 'lembar', 1, 90),
+                    ('h-rim', 'b-a3', 'rim', 500, 45000),
+                    ('h-box', 'b-a3', 'box', 2500, 225000);",
+        )
+        .unwrap();
+    }
+
+    /// 2 rim at 50.000: 100.000
+    fn purchase(status: &str, jumlah_dibayar: Option<f64>) -> CreatePurchaseRequest {
+        CreatePurchaseRequest {
+            nomor_pembelian: None,
+            nomor_faktur: "FK-001".to_string(),
+            vendor_id: Some("v-kertas".to_string()),
+            tanggal: Some("2026-03-10".to_string()),
+            metode_pembayaran: Some("TRANSFER".to_string()),
+            status_pembayaran: status.to_string(),
+            jumlah_dibayar,
+            jatuh_tempo: None,
+            catatan: None,
+            dibuat_oleh: None,
+            perbarui_harga_beli: true,
+            items: vec![PurchaseLine {
+                barang_id: "b-a3".to_string(),
+                harga_satuan_id: "h-rim".to_string(),
+                jumlah: 2.0,
+                harga_satuan: 50_000.0,
+            }],
+        }
+    }
+
+    /// kredit of the purchase's cash book rows
+    fn keuangan(conn: &Connection, purchase_id: &str) -> Vec<f64> {
+        let mut stmt = conn
+            .prepare("SELECT kredit FROM keuangan WHERE kategori_transaksi = 'SUPPLY' AND keperluan LIKE '%' || ?1 || '%'")
+            .unwrap();
+        stmt.query_map(params![purchase_id], |row| row.get(0))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .unwrap()
+    }
+
+    #[test]
+    fn purchase_in_a_larger_unit_updates_stock_and_every_unit_price() {
+        let db = test_db();
+        let mut guard = db.lock().unwrap();
+        let conn = guard.as_mut().unwrap();
+        seed(conn);
+
+        let result = create_purchase(conn, purchase("LUNAS", None)).unwrap();
+        assert_eq!((result.total_jumlah, result.jumlah_dibayar), (100_000.0, 100_000.0));
+        assert_eq!(result.hutang_id, None);
+
+        let stok: f64 = conn
+            .query_row("SELECT jumlah_stok FROM barang WHERE id = 'b-a3'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stok, 1_000.0);
+
+        let mut stmt = conn
+            .prepare("SELECT id, harga_beli FROM harga_barang_satuan WHERE barang_id = 'b-a3' ORDER BY faktor_konversi")
+            .unwrap();
+        let prices: Vec<(String, f64)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .unwrap();
+        assert_eq!(
+            prices,
+            vec![
+                ("h-lembar".to_string(), 100.0),
+                ("h-rim".to_string(), 50_000.0),
+                ("h-box".to_string(), 250_000.0)
+            ]
+        );
+        assert_eq!(keuangan(conn, &result.id), vec![100_000.0]);
+    }
+
+    #[test]
+    fn partial_payment_leaves_the_rest_owed_to_the_vendor() {
+        let db = test_db();
+        let mut guard = db.lock().unwrap();
+        let conn = guard.as_mut().unwrap();
+        seed(conn);
+
+        let request = CreatePurchaseRequest {
+            jatuh_tempo: Some("2026-04-10".to_string()),
+            ..purchase("SEBAGIAN", Some(30_000.0))
+        };
+        let result = create_purchase(conn, request).unwrap();
+        assert_eq!(result.sisa_hutang, 70_000.0);
+
+        let hutang: (f64, f64, f64, String, Option<String>) = conn
+            .query_row(
+                "SELECT jumlah_hutang, jumlah_terbayar, sisa_hutang, status, jatuh_tempo
+                 FROM hutang_pembelian WHERE id = ?1",
+                params![result.hutang_id.as_deref().unwrap()],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            hutang,
+            (70_000.0, 0.0, 70_000.0, "AKTIF".to_string(), Some("2026-04-10".to_string()))
+        );
+        assert_eq!(keuangan(conn, &result.id), vec![30_000.0]);
+    }
+
+    #[test]
+    fn invalid_dates_are_rejected_before_anything_is_written() {
+        let db = test_db();
+        let mut guard = db.lock().unwrap();
+        let conn = guard.as_mut().unwrap();
+        seed(conn);
+
+        let bad_tanggal = CreatePurchaseRequest {
+            tanggal: Some("10/03/2026".to_string()),
+            ..purchase("HUTANG", None)
+        };
+        assert!(create_purchase(conn, bad_tanggal).is_err());
+        let bad_jatuh_tempo = CreatePurchaseRequest {
+            jatuh_tempo: Some("2026-02-30".to_string()),
+            ..purchase("HUTANG", None)
+        };
+        assert!(create_purchase(conn, bad_jatuh_tempo).is_err());
+        let due_before_purchase = CreatePurchaseRequest {
+            jatuh_tempo: Some("2026-03-09".to_string()),
+            ..purchase("HUTANG", None)
+        };
+        assert!(create_purchase(conn, due_before_purchase).is_err());
+
+        let purchases: i64 = conn
+            .query_row("SELECT COUNT(*) FROM pembelian", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(purchases, 0);
+    }
+}
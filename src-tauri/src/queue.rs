@@ -0,0 +1,377 @@
+use crate::conflict::parse_timestamp;
+use crate::scheduler;
+use crate::settings;
+use crate::sync::{self, QueuedOperation, SyncOperation, QUEUED_OPERATION_COLUMNS};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection};
+use serde::{Deserialize, Serialize};
+
+/// Errors shown in the dashboard
+const RECENT_ERRORS: usize = 20;
+
+/// Days synced entries are kept for troubleshooting before they are purged
+pub const RETENTION_DAYS_KEY: &str = "sync.retention_days";
+pub const DEFAULT_RETENTION_DAYS: i64 = 30;
+pub const MAX_RETENTION_DAYS: i64 = 3650;
+
+const LAST_MAINTENANCE_KEY: &str = "sync.last_maintenance_at";
+
+/// Background maintenance runs at most this often
+const MAINTENANCE_INTERVAL_HOURS: i64 = 24;
+
+/// VACUUM only when at least this share of the file is free pages
+const VACUUM_FREE_RATIO: f64 = 0.25;
+
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 500;
+
+/// Queue entries of one table and operation, by state
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueGroup {
+    pub table_name: String,
+    pub operation: String,
+    /// Waiting to be pushed, including entries backing off after a failure
+    pub pending: i64,
+    /// Not yet synced and failed at least once (retrying or dead-lettered)
+    pub failed: i64,
+    pub dead_letter: i64,
+    pub conflict: i64,
+    pub synced: i64,
+}
+
+/// Overview of the sync queue for the admin dashboard
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueStats {
+    pub groups: Vec<QueueGroup>,
+    pub pending: i64,
+    pub failed: i64,
+    pub dead_letter: i64,
+    pub conflict: i64,
+    pub synced: i64,
+    pub oldest_pending_at: Option<String>,
+    pub oldest_pending_age_secs: Option<i64>,
+    pub last_success_at: Option<String>,
+    pub recent_errors: Vec<QueuedOperation>,
+}
+
+/// Filter and page of `list_queue`; every filter is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueQuery {
+    pub status: Option<String>,
+    pub table_name: Option<String>,
+    pub operation: Option<String>,
+    /// Only entries that failed at least once
+    #[serde(default)]
+    pub failed_only: bool,
+    /// Zero-based
+    #[serde(default)]
+    pub page: usize,
+    pub page_size: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuePage {
+    pub entries: Vec<QueuedOperation>,
+    pub total: i64,
+    pub page: usize,
+    pub page_size: usize,
+}
+
+pub fn stats(conn: &Connection) -> Result<QueueStats, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT table_name, operation,
+                    SUM(synced_at IS NULL AND status = 'pending'),
+                    SUM(synced_at IS NULL AND attempts > 0 AND status IN ('pending', 'dead_letter')),
+                    SUM(status = 'dead_letter'),
+                    SUM(status = 'conflict'),
+                    SUM(status = 'synced')
+             FROM sync_queue
+             GROUP BY table_name, operation
+             ORDER BY table_name, operation",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let groups = stmt
+        .query_map([], |row| {
+            Ok(QueueGroup {
+                table_name: row.get(0)?,
+                operation: row.get(1)?,
+                pending: row.get(2)?,
+                failed: row.get(3)?,
+                dead_letter: row.get(4)?,
+                conflict: row.get(5)?,
+                synced: row.get(6)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let oldest_pending_at: Option<String> = conn
+        .query_row(
+            "SELECT MIN(created_at) FROM sync_queue WHERE synced_at IS NULL AND status = 'pending'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let oldest_pending_age_secs = oldest_pending_at
+        .as_deref()
+        .and_then(parse_timestamp)
+        .map(|t| (chrono::Utc::now() - t).num_seconds().max(0));
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM sync_queue
+             WHERE synced_at IS NULL AND last_error IS NOT NULL
+             ORDER BY COALESCE(last_attempt_at, created_at) DESC, rowid DESC
+             LIMIT ?1",
+            QUEUED_OPERATION_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let recent_errors = stmt
+        .query_map([RECENT_ERRORS as i64], QueuedOperation::from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let total = |f: fn(&QueueGroup) -> i64| groups.iter().map(f).sum();
+
+    Ok(QueueStats {
+        pending: total(|g| g.pending),
+        failed: total(|g| g.failed),
+        dead_letter: total(|g| g.dead_letter),
+        conflict: total(|g| g.conflict),
+        synced: total(|g| g.synced),
+        groups,
+        oldest_pending_at,
+        oldest_pending_age_secs,
+        last_success_at: settings::get(conn, scheduler::LAST_SUCCESS_KEY)?,
+        recent_errors,
+    })
+}
+
+/// One page of `sync_queue`, newest first
+pub fn list_queue(conn: &Connection, query: &QueueQuery) -> Result<QueuePage, String> {
+    let page_size = query.page_size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    let mut conditions = Vec::new();
+    let mut values: Vec<SqlValue> = Vec::new();
+    for (column, value) in [
+        ("status", &query.status),
+        ("table_name", &query.table_name),
+        ("operation", &query.operation),
+    ] {
+        if let Some(value) = value {
+            values.push(SqlValue::Text(value.clone()));
+            conditions.push(format!("{} = ?{}", column, values.len()));
+        }
+    }
+    if query.failed_only {
+        conditions.push("attempts > 0".to_string());
+    }
+    let filter = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+
+    let total: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM sync_queue {}", filter),
+            params_from_iter(values.iter()),
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    values.push(SqlValue::Integer(page_size as i64));
+    values.push(SqlValue::Integer((query.page * page_size) as i64));
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM sync_queue {}
+             ORDER BY created_at DESC, rowid DESC
+             LIMIT ?{} OFFSET ?{}",
+            QUEUED_OPERATION_COLUMNS,
+            filter,
+            values.len() - 1,
+            values.len()
+        ))
+        .map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map(params_from_iter(values.iter()), QueuedOperation::from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(QueuePage {
+        entries,
+        total,
+        page: query.page,
+        page_size,
+    })
+}
+
+/// What one maintenance run did
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceReport {
+    /// Pending entries folded into a later entry of the same record
+    pub compacted: usize,
+    /// Pending entries dropped because the record was inserted and deleted again
+    pub dropped: usize,
+    /// Synced, superseded and discarded entries past the retention period
+    pub purged: usize,
+    pub vacuumed: bool,
+    pub ran_at: String,
+}
+
+/// Compact the pending queue, purge old entries and optimize the database
+pub fn run_maintenance(conn: &Connection) -> Result<MaintenanceReport, String> {
+    let (compacted, dropped) = compact(conn)?;
+    let retention_days = settings::get_or(conn, RETENTION_DAYS_KEY, DEFAULT_RETENTION_DAYS)?
+        .clamp(1, MAX_RETENTION_DAYS);
+    let purged = purge_synced(conn, retention_days)?;
+    let vacuumed = optimize(conn)?;
+
+    let ran_at = chrono::Utc::now().to_rfc3339();
+    settings::set(conn, LAST_MAINTENANCE_KEY, &ran_at)?;
+
+    Ok(MaintenanceReport {
+        compacted,
+        dropped,
+        purged,
+        vacuumed,
+        ran_at,
+    })
+}
+
+/// Whether the background task should run `run_maintenance` again
+pub fn maintenance_due(conn: &Connection) -> Result<bool, String> {
+    let last = settings::get(conn, LAST_MAINTENANCE_KEY)?
+        .as_deref()
+        .and_then(parse_timestamp);
+    Ok(last.is_none_or(|t| {
+        chrono::Utc::now() - t >= chrono::Duration::hours(MAINTENANCE_INTERVAL_HOURS)
+    }))
+}
+
+/// Fold the pending entries of each record into one, the same way the push
+/// does: insert + updates → insert, updates → update, insert … delete → nothing.
+/// Only entries that were never attempted are touched, and only for records
+/// with no entry that is retrying or dead-lettered. Returns (compacted, dropped).
+pub fn compact(conn: &Connection) -> Result<(usize, usize), String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+
+    let operations = {
+        let mut stmt = tx
+            .prepare(
+                "SELECT q.id, q.table_name, q.operation, q.record_id, q.data, q.created_at, q.base_version
+                 FROM sync_queue q
+                 WHERE q.synced_at IS NULL AND q.status = 'pending' AND q.attempts = 0
+                   AND q.record_id IS NOT NULL
+                   AND NOT EXISTS (
+                       SELECT 1 FROM sync_queue e
+                       WHERE e.table_name = q.table_name AND e.record_id = q.record_id
+                         AND e.synced_at IS NULL
+                         AND (e.status = 'dead_letter' OR (e.status = 'pending' AND e.attempts > 0))
+                   )
+                 ORDER BY q.created_at ASC, q.rowid ASC",
+            )
+            .map_err(|e| e.to_string())?;
+        let operations = stmt.query_map([], |row| {
+            Ok(SyncOperation {
+                id: row.get(0)?,
+                table_name: row.get(1)?,
+                operation: row.get(2)?,
+                record_id: row.get(3)?,
+                data: row.get(4)?,
+                created_at: row.get(5)?,
+                base_version: row.get(6)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+        operations
+    };
+    let first_created: std::collections::HashMap<String, String> = operations
+        .iter()
+        .map(|op| (op.id.clone(), op.created_at.clone()))
+        .collect();
+
+    let mut compacted = 0;
+    let mut dropped = 0;
+
+    for change in sync::coalesce(operations) {
+        if change.queue_ids.len() < 2 {
+            continue;
+        }
+
+        if change.op.operation == "noop" {
+            for id in &change.queue_ids {
+                tx.execute("DELETE FROM sync_queue WHERE id = ?1", params![id])
+                    .map_err(|e| e.to_string())?;
+            }
+            dropped += change.queue_ids.len();
+            continue;
+        }
+
+        // Keep the latest entry (it has the newest data) but at the position
+        // of the first one, so parents still sort before their children
+        for id in change.queue_ids.iter().filter(|id| **id != change.op.id) {
+            tx.execute("DELETE FROM sync_queue WHERE id = ?1", params![id])
+                .map_err(|e| e.to_string())?;
+        }
+        tx.execute(
+            "UPDATE sync_queue SET operation = ?1, base_version = ?2, created_at = ?3 WHERE id = ?4",
+            params![
+                change.op.operation,
+                change.op.base_version,
+                first_created.get(&change.queue_ids[0]),
+                change.op.id
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        compacted += change.queue_ids.len() - 1;
+    }
+
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok((compacted, dropped))
+}
+
+/// Delete finished entries older than `days`; pending, conflicting and
+/// dead-lettered entries are kept whatever their age
+pub fn purge_synced(conn: &Connection, days: i64) -> Result<usize, String> {
+    let cutoff = (chrono::Utc::now() - chrono::Duration::days(days)).to_rfc3339();
+    conn.execute(
+        "DELETE FROM sync_queue
+         WHERE status IN ('synced', 'superseded', 'discarded')
+           AND COALESCE(synced_at, created_at) < ?1",
+        params![cutoff],
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// `PRAGMA optimize`, plus VACUUM when enough of the file is free pages.
+/// Returns whether VACUUM ran.
+pub fn optimize(conn: &Connection) -> Result<bool, String> {
+    conn.execute_batch("PRAGMA optimize").map_err(|e| e.to_string())?;
+
+    let pragma = |name: &str| -> Result<i64, String> {
+        conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))
+            .map_err(|e| e.to_string())
+    };
+    let page_count = pragma("page_count")?;
+    let free = pragma("freelist_count")?;
+    if page_count == 0 || (free as f64) / (page_count as f64) < VACUUM_FREE_RATIO {
+        return Ok(false);
+    }
+
+    conn.execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")
+        .map_err(|e| e.to_string())?;
+    Ok(true)
+}
@@ -0,0 +1,275 @@
+use crate::settings;
+use crate::sync;
+use crate::{auth, pull, queue, AppState};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::Notify;
+
+const ENABLED_KEY: &str = "sync.auto_enabled";
+const INTERVAL_KEY: &str = "sync.interval_minutes";
+pub const LAST_SUCCESS_KEY: &str = "sync.last_success_at";
+
+/// Same default as the old JavaScript auto-sync
+const DEFAULT_INTERVAL_MINUTES: u64 = 20;
+const MIN_INTERVAL_MINUTES: u64 = 1;
+const MAX_INTERVAL_MINUTES: u64 = 24 * 60;
+
+/// Give the app (and the Next.js server) time to start before the first cycle
+const STARTUP_DELAY: Duration = Duration::from_secs(15);
+
+/// Shared state of the background sync task, managed by Tauri
+#[derive(Default)]
+pub struct SyncScheduler {
+    running: AtomicBool,
+    wake: Notify,
+    last_report: Mutex<Option<SyncCycleReport>>,
+}
+
+/// Marks a sync cycle as in progress until dropped
+pub struct CycleGuard<'a>(&'a AtomicBool);
+
+impl Drop for CycleGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+impl SyncScheduler {
+    /// Claim the right to run a cycle; `None` while another one is running
+    pub fn try_begin(&self) -> Option<CycleGuard<'_>> {
+        self.running
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()
+            .map(|_| CycleGuard(&self.running))
+    }
+
+    /// Re-read the schedule now instead of at the end of the current wait
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+}
+
+/// Background sync settings stored in `app_settings`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncSchedule {
+    pub enabled: bool,
+    pub interval_minutes: u64,
+    /// Queue entries pushed per round
+    #[serde(default = "default_page_size")]
+    pub page_size: usize,
+}
+
+fn default_page_size() -> usize {
+    sync::DEFAULT_PAGE_SIZE
+}
+
+/// Schedule plus what the background task is doing
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncScheduleStatus {
+    #[serde(flatten)]
+    pub schedule: SyncSchedule,
+    pub is_syncing: bool,
+    pub last_success_at: Option<String>,
+    pub last_report: Option<SyncCycleReport>,
+}
+
+/// Outcome of one push + pull cycle, also the payload of `sync://finished`
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncCycleReport {
+    pub trigger: String,
+    pub started_at: String,
+    pub finished_at: String,
+    pub online: bool,
+    pub pushed: i32,
+    pub failed: i32,
+    pub conflicts: i32,
+    pub pulled: usize,
+    pub pull_failed_tables: usize,
+    pub error: Option<String>,
+}
+
+/// Payload of `sync://progress`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SyncProgress<'a> {
+    phase: &'a str,
+    pushed: i32,
+    failed: i32,
+    conflicts: i32,
+    pulled: usize,
+}
+
+pub fn load_schedule(conn: &rusqlite::Connection) -> Result<SyncSchedule, String> {
+    Ok(SyncSchedule {
+        enabled: settings::get_or(conn, ENABLED_KEY, true)?,
+        interval_minutes: settings::get_or(conn, INTERVAL_KEY, DEFAULT_INTERVAL_MINUTES)?
+            .clamp(MIN_INTERVAL_MINUTES, MAX_INTERVAL_MINUTES),
+        page_size: settings::get_or(conn, sync::PAGE_SIZE_KEY, sync::DEFAULT_PAGE_SIZE)?
+            .clamp(1, sync::MAX_PAGE_SIZE),
+    })
+}
+
+pub fn save_schedule(conn: &rusqlite::Connection, schedule: &SyncSchedule) -> Result<(), String> {
+    if !(MIN_INTERVAL_MINUTES..=MAX_INTERVAL_MINUTES).contains(&schedule.interval_minutes) {
+        return Err(format!(
+            "Sync interval must be between {} and {} minutes",
+            MIN_INTERVAL_MINUTES, MAX_INTERVAL_MINUTES
+        ));
+    }
+
+    if !(1..=sync::MAX_PAGE_SIZE).contains(&schedule.page_size) {
+        return Err(format!("Page size must be between 1 and {}", sync::MAX_PAGE_SIZE));
+    }
+
+    settings::set(conn, ENABLED_KEY, &schedule.enabled.to_string())?;
+    settings::set(conn, INTERVAL_KEY, &schedule.interval_minutes.to_string())?;
+    settings::set(conn, sync::PAGE_SIZE_KEY, &schedule.page_size.to_string())
+}
+
+pub fn status(app: &AppHandle) -> Result<SyncScheduleStatus, String> {
+    let scheduler = app.state::<SyncScheduler>();
+    let state = app.state::<AppState>();
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    let last_report = scheduler.last_report.lock().map_err(|e| e.to_string())?.clone();
+
+    Ok(SyncScheduleStatus {
+        schedule: load_schedule(conn)?,
+        is_syncing: scheduler.running.load(Ordering::SeqCst),
+        last_success_at: settings::get(conn, LAST_SUCCESS_KEY)?,
+        last_report,
+    })
+}
+
+/// Spawn the background task: one cycle per interval while auto-sync is
+/// enabled. Changing the schedule wakes it up early.
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(STARTUP_DELAY).await;
+
+        loop {
+            let schedule = {
+                let state = app.state::<AppState>();
+                let db_guard = state.db.lock().ok();
+                db_guard
+                    .as_ref()
+                    .and_then(|g| g.as_ref())
+                    .and_then(|conn| load_schedule(conn).ok())
+            };
+            let schedule = schedule.unwrap_or(SyncSchedule {
+                enabled: false,
+                interval_minutes: DEFAULT_INTERVAL_MINUTES,
+                page_size: sync::DEFAULT_PAGE_SIZE,
+            });
+
+            if schedule.enabled {
+                if let Err(e) = run_cycle(&app, "scheduled").await {
+                    println!("Background sync skipped: {}", e);
+                }
+            }
+
+            match maintain(&app, false) {
+                Ok(Some(report)) => println!(
+                    "Sync queue maintenance: {} compacted, {} dropped, {} purged, vacuum: {}",
+                    report.compacted, report.dropped, report.purged, report.vacuumed
+                ),
+                Ok(None) => {}
+                Err(e) => println!("Sync queue maintenance skipped: {}", e),
+            }
+
+            let scheduler = app.state::<SyncScheduler>();
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(schedule.interval_minutes * 60)) => {}
+                _ = scheduler.wake.notified() => {}
+            }
+        }
+    });
+}
+
+/// Run one push + pull cycle and report it through `sync://` events.
+/// Fails without doing anything if a cycle is already running.
+pub async fn run_cycle(app: &AppHandle, trigger: &str) -> Result<SyncCycleReport, String> {
+    let scheduler = app.state::<SyncScheduler>();
+    let _guard = scheduler.try_begin().ok_or("Sync already in progress")?;
+
+    let mut report = SyncCycleReport {
+        trigger: trigger.to_string(),
+        started_at: chrono::Utc::now().to_rfc3339(),
+        ..Default::default()
+    };
+    app.emit("sync://started", &report).ok();
+
+    let result = cycle(app, &mut report).await;
+    report.error = result.err();
+    report.finished_at = chrono::Utc::now().to_rfc3339();
+
+    if report.error.is_none() && report.failed == 0 && report.pull_failed_tables == 0 {
+        let state = app.state::<AppState>();
+        let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+        if let Some(conn) = db_guard.as_ref() {
+            settings::set(conn, LAST_SUCCESS_KEY, &report.finished_at)?;
+        }
+    }
+
+    *scheduler.last_report.lock().map_err(|e| e.to_string())? = Some(report.clone());
+    app.emit("sync://finished", &report).ok();
+
+    Ok(report)
+}
+
+/// Compact and purge the sync queue and optimize the database, between
+/// sync cycles so no push is working on the entries being rewritten.
+/// Unless `force`d, does nothing when the last run is less than a day old.
+pub fn maintain(app: &AppHandle, force: bool) -> Result<Option<queue::MaintenanceReport>, String> {
+    let scheduler = app.state::<SyncScheduler>();
+    let _guard = scheduler.try_begin().ok_or("Sync already in progress")?;
+
+    let state = app.state::<AppState>();
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+
+    if !force && !queue::maintenance_due(conn)? {
+        return Ok(None);
+    }
+    queue::run_maintenance(conn).map(Some)
+}
+
+async fn cycle(app: &AppHandle, report: &mut SyncCycleReport) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let config = auth::resolve_config(&state.db).await?;
+
+    if !sync::check_connectivity(&config).await {
+        return Err("Supabase is unreachable".to_string());
+    }
+    report.online = true;
+
+    emit_progress(app, "push", report);
+    let pushed = sync::push_pending(&state.db, &config).await?;
+    report.pushed = pushed.synced;
+    report.failed = pushed.failed;
+    report.conflicts = pushed.conflicts;
+
+    emit_progress(app, "pull", report);
+    let pulled = pull::pull_all(&state.db, &config).await;
+    report.pulled = pulled.pulled;
+    report.pull_failed_tables = pulled.failed_tables;
+
+    Ok(())
+}
+
+fn emit_progress(app: &AppHandle, phase: &str, report: &SyncCycleReport) {
+    let progress = SyncProgress {
+        phase,
+        pushed: report.pushed,
+        failed: report.failed,
+        conflicts: report.conflicts,
+        pulled: report.pulled,
+    };
+    app.emit("sync://progress", progress).ok();
+}
@@ -0,0 +1,839 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// Storage class of a column, as declared in `database/sqlite-schema.sql`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ColumnType {
+    Text,
+    Integer,
+    Real,
+}
+
+/// A column known to the registry
+#[derive(Debug)]
+pub struct ColumnDef {
+    pub name: &'static str,
+    pub kind: ColumnType,
+}
+
+impl ColumnDef {
+    const fn text(name: &'static str) -> Self {
+        Self { name, kind: ColumnType::Text }
+    }
+
+    const fn integer(name: &'static str) -> Self {
+        Self { name, kind: ColumnType::Integer }
+    }
+
+    const fn real(name: &'static str) -> Self {
+        Self { name, kind: ColumnType::Real }
+    }
+}
+
+/// A table the generic CRUD commands are allowed to touch
+#[derive(Debug)]
+pub struct TableDef {
+    pub name: &'static str,
+    pub columns: &'static [ColumnDef],
+    /// Foreign keys as `(column, parent table)`
+    pub references: &'static [(&'static str, &'static str)],
+}
+
+impl TableDef {
+    pub fn column(&self, name: &str) -> Option<&ColumnDef> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    /// Whether the table carries the `sync_status`/`sync_version` tracking columns
+    pub fn is_synced(&self) -> bool {
+        self.column("sync_status").is_some() && self.column("sync_version").is_some()
+    }
+}
+
+/// Error returned by the table-checked CRUD commands.
+/// Serialized to the webview as `{ "kind": "...", ... }`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DbError {
+    UnknownTable { table: String },
+    UnknownColumn { table: String, column: String },
+    InvalidValue { table: String, column: String, expected: ColumnType },
+    InvalidData { message: String },
+    Database { message: String },
+    /// An operation inside `db_transaction` failed and the batch was rolled back
+    Batch { index: usize, error: Box<DbError> },
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::UnknownTable { table } => write!(f, "Unknown table: {}", table),
+            DbError::UnknownColumn { table, column } => {
+                write!(f, "Unknown column {} on table {}", column, table)
+            }
+            DbError::InvalidValue { table, column, expected } => {
+                write!(f, "Invalid value for {}.{} (expected {:?})", table, column, expected)
+            }
+            DbError::InvalidData { message } | DbError::Database { message } => {
+                write!(f, "{}", message)
+            }
+            DbError::Batch { index, error } => {
+                write!(f, "Operation {} failed, transaction rolled back: {}", index, error)
+            }
+        }
+    }
+}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(e: rusqlite::Error) -> Self {
+        DbError::Database { message: e.to_string() }
+    }
+}
+
+impl From<String> for DbError {
+    fn from(message: String) -> Self {
+        DbError::Database { message }
+    }
+}
+
+impl From<&str> for DbError {
+    fn from(message: &str) -> Self {
+        DbError::Database { message: message.to_string() }
+    }
+}
+
+/// Look up a table, rejecting anything not in the registry
+pub fn table(name: &str) -> Result<&'static TableDef, DbError> {
+    TABLES
+        .iter()
+        .find(|t| t.name == name)
+        .ok_or_else(|| DbError::UnknownTable { table: name.to_string() })
+}
+
+/// Tables ordered so every parent comes before the tables referencing it
+pub fn dependency_order() -> Vec<&'static TableDef> {
+    let mut ordered: Vec<&'static TableDef> = Vec::with_capacity(TABLES.len());
+
+    while ordered.len() < TABLES.len() {
+        let before = ordered.len();
+        for table in TABLES {
+            let placed = |name: &str| ordered.iter().any(|t| t.name == name);
+            if placed(table.name) {
+                continue;
+            }
+            let ready = table
+                .references
+                .iter()
+                .all(|(_, parent)| *parent == table.name || placed(parent));
+            if ready {
+                ordered.push(table);
+            }
+        }
+        // A reference cycle would never resolve; append the rest as-is
+        if ordered.len() == before {
+            for table in TABLES {
+                if !ordered.iter().any(|t| t.name == table.name) {
+                    ordered.push(table);
+                }
+            }
+        }
+    }
+
+    ordered
+}
+
+/// Quote an identifier for use in SQL
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Validate every key of `data` against `table` and convert the values.
+/// Returns the quoted column names alongside the bound values, in the same order.
+pub fn validate_data(
+    table: &TableDef,
+    data: &Map<String, Value>,
+) -> Result<(Vec<String>, Vec<rusqlite::types::Value>), DbError> {
+    let mut columns = Vec::with_capacity(data.len());
+    let mut values = Vec::with_capacity(data.len());
+
+    for (key, value) in data {
+        let column = table.column(key).ok_or_else(|| DbError::UnknownColumn {
+            table: table.name.to_string(),
+            column: key.clone(),
+        })?;
+
+        if !value_fits(column.kind, value) {
+            return Err(DbError::InvalidValue {
+                table: table.name.to_string(),
+                column: key.clone(),
+                expected: column.kind,
+            });
+        }
+
+        columns.push(quote_ident(column.name));
+        values.push(crate::json_to_rusqlite_value(value));
+    }
+
+    Ok((columns, values))
+}
+
+/// Build a checked `INSERT` for `data`, generating an `id` when none is given.
+/// Returns the record id, the SQL and its parameters.
+pub fn build_insert(
+    table: &TableDef,
+    data: &Map<String, Value>,
+) -> Result<(String, String, Vec<rusqlite::types::Value>), DbError> {
+    let mut data = data.clone();
+    let id = match data.get("id").and_then(|v| v.as_str()) {
+        Some(id) => id.to_string(),
+        None => {
+            let id = uuid::Uuid::new_v4().to_string();
+            data.insert("id".to_string(), Value::String(id.clone()));
+            id
+        }
+    };
+
+    let (columns, values) = validate_data(table, &data)?;
+    let placeholders: Vec<&str> = columns.iter().map(|_| "?").collect();
+
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        quote_ident(table.name),
+        columns.join(", "),
+        placeholders.join(", ")
+    );
+
+    Ok((id, sql, values))
+}
+
+/// Build a checked `UPDATE ... WHERE id = ?` for `data`
+pub fn build_update(
+    table: &TableDef,
+    id: &str,
+    data: &Map<String, Value>,
+) -> Result<(String, Vec<rusqlite::types::Value>), DbError> {
+    if data.is_empty() {
+        return Err(DbError::InvalidData {
+            message: format!("No columns to update on {}", table.name),
+        });
+    }
+
+    let (columns, mut values) = validate_data(table, data)?;
+    let set_clauses: Vec<String> = columns.iter().map(|c| format!("{} = ?", c)).collect();
+
+    let sql = format!(
+        "UPDATE {} SET {} WHERE \"id\" = ?",
+        quote_ident(table.name),
+        set_clauses.join(", ")
+    );
+    values.push(rusqlite::types::Value::Text(id.to_string()));
+
+    Ok((sql, values))
+}
+
+/// Build a checked `DELETE ... WHERE id = ?`
+pub fn build_delete(table: &TableDef) -> String {
+    format!("DELETE FROM {} WHERE \"id\" = ?", quote_ident(table.name))
+}
+
+// SQLite would happily store a string in a REAL column; reject values that
+// clearly do not belong so bad payloads fail loudly instead of corrupting totals.
+fn value_fits(kind: ColumnType, value: &Value) -> bool {
+    match (kind, value) {
+        (_, Value::Null) => true,
+        (ColumnType::Text, _) => true,
+        (ColumnType::Integer, Value::Bool(_)) => true,
+        (ColumnType::Integer, Value::Number(n)) => {
+            n.is_i64() || n.as_f64().map(|f| f.fract() == 0.0).unwrap_or(false)
+        }
+        (ColumnType::Integer, Value::String(s)) => s.trim().parse::<i64>().is_ok(),
+        (ColumnType::Real, Value::Number(_)) => true,
+        (ColumnType::Real, Value::String(s)) => s.trim().parse::<f64>().is_ok(),
+        _ => false,
+    }
+}
+
+/// Every table defined in `database/sqlite-schema.sql` and later migrations.
+/// Keep in sync with the migrations when a table or column is added.
+pub const TABLES: &[TableDef] = &[
+    TableDef {
+        name: "barang",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("nama"),
+            ColumnDef::text("deskripsi"),
+            ColumnDef::text("kategori_id"),
+            ColumnDef::text("subkategori_id"),
+            ColumnDef::text("satuan_dasar"),
+            ColumnDef::text("spesifikasi"),
+            ColumnDef::real("jumlah_stok"),
+            ColumnDef::real("level_stok_minimum"),
+            ColumnDef::integer("lacak_inventori_status"),
+            ColumnDef::integer("butuh_dimensi_status"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::integer("frekuensi_terjual"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("kategori_id", "kategori_barang"),
+            ("subkategori_id", "subkategori_barang"),
+        ],
+    },
+    TableDef {
+        name: "harga_barang_satuan",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("barang_id"),
+            ColumnDef::text("nama_satuan"),
+            ColumnDef::real("faktor_konversi"),
+            ColumnDef::real("harga_beli"),
+            ColumnDef::real("harga_jual"),
+            ColumnDef::real("harga_member"),
+            ColumnDef::integer("default_status"),
+            ColumnDef::integer("urutan_tampilan"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("barang_id", "barang"),
+        ],
+    },
+    TableDef {
+        name: "hutang_pembelian",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("id_pembelian"),
+            ColumnDef::real("jumlah_hutang"),
+            ColumnDef::real("jumlah_terbayar"),
+            ColumnDef::real("sisa_hutang"),
+            ColumnDef::text("jatuh_tempo"),
+            ColumnDef::text("status"),
+            ColumnDef::text("catatan"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("id_pembelian", "pembelian"),
+        ],
+    },
+    TableDef {
+        name: "item_finishing",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("item_produksi_id"),
+            ColumnDef::text("jenis_finishing"),
+            ColumnDef::text("keterangan"),
+            ColumnDef::text("status"),
+            ColumnDef::text("operator_id"),
+            ColumnDef::text("mulai_proses"),
+            ColumnDef::text("selesai_proses"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("item_produksi_id", "item_produksi"),
+            ("operator_id", "profil"),
+        ],
+    },
+    TableDef {
+        name: "item_pembelian",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("pembelian_id"),
+            ColumnDef::text("barang_id"),
+            ColumnDef::text("harga_satuan_id"),
+            ColumnDef::real("jumlah"),
+            ColumnDef::text("nama_satuan"),
+            ColumnDef::real("faktor_konversi"),
+            ColumnDef::real("harga_satuan"),
+            ColumnDef::real("subtotal"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("barang_id", "barang"),
+            ("harga_satuan_id", "harga_barang_satuan"),
+            ("pembelian_id", "pembelian"),
+        ],
+    },
+    TableDef {
+        name: "item_penjualan",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("penjualan_id"),
+            ColumnDef::text("barang_id"),
+            ColumnDef::text("harga_satuan_id"),
+            ColumnDef::real("jumlah"),
+            ColumnDef::text("nama_satuan"),
+            ColumnDef::real("faktor_konversi"),
+            ColumnDef::real("harga_satuan"),
+            ColumnDef::real("subtotal"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("barang_id", "barang"),
+            ("harga_satuan_id", "harga_barang_satuan"),
+            ("penjualan_id", "penjualan"),
+        ],
+    },
+    TableDef {
+        name: "item_produksi",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("order_produksi_id"),
+            ColumnDef::text("item_penjualan_id"),
+            ColumnDef::text("barang_nama"),
+            ColumnDef::real("jumlah"),
+            ColumnDef::text("nama_satuan"),
+            ColumnDef::real("panjang"),
+            ColumnDef::real("lebar"),
+            ColumnDef::text("keterangan_dimensi"),
+            ColumnDef::text("mesin_printing"),
+            ColumnDef::text("jenis_bahan"),
+            ColumnDef::text("status"),
+            ColumnDef::text("catatan_produksi"),
+            ColumnDef::text("operator_id"),
+            ColumnDef::text("mulai_proses"),
+            ColumnDef::text("selesai_proses"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("item_penjualan_id", "item_penjualan"),
+            ("operator_id", "profil"),
+            ("order_produksi_id", "order_produksi"),
+        ],
+    },
+    TableDef {
+        name: "item_stok_opname",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("stok_opname_id"),
+            ColumnDef::text("barang_id"),
+            ColumnDef::real("stok_sistem"),
+            ColumnDef::real("jumlah_hitung"),
+            ColumnDef::real("jumlah_input"),
+            ColumnDef::text("satuan_input"),
+            ColumnDef::real("faktor_konversi"),
+            ColumnDef::real("harga_beli"),
+            ColumnDef::text("catatan"),
+            ColumnDef::text("dihitung_pada"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("stok_opname_id", "stok_opname"),
+            ("barang_id", "barang"),
+        ],
+    },
+    TableDef {
+        name: "kategori_barang",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("nama"),
+            ColumnDef::integer("butuh_spesifikasi_status"),
+            ColumnDef::integer("urutan_tampilan"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[],
+    },
+    TableDef {
+        name: "keuangan",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("tanggal"),
+            ColumnDef::text("kategori_transaksi"),
+            ColumnDef::real("debit"),
+            ColumnDef::real("kredit"),
+            ColumnDef::text("keperluan"),
+            ColumnDef::real("omzet"),
+            ColumnDef::real("biaya_operasional"),
+            ColumnDef::real("biaya_bahan"),
+            ColumnDef::real("saldo"),
+            ColumnDef::real("laba_bersih"),
+            ColumnDef::real("kasbon_anwar"),
+            ColumnDef::real("kasbon_suri"),
+            ColumnDef::real("kasbon_cahaya"),
+            ColumnDef::real("kasbon_dinil"),
+            ColumnDef::real("bagi_hasil_anwar"),
+            ColumnDef::real("bagi_hasil_suri"),
+            ColumnDef::real("bagi_hasil_gemi"),
+            ColumnDef::text("catatan"),
+            ColumnDef::text("dibuat_oleh"),
+            ColumnDef::text("diarsipkan_pada"),
+            ColumnDef::text("label_arsip"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::integer("urutan_tampilan"),
+            ColumnDef::integer("override_saldo"),
+            ColumnDef::integer("override_omzet"),
+            ColumnDef::integer("override_biaya_operasional"),
+            ColumnDef::integer("override_biaya_bahan"),
+            ColumnDef::integer("override_laba_bersih"),
+            ColumnDef::integer("override_kasbon_anwar"),
+            ColumnDef::integer("override_kasbon_suri"),
+            ColumnDef::integer("override_kasbon_cahaya"),
+            ColumnDef::integer("override_kasbon_dinil"),
+            ColumnDef::integer("override_bagi_hasil_anwar"),
+            ColumnDef::integer("override_bagi_hasil_suri"),
+            ColumnDef::integer("override_bagi_hasil_gemi"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[],
+    },
+    TableDef {
+        name: "kredensial",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("pemilik_id"),
+            ColumnDef::text("nama_layanan"),
+            ColumnDef::text("nama_pengguna_akun"),
+            ColumnDef::text("password_terenkripsi"),
+            ColumnDef::text("catatan"),
+            ColumnDef::integer("privat_status"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("pemilik_id", "profil"),
+        ],
+    },
+    TableDef {
+        name: "mutasi_stok",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("barang_id"),
+            ColumnDef::text("tanggal"),
+            ColumnDef::text("jenis"),
+            ColumnDef::real("jumlah"),
+            ColumnDef::real("stok_sebelum"),
+            ColumnDef::real("stok_sesudah"),
+            ColumnDef::text("satuan"),
+            ColumnDef::text("referensi_tabel"),
+            ColumnDef::text("referensi_id"),
+            ColumnDef::text("catatan"),
+            ColumnDef::text("dibuat_oleh"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("barang_id", "barang"),
+            ("dibuat_oleh", "profil"),
+        ],
+    },
+    TableDef {
+        name: "opsi_finishing",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("nama"),
+            ColumnDef::integer("urutan_tampilan"),
+            ColumnDef::integer("aktif_status"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[],
+    },
+    TableDef {
+        name: "order_produksi",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("penjualan_id"),
+            ColumnDef::text("nomor_spk"),
+            ColumnDef::text("pelanggan_nama"),
+            ColumnDef::integer("total_item"),
+            ColumnDef::text("status"),
+            ColumnDef::text("prioritas"),
+            ColumnDef::text("tanggal_deadline"),
+            ColumnDef::text("catatan"),
+            ColumnDef::text("dibuat_oleh"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("diselesaikan_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("dibuat_oleh", "profil"),
+            ("penjualan_id", "penjualan"),
+        ],
+    },
+    TableDef {
+        name: "pelanggan",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("tipe_pelanggan"),
+            ColumnDef::text("nama"),
+            ColumnDef::text("nama_perusahaan"),
+            ColumnDef::text("npwp"),
+            ColumnDef::text("email"),
+            ColumnDef::text("telepon"),
+            ColumnDef::text("alamat"),
+            ColumnDef::integer("member_status"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[],
+    },
+    TableDef {
+        name: "pelunasan_hutang",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("id_hutang"),
+            ColumnDef::text("tanggal_bayar"),
+            ColumnDef::real("jumlah_bayar"),
+            ColumnDef::text("metode_pembayaran"),
+            ColumnDef::text("referensi"),
+            ColumnDef::text("catatan"),
+            ColumnDef::text("dibuat_oleh"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("dibuat_oleh", "profil"),
+            ("id_hutang", "hutang_pembelian"),
+        ],
+    },
+    TableDef {
+        name: "pelunasan_piutang",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("id_piutang"),
+            ColumnDef::text("tanggal_bayar"),
+            ColumnDef::real("jumlah_bayar"),
+            ColumnDef::text("metode_pembayaran"),
+            ColumnDef::text("referensi"),
+            ColumnDef::text("catatan"),
+            ColumnDef::text("dibuat_oleh"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("dibuat_oleh", "profil"),
+            ("id_piutang", "piutang_penjualan"),
+        ],
+    },
+    TableDef {
+        name: "pembelian",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("nomor_pembelian"),
+            ColumnDef::text("vendor_id"),
+            ColumnDef::real("total_jumlah"),
+            ColumnDef::real("jumlah_dibayar"),
+            ColumnDef::text("metode_pembayaran"),
+            ColumnDef::text("catatan"),
+            ColumnDef::text("dibuat_oleh"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("tanggal"),
+            ColumnDef::text("nomor_faktur"),
+            ColumnDef::text("status_pembayaran"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("dibuat_oleh", "profil"),
+            ("vendor_id", "vendor"),
+        ],
+    },
+    TableDef {
+        name: "penjualan",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("nomor_invoice"),
+            ColumnDef::text("pelanggan_id"),
+            ColumnDef::real("total_jumlah"),
+            ColumnDef::real("jumlah_dibayar"),
+            ColumnDef::real("jumlah_kembalian"),
+            ColumnDef::text("metode_pembayaran"),
+            ColumnDef::text("kasir_id"),
+            ColumnDef::text("catatan"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("kasir_id", "profil"),
+            ("pelanggan_id", "pelanggan"),
+        ],
+    },
+    TableDef {
+        name: "piutang_penjualan",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("id_penjualan"),
+            ColumnDef::real("jumlah_piutang"),
+            ColumnDef::real("jumlah_terbayar"),
+            ColumnDef::real("sisa_piutang"),
+            ColumnDef::text("jatuh_tempo"),
+            ColumnDef::text("status"),
+            ColumnDef::text("catatan"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("id_penjualan", "penjualan"),
+        ],
+    },
+    TableDef {
+        name: "profil",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("nama_pengguna"),
+            ColumnDef::text("email"),
+            ColumnDef::text("nama_lengkap"),
+            ColumnDef::text("password_hash"),
+            ColumnDef::text("role"),
+            ColumnDef::integer("aktif_status"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[],
+    },
+    TableDef {
+        name: "satuan_barang",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("nama"),
+            ColumnDef::integer("urutan_tampilan"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[],
+    },
+    TableDef {
+        name: "spesifikasi_cepat_barang",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("kategori_id"),
+            ColumnDef::text("tipe_spesifikasi"),
+            ColumnDef::text("nilai_spesifikasi"),
+            ColumnDef::integer("urutan_tampilan"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("kategori_id", "kategori_barang"),
+        ],
+    },
+    TableDef {
+        name: "stok_opname",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("nomor_opname"),
+            ColumnDef::text("tanggal"),
+            ColumnDef::text("status"),
+            ColumnDef::text("kategori_ids"),
+            ColumnDef::text("catatan"),
+            ColumnDef::text("dibuat_oleh"),
+            ColumnDef::text("diposting_pada"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("dibuat_oleh", "profil"),
+        ],
+    },
+    TableDef {
+        name: "subkategori_barang",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("kategori_id"),
+            ColumnDef::text("nama"),
+            ColumnDef::integer("urutan_tampilan"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[
+            ("kategori_id", "kategori_barang"),
+        ],
+    },
+    TableDef {
+        name: "vendor",
+        columns: &[
+            ColumnDef::text("id"),
+            ColumnDef::text("nama_perusahaan"),
+            ColumnDef::text("email"),
+            ColumnDef::text("telepon"),
+            ColumnDef::text("alamat"),
+            ColumnDef::text("kontak_person"),
+            ColumnDef::text("ketentuan_bayar"),
+            ColumnDef::integer("aktif_status"),
+            ColumnDef::text("catatan"),
+            ColumnDef::text("dibuat_pada"),
+            ColumnDef::text("diperbarui_pada"),
+            ColumnDef::text("sync_status"),
+            ColumnDef::text("last_synced_at"),
+            ColumnDef::integer("sync_version"),
+        ],
+        references: &[],
+    },
+];
@@ -0,0 +1,97 @@
+use crate::settings;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rusqlite::Connection;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Key file next to the database. Copying the database alone (a backup, a
+/// support request) does not expose the secrets stored in it.
+const KEY_FILE: &str = "secret.key";
+
+/// Prefix of encrypted values: `enc:v1:` + base64(nonce || ciphertext)
+const PREFIX: &str = "enc:v1:";
+
+const NONCE_LEN: usize = 12;
+
+static KEY: OnceLock<Key> = OnceLock::new();
+
+/// Load the local encryption key, creating it on first run
+pub fn init(app_data_dir: &Path) -> Result<(), String> {
+    let path = app_data_dir.join(KEY_FILE);
+
+    let key = match fs::read(&path) {
+        Ok(bytes) if bytes.len() == 32 => *Key::from_slice(&bytes),
+        Ok(_) => return Err(format!("{} is corrupt", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+            fs::write(&path, key.as_slice()).map_err(|e| e.to_string())?;
+            restrict_permissions(&path)?;
+            key
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+
+    KEY.set(key).ok();
+    Ok(())
+}
+
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600)).map_err(|e| e.to_string())
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+fn cipher() -> Result<ChaCha20Poly1305, String> {
+    KEY.get()
+        .map(ChaCha20Poly1305::new)
+        .ok_or_else(|| "Secret store not initialized".to_string())
+}
+
+pub fn encrypt(plaintext: &str) -> Result<String, String> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher()?
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|_| "Failed to encrypt secret")?;
+
+    let mut blob = nonce.to_vec();
+    blob.extend_from_slice(&ciphertext);
+    Ok(format!("{}{}", PREFIX, BASE64.encode(blob)))
+}
+
+/// Decrypt a stored value. Values written before encryption was introduced
+/// are returned unchanged and get encrypted on their next write.
+pub fn decrypt(stored: &str) -> Result<String, String> {
+    let Some(encoded) = stored.strip_prefix(PREFIX) else {
+        return Ok(stored.to_string());
+    };
+
+    let blob = BASE64.decode(encoded).map_err(|e| e.to_string())?;
+    if blob.len() < NONCE_LEN {
+        return Err("Stored secret is truncated".to_string());
+    }
+    let (nonce, ciphertext) = blob.split_at(NONCE_LEN);
+    let plaintext = cipher()?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Stored secret could not be decrypted (was secret.key replaced?)")?;
+
+    String::from_utf8(plaintext).map_err(|e| e.to_string())
+}
+
+/// Decrypted value of a secret setting
+pub fn get(conn: &Connection, key: &str) -> Result<Option<String>, String> {
+    settings::get(conn, key)?.map(|v| decrypt(&v)).transpose()
+}
+
+/// Store a setting encrypted
+pub fn set(conn: &Connection, key: &str, value: &str) -> Result<(), String> {
+    settings::set(conn, key, &encrypt(value)?)
+}
@@ -0,0 +1,40 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use std::str::FromStr;
+
+/// Raw value of a setting, if it was ever stored
+pub fn get(conn: &Connection, key: &str) -> Result<Option<String>, String> {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        params![key],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+/// Parsed value of a setting, or `default` when missing or unreadable
+pub fn get_or<T: FromStr>(conn: &Connection, key: &str, default: T) -> Result<T, String> {
+    Ok(get(conn, key)?
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default))
+}
+
+/// Store a setting, replacing any previous value
+pub fn set(conn: &Connection, key: &str, value: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO app_settings (key, value, updated_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+        params![key, value, chrono::Utc::now().to_rfc3339()],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Delete a setting; later reads fall back to the default
+pub fn remove(conn: &Connection, key: &str) -> Result<(), String> {
+    conn.execute("DELETE FROM app_settings WHERE key = ?1", params![key])
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
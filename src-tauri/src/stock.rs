@@ -0,0 +1,418 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Why a barang's stock changed (`mutasi_stok.jenis`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum JenisMutasi {
+    /// Stock the barang already had when its ledger started
+    SaldoAwal,
+    Penjualan,
+    Pembelian,
+    RusakProduksi,
+    Penyesuaian,
+    StokOpname,
+}
+
+impl JenisMutasi {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JenisMutasi::SaldoAwal => "SALDO_AWAL",
+            JenisMutasi::Penjualan => "PENJUALAN",
+            JenisMutasi::Pembelian => "PEMBELIAN",
+            JenisMutasi::RusakProduksi => "RUSAK_PRODUKSI",
+            JenisMutasi::Penyesuaian => "PENYESUAIAN",
+            JenisMutasi::StokOpname => "STOK_OPNAME",
+        }
+    }
+}
+
+/// A stock change to record; `jumlah` is signed and in `satuan_dasar`
+#[derive(Debug)]
+pub struct Mutasi<'a> {
+    pub barang_id: &'a str,
+    pub jenis: JenisMutasi,
+    pub jumlah: f64,
+    /// Row that caused the change, e.g. `("item_penjualan", id)`
+    pub referensi: Option<(&'a str, &'a str)>,
+    pub catatan: Option<&'a str>,
+    pub dibuat_oleh: Option<&'a str>,
+}
+
+/// A `mutasi_stok` row
+#[derive(Debug, Serialize)]
+pub struct MutasiStok {
+    pub id: String,
+    pub barang_id: String,
+    pub tanggal: String,
+    pub jenis: String,
+    pub jumlah: f64,
+    pub stok_sebelum: f64,
+    pub stok_sesudah: f64,
+    pub satuan: Option<String>,
+    pub referensi_tabel: Option<String>,
+    pub referensi_id: Option<String>,
+    pub catatan: Option<String>,
+    pub dibuat_oleh: Option<String>,
+}
+
+/// Payload of `adjust_stock`: a correction or production waste entered by hand
+#[derive(Debug, Deserialize)]
+pub struct AdjustStockRequest {
+    pub barang_id: String,
+    pub jenis: JenisMutasi,
+    /// Signed change in `satuan_dasar`
+    pub jumlah: f64,
+    /// e.g. the item_produksi the waste came from
+    pub referensi_tabel: Option<String>,
+    pub referensi_id: Option<String>,
+    pub catatan: Option<String>,
+    pub dibuat_oleh: Option<String>,
+}
+
+/// A barang whose `jumlah_stok` was compared with its ledger
+#[derive(Debug, Serialize)]
+pub struct StockRecompute {
+    pub barang_id: String,
+    pub nama: String,
+    pub jumlah_stok: f64,
+    pub jumlah_ledger: f64,
+    /// `jumlah_stok` differs from the ledger
+    pub drifted: bool,
+    /// `jumlah_stok` was set to the ledger sum
+    pub updated: bool,
+}
+
+/// Apply a stock change to `barang.jumlah_stok` and record it in the ledger.
+/// Call inside the transaction that writes the cause (sale, purchase, ...).
+/// The first change of a barang also records its existing stock as
+/// SALDO_AWAL, and a stock figure changed outside the ledger is recorded as
+/// PENYESUAIAN first, so the ledger always sums to `jumlah_stok`.
+pub fn apply(conn: &Connection, mutasi: &Mutasi, now: &str) -> Result<MutasiStok, String> {
+    if !mutasi.jumlah.is_finite() {
+        return Err(format!("Jumlah mutasi tidak valid untuk barang {}", mutasi.barang_id));
+    }
+
+    let (sebelum, satuan): (f64, Option<String>) = conn
+        .query_row(
+            "SELECT COALESCE(jumlah_stok, 0), satuan_dasar FROM barang WHERE id = ?1",
+            params![mutasi.barang_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Barang tidak ditemukan: {}", mutasi.barang_id))?;
+
+    let (entries, jumlah_ledger): (i64, f64) = conn
+        .query_row(
+            "SELECT COUNT(*), COALESCE(SUM(jumlah), 0) FROM mutasi_stok WHERE barang_id = ?1",
+            params![mutasi.barang_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    if entries == 0 && sebelum != 0.0 {
+        // Fixed id: two PCs opening the same ledger produce one row in the cloud
+        let opening = MutasiStok {
+            id: format!("saldo-awal-{}", mutasi.barang_id),
+            barang_id: mutasi.barang_id.to_string(),
+            tanggal: now.to_string(),
+            jenis: JenisMutasi::SaldoAwal.as_str().to_string(),
+            jumlah: sebelum,
+            stok_sebelum: 0.0,
+            stok_sesudah: sebelum,
+            satuan: satuan.clone(),
+            referensi_tabel: None,
+            referensi_id: None,
+            catatan: Some("Stok sebelum pencatatan mutasi".to_string()),
+            dibuat_oleh: None,
+        };
+        insert(conn, &opening)?;
+    } else if entries > 0 && (sebelum - jumlah_ledger).abs() >= 1e-6 {
+        // jumlah_stok was changed without a movement, e.g. typed into the
+        // material form or synced from an older client
+        let untracked = MutasiStok {
+            id: Uuid::new_v4().to_string(),
+            barang_id: mutasi.barang_id.to_string(),
+            tanggal: now.to_string(),
+            jenis: JenisMutasi::Penyesuaian.as_str().to_string(),
+            jumlah: sebelum - jumlah_ledger,
+            stok_sebelum: jumlah_ledger,
+            stok_sesudah: sebelum,
+            satuan: satuan.clone(),
+            referensi_tabel: None,
+            referensi_id: None,
+            catatan: Some("Perubahan stok di luar pencatatan mutasi".to_string()),
+            dibuat_oleh: None,
+        };
+        insert(conn, &untracked)?;
+    }
+
+    let sesudah = sebelum + mutasi.jumlah;
+    conn.execute(
+        "UPDATE barang SET jumlah_stok = ?1, diperbarui_pada = ?2 WHERE id = ?3",
+        params![sesudah, now, mutasi.barang_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let row = MutasiStok {
+        id: Uuid::new_v4().to_string(),
+        barang_id: mutasi.barang_id.to_string(),
+        tanggal: now.to_string(),
+        jenis: mutasi.jenis.as_str().to_string(),
+        jumlah: mutasi.jumlah,
+        stok_sebelum: sebelum,
+        stok_sesudah: sesudah,
+        satuan,
+        referensi_tabel: mutasi.referensi.map(|(table, _)| table.to_string()),
+        referensi_id: mutasi.referensi.map(|(_, id)| id.to_string()),
+        catatan: mutasi.catatan.map(str::to_string),
+        dibuat_oleh: mutasi.dibuat_oleh.map(str::to_string),
+    };
+    insert(conn, &row)?;
+
+    Ok(row)
+}
+
+fn insert(conn: &Connection, row: &MutasiStok) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO mutasi_stok (id, barang_id, tanggal, jenis, jumlah, stok_sebelum, stok_sesudah,
+            satuan, referensi_tabel, referensi_id, catatan, dibuat_oleh, dibuat_pada)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?3)",
+        params![
+            row.id,
+            row.barang_id,
+            row.tanggal,
+            row.jenis,
+            row.jumlah,
+            row.stok_sebelum,
+            row.stok_sesudah,
+            row.satuan,
+            row.referensi_tabel,
+            row.referensi_id,
+            row.catatan,
+            row.dibuat_oleh
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Record a manual correction or production waste in its own transaction.
+/// Sales, purchases and stock opname write their movements themselves.
+pub fn adjust(conn: &mut Connection, req: AdjustStockRequest) -> Result<MutasiStok, String> {
+    match req.jenis {
+        JenisMutasi::Penyesuaian => {}
+        JenisMutasi::RusakProduksi if req.jumlah < 0.0 => {}
+        JenisMutasi::RusakProduksi => {
+            return Err("Jumlah barang rusak harus negatif (mengurangi stok)".to_string());
+        }
+        other => {
+            return Err(format!("Mutasi {} tidak bisa dicatat manual", other.as_str()));
+        }
+    }
+    if req.jumlah == 0.0 {
+        return Err("Jumlah mutasi tidak boleh 0".to_string());
+    }
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().to_rfc3339();
+    let catatan = req.catatan.as_deref().map(str::trim).filter(|c| !c.is_empty());
+    let referensi = match (&req.referensi_tabel, &req.referensi_id) {
+        (Some(table), Some(id)) => Some((table.as_str(), id.as_str())),
+        _ => None,
+    };
+
+    let row = apply(
+        &tx,
+        &Mutasi {
+            barang_id: &req.barang_id,
+            jenis: req.jenis,
+            jumlah: req.jumlah,
+            referensi,
+            catatan,
+            dibuat_oleh: req.dibuat_oleh.as_deref(),
+        },
+        &now,
+    )?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(row)
+}
+
+/// Movements of one barang, newest first
+pub fn history(conn: &Connection, barang_id: &str, limit: Option<i64>) -> Result<Vec<MutasiStok>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, barang_id, tanggal, jenis, jumlah, stok_sebelum, stok_sesudah, satuan,
+                    referensi_tabel, referensi_id, catatan, dibuat_oleh
+             FROM mutasi_stok
+             WHERE barang_id = ?1
+             ORDER BY tanggal DESC, rowid DESC
+             LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map(params![barang_id, limit.unwrap_or(-1)], |row| {
+            Ok(MutasiStok {
+                id: row.get(0)?,
+                barang_id: row.get(1)?,
+                tanggal: row.get(2)?,
+                jenis: row.get(3)?,
+                jumlah: row.get(4)?,
+                stok_sebelum: row.get(5)?,
+                stok_sesudah: row.get(6)?,
+                satuan: row.get(7)?,
+                referensi_tabel: row.get(8)?,
+                referensi_id: row.get(9)?,
+                catatan: row.get(10)?,
+                dibuat_oleh: row.get(11)?,
+            })
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(|e| e.to_string())?;
+
+    Ok(rows)
+}
+
+/// Compare `jumlah_stok` with the sum of the ledger for every barang that has
+/// one (or just `barang_id`). With `overwrite`, drifted barang are set to the
+/// ledger sum, undoing edits that bypassed the ledger; otherwise the drift is
+/// only reported.
+pub fn recompute(
+    conn: &mut Connection,
+    barang_id: Option<&str>,
+    overwrite: bool,
+) -> Result<Vec<StockRecompute>, String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let found: Vec<(String, String, f64, f64)> = {
+        let mut stmt = tx
+            .prepare(
+                "SELECT b.id, b.nama, COALESCE(b.jumlah_stok, 0), SUM(m.jumlah)
+                 FROM barang b
+                 JOIN mutasi_stok m ON m.barang_id = b.id
+                 WHERE ?1 IS NULL OR b.id = ?1
+                 GROUP BY b.id
+                 ORDER BY b.nama",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![barang_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(|e| e.to_string())?;
+        rows
+    };
+
+    let mut results = Vec::with_capacity(found.len());
+    for (id, nama, jumlah_stok, jumlah_ledger) in found {
+        // Sums of REAL quantities pick up float noise
+        let drifted = (jumlah_stok - jumlah_ledger).abs() >= 1e-6;
+        let updated = drifted && overwrite;
+        if updated {
+            tx.execute(
+                "UPDATE barang SET jumlah_stok = ?1, diperbarui_pada = ?2 WHERE id = ?3",
+                params![jumlah_ledger, now, id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        results.push(StockRecompute {
+            barang_id: id,
+            nama,
+            jumlah_stok,
+            jumlah_ledger,
+            drifted,
+            updated,
+        });
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_postgrest::test_db;
+
+    fn mutasi(jenis: JenisMutasi, jumlah: f64) -> Mutasi<'static> {
+        Mutasi {
+            barang_id: "b-vinyl",
+            jenis,
+            jumlah,
+            referensi: None,
+            catatan: None,
+            dibuat_oleh: None,
+        }
+    }
+
+    /// (jenis, jumlah, stok_sebelum, stok_sesudah), oldest first
+    fn ledger(conn: &Connection) -> Vec<(String, f64, f64, f64)> {
+        let mut rows: Vec<_> = history(conn, "b-vinyl", None)
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.jenis, m.jumlah, m.stok_sebelum, m.stok_sesudah))
+            .collect();
+        rows.reverse();
+        rows
+    }
+
+    fn jumlah_stok(conn: &Connection) -> f64 {
+        conn.query_row("SELECT jumlah_stok FROM barang WHERE id = 'b-vinyl'", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn existing_and_untracked_stock_enter_the_ledger() {
+        let db = test_db();
+        let mut guard = db.lock().unwrap();
+        let conn = guard.as_mut().unwrap();
+        conn.execute("INSERT INTO barang (id, nama, satuan_dasar, jumlah_stok) VALUES ('b-vinyl', 'Vinyl', 'm2', 10)", [])
+            .unwrap();
+
+        apply(conn, &mutasi(JenisMutasi::Penjualan, -2.0), "2026-01-01T00:00:00Z").unwrap();
+        // Typed into the material form, bypassing the ledger
+        conn.execute("UPDATE barang SET jumlah_stok = 20 WHERE id = 'b-vinyl'", [])
+            .unwrap();
+        apply(conn, &mutasi(JenisMutasi::Pembelian, 5.0), "2026-01-02T00:00:00Z").unwrap();
+
+        assert_eq!(
+            ledger(conn),
+            vec![
+                ("SALDO_AWAL".to_string(), 10.0, 0.0, 10.0),
+                ("PENJUALAN".to_string(), -2.0, 10.0, 8.0),
+                ("PENYESUAIAN".to_string(), 12.0, 8.0, 20.0),
+                ("PEMBELIAN".to_string(), 5.0, 20.0, 25.0),
+            ]
+        );
+        assert_eq!(jumlah_stok(conn), 25.0);
+    }
+
+    #[test]
+    fn drift_is_reported_and_only_overwritten_when_asked() {
+        let db = test_db();
+        let mut guard = db.lock().unwrap();
+        let conn = guard.as_mut().unwrap();
+        conn.execute("INSERT INTO barang (id, nama, satuan_dasar, jumlah_stok) VALUES ('b-vinyl', 'Vinyl', 'm2', 10)", [])
+            .unwrap();
+        apply(conn, &mutasi(JenisMutasi::Penjualan, -2.0), "2026-01-01T00:00:00Z").unwrap();
+        conn.execute("UPDATE barang SET jumlah_stok = 20 WHERE id = 'b-vinyl'", [])
+            .unwrap();
+
+        let report = recompute(conn, None, false).unwrap();
+        assert_eq!(report.len(), 1);
+        assert_eq!((report[0].jumlah_stok, report[0].jumlah_ledger), (20.0, 8.0));
+        assert!(report[0].drifted && !report[0].updated);
+        assert_eq!(jumlah_stok(conn), 20.0);
+
+        let report = recompute(conn, Some("b-vinyl"), true).unwrap();
+        assert!(report[0].drifted && report[0].updated);
+        assert_eq!(jumlah_stok(conn), 8.0);
+
+        let report = recompute(conn, None, false).unwrap();
+        assert!(!report[0].drifted);
+    }
+}
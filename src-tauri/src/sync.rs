@@ -1,20 +1,88 @@
-use rusqlite::Connection;
-use serde_json::Value;
-use std::env;
+use crate::capture;
+use crate::config;
+use crate::conflict;
+use crate::schema::{self, quote_ident, TableDef};
+use crate::settings;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Failed pushes after which an operation is moved to the dead-letter state
+pub const MAX_ATTEMPTS: i64 = 8;
+
+/// Delay before the first retry; doubles with every further failure
+const RETRY_BASE_SECS: i64 = 30;
+
+/// Upper bound for the retry delay
+const RETRY_MAX_SECS: i64 = 60 * 60;
+
+/// Setting holding how many queue entries are pushed per round
+pub const PAGE_SIZE_KEY: &str = "sync.push_page_size";
+pub const DEFAULT_PAGE_SIZE: usize = 200;
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Ids per `id=in.(...)` filter, to keep URLs short
+const IDS_PER_REQUEST: usize = 100;
+
+/// Upper bound for one push or pull request
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
 
 /// Supabase sync configuration
 pub struct SupabaseConfig {
-    pub url: String,
-    pub anon_key: String,
+    /// Base of the REST API: `<project url>/rest/v1`, or a local PostgREST
+    pub rest_url: String,
+    /// Sent as `apikey`: the anon key, or the service key in service mode
+    pub api_key: String,
+    /// Sent as the bearer token: a user JWT, the service key, or the anon key
+    pub access_token: String,
+    pub timeout: Duration,
 }
 
 impl SupabaseConfig {
-    /// Load from environment variables
-    pub fn from_env() -> Option<Self> {
-        let url = env::var("NEXT_PUBLIC_SUPABASE_URL").ok()?;
-        let anon_key = env::var("NEXT_PUBLIC_SUPABASE_ANON_KEY").ok()?;
-        
-        Some(Self { url, anon_key })
+    pub fn new(url: String, api_key: String, access_token: String) -> Self {
+        Self {
+            rest_url: format!("{}/rest/v1", url),
+            api_key,
+            access_token,
+            timeout: REQUEST_TIMEOUT,
+        }
+    }
+    
+    /// Anonymous access with the project's anon key
+    pub fn anon(url: String, anon_key: String) -> Self {
+        Self::new(url, anon_key.clone(), anon_key)
+    }
+    
+    /// Send REST requests to `rest_url` instead of `<project url>/rest/v1`
+    pub fn with_rest_url(mut self, rest_url: Option<String>) -> Self {
+        if let Some(rest_url) = rest_url {
+            self.rest_url = rest_url;
+        }
+        self
+    }
+    
+    /// REST endpoint of a table
+    pub fn table_url(&self, table: &str) -> String {
+        format!("{}/{}", self.rest_url, table)
+    }
+    
+    /// HTTP client with the request timeout applied
+    pub fn client(&self) -> reqwest::Client {
+        reqwest::Client::builder()
+            .timeout(self.timeout)
+            .build()
+            .unwrap_or_default()
+    }
+    
+
+    /// Add the Supabase credential headers to a request
+    pub fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        request
+            .header("apikey", &self.api_key)
+            .header("Authorization", format!("Bearer {}", self.access_token))
     }
 }
 
@@ -26,89 +94,501 @@ pub struct SyncOperation {
     pub operation: String,
     pub record_id: Option<String>,
     pub data: Option<String>,
+    pub created_at: String,
+    /// Remote `sync_version` this change was made on top of
+    pub base_version: Option<i64>,
 }
 
-/// Result of sync operation
+/// Why an operation was not pushed
 #[derive(Debug)]
+pub enum PushError {
+    Failed(String),
+    /// The cloud row changed (or is gone) since the local change was made
+    Conflict { remote: Option<Map<String, Value>> },
+}
+
+impl From<String> for PushError {
+    fn from(message: String) -> Self {
+        PushError::Failed(message)
+    }
+}
+
+impl From<&str> for PushError {
+    fn from(message: &str) -> Self {
+        PushError::Failed(message.to_string())
+    }
+}
+
+/// A sync_queue entry as shown to the user
+#[derive(Debug, Serialize)]
+pub struct QueuedOperation {
+    pub id: String,
+    pub table_name: String,
+    pub operation: String,
+    pub record_id: Option<String>,
+    pub data: Option<Value>,
+    pub created_at: String,
+    pub status: String,
+    pub synced_at: Option<String>,
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    pub last_attempt_at: Option<String>,
+    pub next_attempt_at: Option<String>,
+}
+
+/// Columns read by `queued_operation`, in order
+pub const QUEUED_OPERATION_COLUMNS: &str = "id, table_name, operation, record_id, data, created_at, \
+    status, synced_at, attempts, last_error, last_attempt_at, next_attempt_at";
+
+impl QueuedOperation {
+    /// Map a row selected with `QUEUED_OPERATION_COLUMNS`
+    pub fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        let data: Option<String> = row.get(4)?;
+        Ok(Self {
+            id: row.get(0)?,
+            table_name: row.get(1)?,
+            operation: row.get(2)?,
+            record_id: row.get(3)?,
+            data: data.and_then(|d| serde_json::from_str(&d).ok()),
+            created_at: row.get(5)?,
+            status: row.get::<_, Option<String>>(6)?.unwrap_or_else(|| "pending".to_string()),
+            synced_at: row.get(7)?,
+            attempts: row.get(8)?,
+            last_error: row.get(9)?,
+            last_attempt_at: row.get(10)?,
+            next_attempt_at: row.get(11)?,
+        })
+    }
+}
+
+/// Result of sync operation
+#[derive(Debug, Default, Serialize)]
 pub struct SyncResult {
     pub synced: i32,
     pub failed: i32,
+    pub conflicts: i32,
+}
+
+/// The queue entries of one record folded into the single change that is pushed
+#[derive(Debug)]
+pub struct PendingChange {
+    /// Latest entry, carrying the folded operation and data and the base
+    /// version of the earliest entry
+    pub op: SyncOperation,
+    /// Every sync_queue entry this change stands for
+    pub queue_ids: Vec<String>,
 }
 
+/// Outcome of pushing one change: the cloud's `sync_version` afterwards, when known
+pub type PushOutcome = Result<Option<i64>, PushError>;
+
+/// Cloud rows by id
+type RemoteRows = HashMap<String, Map<String, Value>>;
+
 /// Sync pending operations to Supabase
 /// Returns operations data for async processing
 pub fn get_operations_for_sync(conn: &Connection) -> Result<Vec<SyncOperation>, String> {
-    get_pending_operations(conn)
+    let page_size = settings::get_or(conn, PAGE_SIZE_KEY, DEFAULT_PAGE_SIZE)?.clamp(1, MAX_PAGE_SIZE);
+    get_pending_operations(conn, page_size)
+}
+
+/// Fold queue entries (in queue order) into one change per record.
+/// insert + updates → insert, updates → one update, insert … delete → nothing,
+/// updates + delete → delete.
+pub fn coalesce(operations: Vec<SyncOperation>) -> Vec<PendingChange> {
+    let mut changes: Vec<PendingChange> = Vec::new();
+    let mut by_record: HashMap<(String, String), usize> = HashMap::new();
+    
+    for op in operations {
+        let key = match &op.record_id {
+            Some(record_id) => (op.table_name.clone(), record_id.clone()),
+            None => {
+                changes.push(PendingChange { queue_ids: vec![op.id.clone()], op });
+                continue;
+            }
+        };
+        
+        let index = match by_record.get(&key) {
+            Some(index) => *index,
+            None => {
+                by_record.insert(key, changes.len());
+                changes.push(PendingChange { queue_ids: vec![op.id.clone()], op });
+                continue;
+            }
+        };
+        
+        let change = &mut changes[index];
+        let operation = match (change.op.operation.as_str(), op.operation.as_str()) {
+            // The row never reached the cloud
+            ("insert" | "noop", "delete") => "noop",
+            (_, "delete") => "delete",
+            ("update", _) => "update",
+            _ => "insert",
+        };
+        let base_version = match operation {
+            "update" | "delete" => change.op.base_version,
+            _ => None,
+        };
+        
+        change.queue_ids.push(op.id.clone());
+        change.op = SyncOperation {
+            operation: operation.to_string(),
+            base_version,
+            ..op
+        };
+    }
+    
+    changes
 }
 
-/// Process sync operations (async, no DB connection needed)
+/// Push one page of operations (async, no DB connection needed).
+///
+/// Entries are coalesced per record. Inserts and changes without a base
+/// version go out as one bulk upsert per table, parents before children.
+/// Updates made on top of a known remote version are sent per row as a
+/// `PATCH` filtered on `sync_version=eq.{base}`, and such deletes as one
+/// filtered request per table and base version, children before parents, so
+/// a cloud write that lands meanwhile makes them match nothing instead of
+/// being overwritten. A row they missed is a conflict unless the cloud
+/// already holds exactly the pushed content (an earlier push whose response
+/// was lost). A bulk request that fails is retried row by row so one bad row
+/// cannot hold back the rest.
 pub async fn process_sync_operations(
     operations: Vec<SyncOperation>,
     config: &SupabaseConfig,
-) -> Vec<(String, Result<(), String>)> {
+) -> Vec<(PendingChange, PushOutcome)> {
     if operations.is_empty() {
         return Vec::new();
     }
     
-    let mut results = Vec::new();
-    let client = reqwest::Client::new();
+    let changes = coalesce(operations);
+    let client = config.client();
+    let mut outcomes: Vec<Option<PushOutcome>> = changes.iter().map(|_| None).collect();
     
-    for op in operations {
-        let op_id = op.id.clone();
-        let result = sync_single_operation(&client, config, &op).await;
-        results.push((op_id, result));
+    let mut writes: Vec<usize> = Vec::new();
+    let mut deletes: Vec<usize> = Vec::new();
+    
+    for (i, change) in changes.iter().enumerate() {
+        let op = &change.op;
+        match (op.operation.as_str(), &op.record_id) {
+            ("noop", _) => outcomes[i] = Some(Ok(None)),
+            ("insert", _) => writes.push(i),
+            ("update" | "delete", None) => {
+                outcomes[i] = Some(Err(format!("No record_id for {}", op.operation).into()));
+            }
+            ("update", Some(_)) => writes.push(i),
+            ("delete", Some(_)) => deletes.push(i),
+            (other, _) => {
+                outcomes[i] = Some(Err(format!("Unknown operation: {}", other).into()));
+            }
+        }
     }
     
-    results
+    // Upserts and conditional updates: parents first
+    for (table, indexes) in group_by_table(&changes, &writes, false) {
+        let (updates, upserts): (Vec<usize>, Vec<usize>) = indexes
+            .into_iter()
+            .partition(|i| changes[*i].op.operation == "update" && changes[*i].op.base_version.is_some());
+        
+        let mut rows: Vec<(usize, Value)> = Vec::new();
+        for i in upserts {
+            match changes[i].op.data.as_deref().map(serde_json::from_str::<Value>) {
+                Some(Ok(data)) => rows.push((i, data)),
+                Some(Err(e)) => outcomes[i] = Some(Err(e.to_string().into())),
+                None => outcomes[i] = Some(Err("No data to push".into())),
+            }
+        }
+        
+        // A bulk insert needs the same keys in every object
+        let mut by_shape: HashMap<String, Vec<(usize, Value)>> = HashMap::new();
+        for (i, data) in rows {
+            let shape = data
+                .as_object()
+                .map(|o| o.keys().cloned().collect::<Vec<_>>().join(","))
+                .unwrap_or_default();
+            by_shape.entry(shape).or_default().push((i, data));
+        }
+        
+        for (_, batch) in by_shape {
+            let payload: Vec<&Value> = batch.iter().map(|(_, data)| data).collect();
+            match bulk_upsert(&client, config, table, &payload).await {
+                Ok(versions) => {
+                    for (i, _) in &batch {
+                        let version = changes[*i].op.record_id.as_ref().and_then(|id| versions.get(id)).copied();
+                        outcomes[*i] = Some(Ok(version));
+                    }
+                }
+                Err(e) if batch.len() == 1 => outcomes[batch[0].0] = Some(Err(e.into())),
+                Err(_) => {
+                    for (i, data) in &batch {
+                        let result = bulk_upsert(&client, config, table, &[data]).await;
+                        let version = result.as_ref().ok().and_then(|versions| {
+                            changes[*i].op.record_id.as_ref().and_then(|id| versions.get(id)).copied()
+                        });
+                        outcomes[*i] = Some(result.map(|_| version).map_err(PushError::Failed));
+                    }
+                }
+            }
+        }
+        
+        let mut missed: Vec<usize> = Vec::new();
+        for i in updates {
+            match conditional_update(&client, config, &changes[i].op).await {
+                Ok(Some(version)) => outcomes[i] = Some(Ok(Some(version))),
+                Ok(None) => missed.push(i),
+                Err(e) => outcomes[i] = Some(Err(e.into())),
+            }
+        }
+        resolve_missed(&client, config, table, &changes, &missed, &mut outcomes).await;
+    }
+    
+    // Deletes: children first, conditional ones grouped by base version
+    for (table, indexes) in group_by_table(&changes, &deletes, true) {
+        let mut by_base: BTreeMap<Option<i64>, Vec<usize>> = BTreeMap::new();
+        for i in indexes {
+            by_base.entry(changes[i].op.base_version).or_default().push(i);
+        }
+        
+        for (base, indexes) in by_base {
+            let ids: Vec<&str> = indexes
+                .iter()
+                .filter_map(|i| changes[*i].op.record_id.as_deref())
+                .collect();
+            
+            let mut missed: Vec<usize> = Vec::new();
+            match bulk_delete(&client, config, table, &ids, base).await {
+                Ok(deleted) => {
+                    for i in indexes {
+                        let id = changes[i].op.record_id.as_deref().unwrap_or_default();
+                        if deleted.iter().any(|d| d == id) {
+                            outcomes[i] = Some(Ok(None));
+                        } else {
+                            missed.push(i);
+                        }
+                    }
+                }
+                Err(e) if indexes.len() == 1 => outcomes[indexes[0]] = Some(Err(e.into())),
+                Err(_) => {
+                    for i in indexes {
+                        let id = changes[i].op.record_id.as_deref().unwrap_or_default();
+                        match bulk_delete(&client, config, table, &[id], base).await {
+                            Ok(deleted) if deleted.is_empty() => missed.push(i),
+                            Ok(_) => outcomes[i] = Some(Ok(None)),
+                            Err(e) => outcomes[i] = Some(Err(e.into())),
+                        }
+                    }
+                }
+            }
+            resolve_missed(&client, config, table, &changes, &missed, &mut outcomes).await;
+        }
+    }
+    
+    changes
+        .into_iter()
+        .zip(outcomes)
+        .map(|(change, outcome)| {
+            let outcome = outcome.unwrap_or_else(|| Err("Operation was not pushed".into()));
+            (change, outcome)
+        })
+        .collect()
 }
 
-/// Update sync status in database
+/// Settle conditional writes that matched no cloud row. A delete whose row is
+/// gone, or an update whose row already holds the pushed content, was applied
+/// before; anything else changed on the cloud meanwhile and is a conflict.
+async fn resolve_missed(
+    client: &reqwest::Client,
+    config: &SupabaseConfig,
+    table: &str,
+    changes: &[PendingChange],
+    missed: &[usize],
+    outcomes: &mut [Option<PushOutcome>],
+) {
+    if missed.is_empty() {
+        return;
+    }
+    
+    let ids: Vec<&str> = missed.iter().filter_map(|i| changes[*i].op.record_id.as_deref()).collect();
+    let remote_rows = match fetch_remote_rows(client, config, table, &ids).await {
+        Ok(rows) => rows,
+        Err(e) => {
+            for &i in missed {
+                outcomes[i] = Some(Err(PushError::Failed(e.clone())));
+            }
+            return;
+        }
+    };
+    
+    for &i in missed {
+        let op = &changes[i].op;
+        let remote = op.record_id.as_deref().and_then(|id| remote_rows.get(id));
+        outcomes[i] = Some(match (op.operation.as_str(), remote) {
+            ("delete", None) => Ok(None),
+            ("update", Some(row)) if holds_pushed_data(op, row) => Ok(row_version(row)),
+            _ => Err(PushError::Conflict { remote: remote.cloned() }),
+        });
+    }
+}
+
+/// Group change indexes by table in foreign-key order (parents first, or
+/// children first when `reverse`). Tables unknown to the registry go last.
+fn group_by_table<'a>(
+    changes: &'a [PendingChange],
+    indexes: &[usize],
+    reverse: bool,
+) -> Vec<(&'a str, Vec<usize>)> {
+    let mut order: Vec<&str> = schema::dependency_order().iter().map(|t| t.name).collect();
+    if reverse {
+        order.reverse();
+    }
+    
+    let mut groups: Vec<(&'a str, Vec<usize>)> = Vec::new();
+    for &i in indexes {
+        let table = changes[i].op.table_name.as_str();
+        match groups.iter_mut().find(|(name, _)| *name == table) {
+            Some((_, group)) => group.push(i),
+            None => groups.push((table, vec![i])),
+        }
+    }
+    
+    let rank = |name: &str| order.iter().position(|t| *t == name).unwrap_or(order.len());
+    groups.sort_by_key(|(name, _)| rank(name));
+    groups
+}
+
+/// Push every operation that is due, page after page, until the queue has
+/// nothing left that can make progress. The database lock is only held
+/// between network requests.
+pub async fn push_pending(
+    db: &Mutex<Option<Connection>>,
+    config: &SupabaseConfig,
+) -> Result<SyncResult, String> {
+    let mut total = SyncResult::default();
+    
+    loop {
+        let operations = {
+            let db_guard = db.lock().map_err(|e| e.to_string())?;
+            let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+            get_operations_for_sync(conn)?
+        };
+        
+        if operations.is_empty() {
+            break;
+        }
+        
+        let results = process_sync_operations(operations, config).await;
+        
+        let round = {
+            let db_guard = db.lock().map_err(|e| e.to_string())?;
+            let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+            update_sync_status(conn, results)?
+        };
+        
+        total.synced += round.synced;
+        total.failed += round.failed;
+        total.conflicts += round.conflicts;
+        
+        // Whatever is left is backing off or waiting behind a failed change
+        if round.synced == 0 && round.conflicts == 0 {
+            break;
+        }
+    }
+    
+    Ok(total)
+}
+
+/// Whether Supabase answers at all; any HTTP response counts as online
+pub async fn check_connectivity(config: &SupabaseConfig) -> bool {
+    let client = match reqwest::Client::builder().timeout(Duration::from_secs(5)).build() {
+        Ok(client) => client,
+        Err(_) => return false,
+    };
+    
+    client
+        .get(format!("{}/", config.rest_url))
+        .header("apikey", &config.api_key)
+        .send()
+        .await
+        .is_ok()
+}
+
+/// Update sync status in database. Counts are in queue entries.
 pub fn update_sync_status(
     conn: &Connection,
-    results: Vec<(String, Result<(), String>)>,
+    results: Vec<(PendingChange, PushOutcome)>,
 ) -> Result<SyncResult, String> {
     let mut synced = 0;
     let mut failed = 0;
+    let mut conflicts = 0;
     
-    for (op_id, result) in results {
+    for (change, result) in results {
+        let entries = change.queue_ids.len() as i32;
         match result {
-            Ok(_) => {
-                if mark_as_synced(conn, &op_id).is_ok() {
-                    synced += 1;
+            Ok(remote_version) => {
+                if mark_as_synced(conn, &change, remote_version).is_ok() {
+                    synced += entries;
                 } else {
-                    failed += 1;
+                    failed += entries;
                 }
             }
-            Err(e) => {
-                mark_as_failed(conn, &op_id, &e).ok();
-                failed += 1;
+            Err(PushError::Conflict { remote }) => {
+                if let Err(e) = conflict::handle_conflict(conn, &change.op, remote) {
+                    for id in &change.queue_ids {
+                        mark_as_failed(conn, id, &e).ok();
+                    }
+                    failed += entries;
+                } else {
+                    conflicts += entries;
+                }
+            }
+            Err(PushError::Failed(e)) => {
+                for id in &change.queue_ids {
+                    mark_as_failed(conn, id, &e).ok();
+                }
+                failed += entries;
             }
         }
     }
     
-    Ok(SyncResult { synced, failed })
+    Ok(SyncResult { synced, failed, conflicts })
 }
 
-/// Get pending operations from sync_queue
-fn get_pending_operations(conn: &Connection) -> Result<Vec<SyncOperation>, String> {
+/// Get pending operations from sync_queue whose retry time has come.
+/// An entry waits while an earlier change to the same record is still backing
+/// off or dead-lettered, so records are always pushed in order.
+fn get_pending_operations(conn: &Connection, limit: usize) -> Result<Vec<SyncOperation>, String> {
+    let now = chrono::Utc::now().to_rfc3339();
+    // Entries of tables excluded from sync stay queued until re-enabled
+    let enabled_tables = settings::get(conn, config::ENABLED_TABLES_KEY)?;
     let mut stmt = conn
         .prepare(
-            "SELECT id, table_name, operation, record_id, data 
-             FROM sync_queue 
-             WHERE synced_at IS NULL AND status != 'failed'
-             ORDER BY created_at ASC 
-             LIMIT 50"
+            "SELECT q.id, q.table_name, q.operation, q.record_id, q.data, q.created_at, q.base_version 
+             FROM sync_queue q
+             WHERE q.synced_at IS NULL AND q.status = 'pending'
+               AND (q.next_attempt_at IS NULL OR q.next_attempt_at <= ?1)
+               AND (?3 IS NULL OR q.table_name IN (SELECT value FROM json_each(?3)))
+               AND NOT EXISTS (
+                   SELECT 1 FROM sync_queue e
+                   WHERE e.table_name = q.table_name AND e.record_id = q.record_id
+                     AND e.synced_at IS NULL
+                     AND (e.status = 'dead_letter' OR (e.status = 'pending' AND e.next_attempt_at > ?1))
+                     AND (e.created_at < q.created_at OR (e.created_at = q.created_at AND e.rowid < q.rowid))
+               )
+             ORDER BY q.created_at ASC, q.rowid ASC 
+             LIMIT ?2"
         )
         .map_err(|e| e.to_string())?;
     
     let operations = stmt
-        .query_map([], |row| {
+        .query_map(params![now, limit as i64, enabled_tables], |row| {
             Ok(SyncOperation {
                 id: row.get(0)?,
                 table_name: row.get(1)?,
                 operation: row.get(2)?,
                 record_id: row.get(3)?,
                 data: row.get(4)?,
+                created_at: row.get(5)?,
+                base_version: row.get(6)?,
             })
         })
         .map_err(|e| e.to_string())?
@@ -118,103 +598,463 @@ fn get_pending_operations(conn: &Connection) -> Result<Vec<SyncOperation>, Strin
     Ok(operations)
 }
 
-/// Sync single operation to Supabase
-async fn sync_single_operation(
+/// Insert or update rows in one request (`resolution=merge-duplicates`).
+/// Returns the cloud's resulting `sync_version` per id for synced tables.
+async fn bulk_upsert(
     client: &reqwest::Client,
     config: &SupabaseConfig,
-    op: &SyncOperation,
-) -> Result<(), String> {
-    let url = format!("{}/rest/v1/{}", config.url, op.table_name);
+    table: &str,
+    rows: &[&Value],
+) -> Result<HashMap<String, i64>, String> {
+    let url = config.table_url(table);
+    let versioned = schema::table(table).map(|t| t.is_synced()).unwrap_or(false);
     
-    match op.operation.as_str() {
-        "insert" => {
-            let data: Value = serde_json::from_str(op.data.as_ref().ok_or("No data for insert")?)
-                .map_err(|e| e.to_string())?;
-            
-            let response = client
-                .post(&url)
-                .header("apikey", &config.anon_key)
-                .header("Authorization", format!("Bearer {}", config.anon_key))
-                .header("Content-Type", "application/json")
-                .header("Prefer", "return=minimal")
-                .json(&data)
-                .send()
-                .await
-                .map_err(|e| e.to_string())?;
-            
-            if !response.status().is_success() {
-                let error_text = response.text().await.unwrap_or_default();
-                return Err(format!("Insert failed: {}", error_text));
-            }
+    let mut request = config
+        .authorize(client.post(&url))
+        .header("Content-Type", "application/json");
+    request = if versioned {
+        request
+            .query(&[("select", "id,sync_version")])
+            .header("Prefer", "resolution=merge-duplicates,return=representation")
+    } else {
+        request.header("Prefer", "resolution=merge-duplicates,return=minimal")
+    };
+    
+    let response = request.json(rows).send().await.map_err(|e| e.to_string())?;
+    
+    if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Upsert into {} failed: {}", table, error_text));
+    }
+    
+    if !versioned {
+        return Ok(HashMap::new());
+    }
+    
+    let returned: Vec<Value> = response.json().await.map_err(|e| e.to_string())?;
+    Ok(returned
+        .iter()
+        .filter_map(|row| {
+            let id = row.get("id")?.as_str()?.to_string();
+            Some((id, row.get("sync_version")?.as_i64()?))
+        })
+        .collect())
+}
+
+/// Delete rows by id in one request per chunk. With a base version only rows
+/// still at that version are deleted. Returns the ids that were deleted.
+async fn bulk_delete(
+    client: &reqwest::Client,
+    config: &SupabaseConfig,
+    table: &str,
+    ids: &[&str],
+    base_version: Option<i64>,
+) -> Result<Vec<String>, String> {
+    let url = config.table_url(table);
+    let mut deleted = Vec::new();
+    
+    for chunk in ids.chunks(IDS_PER_REQUEST) {
+        let mut request = config
+            .authorize(client.delete(&url))
+            .query(&[("id", in_filter(chunk))]);
+        request = match base_version {
+            Some(base) => request
+                .query(&[("sync_version", format!("eq.{}", base)), ("select", "id".to_string())])
+                .header("Prefer", "return=representation"),
+            None => request.header("Prefer", "return=minimal"),
+        };
+        
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("Delete from {} failed: {}", table, error_text));
         }
         
-        "update" => {
-            let record_id = op.record_id.as_ref().ok_or("No record_id for update")?;
-            let data: Value = serde_json::from_str(op.data.as_ref().ok_or("No data for update")?)
-                .map_err(|e| e.to_string())?;
-            
-            let update_url = format!("{}?id=eq.{}", url, record_id);
-            
-            let response = client
-                .patch(&update_url)
-                .header("apikey", &config.anon_key)
-                .header("Authorization", format!("Bearer {}", config.anon_key))
-                .header("Content-Type", "application/json")
-                .header("Prefer", "return=minimal")
-                .json(&data)
-                .send()
-                .await
-                .map_err(|e| e.to_string())?;
-            
-            if !response.status().is_success() {
-                let error_text = response.text().await.unwrap_or_default();
-                return Err(format!("Update failed: {}", error_text));
-            }
+        if base_version.is_none() {
+            deleted.extend(chunk.iter().map(|id| id.to_string()));
+            continue;
         }
+        let returned: Vec<Value> = response.json().await.map_err(|e| e.to_string())?;
+        deleted.extend(
+            returned
+                .iter()
+                .filter_map(|row| row.get("id").and_then(|v| v.as_str()).map(str::to_string)),
+        );
+    }
+    
+    Ok(deleted)
+}
+
+/// Update one row only if the cloud still holds it at the change's base
+/// version. Returns the cloud's new `sync_version`, or None when no row matched.
+async fn conditional_update(
+    client: &reqwest::Client,
+    config: &SupabaseConfig,
+    op: &SyncOperation,
+) -> Result<Option<i64>, String> {
+    let record_id = op.record_id.as_deref().ok_or("No record_id for update")?;
+    let base = op.base_version.ok_or("No base version for update")?;
+    let data: Value = serde_json::from_str(op.data.as_deref().ok_or("No data to push")?)
+        .map_err(|e| e.to_string())?;
+    
+    let response = config
+        .authorize(client.patch(config.table_url(&op.table_name)))
+        .query(&[
+            ("id", format!("eq.{}", record_id)),
+            ("sync_version", format!("eq.{}", base)),
+            ("select", "id,sync_version".to_string()),
+        ])
+        .header("Content-Type", "application/json")
+        .header("Prefer", "return=representation")
+        .json(&data)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    
+    if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Update of {} failed: {}", op.table_name, error_text));
+    }
+    
+    let updated: Vec<Value> = response.json().await.map_err(|e| e.to_string())?;
+    match updated.first() {
+        Some(row) => Ok(Some(row.get("sync_version").and_then(|v| v.as_i64()).unwrap_or(base + 1))),
+        None => Ok(None),
+    }
+}
+
+/// Current cloud copies of the given rows, by id (missing ids are not returned)
+async fn fetch_remote_rows(
+    client: &reqwest::Client,
+    config: &SupabaseConfig,
+    table: &str,
+    ids: &[&str],
+) -> Result<RemoteRows, String> {
+    let url = config.table_url(table);
+    let mut rows = HashMap::new();
+    
+    for chunk in ids.chunks(IDS_PER_REQUEST) {
+        let response = config
+            .authorize(client.get(&url))
+            .query(&[("select", "*".to_string()), ("id", in_filter(chunk))])
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
         
-        "delete" => {
-            let record_id = op.record_id.as_ref().ok_or("No record_id for delete")?;
-            let delete_url = format!("{}?id=eq.{}", url, record_id);
-            
-            let response = client
-                .delete(&delete_url)
-                .header("apikey", &config.anon_key)
-                .header("Authorization", format!("Bearer {}", config.anon_key))
-                .header("Prefer", "return=minimal")
-                .send()
-                .await
-                .map_err(|e| e.to_string())?;
-            
-            if !response.status().is_success() {
-                let error_text = response.text().await.unwrap_or_default();
-                return Err(format!("Delete failed: {}", error_text));
-            }
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("Fetching remote rows from {} failed: {}", table, error_text));
         }
         
-        _ => return Err(format!("Unknown operation: {}", op.operation)),
+        let page: Vec<Value> = response.json().await.map_err(|e| e.to_string())?;
+        for row in page {
+            if let Value::Object(map) = row {
+                if let Some(id) = map.get("id").and_then(|v| v.as_str()) {
+                    rows.insert(id.to_string(), map.clone());
+                }
+            }
+        }
     }
     
-    Ok(())
+    Ok(rows)
+}
+
+/// PostgREST `in.(...)` filter with quoted values
+fn in_filter(ids: &[&str]) -> String {
+    let quoted: Vec<String> = ids.iter().map(|id| format!("\"{}\"", id.replace('"', "\\\""))).collect();
+    format!("in.({})", quoted.join(","))
+}
+
+fn row_version(row: &Map<String, Value>) -> Option<i64> {
+    row.get("sync_version").and_then(|v| v.as_i64())
+}
+
+/// `sync_version` inside the snapshot that is being pushed
+fn data_version(op: &SyncOperation) -> Option<i64> {
+    let data: Value = serde_json::from_str(op.data.as_deref()?).ok()?;
+    data.get("sync_version").and_then(|v| v.as_i64())
+}
+
+/// Whether the cloud row holds every value of the pushed snapshot. Versions
+/// cannot tell our own lost push from another PC's single edit, as both
+/// leave the row at base + 1; the content can.
+fn holds_pushed_data(op: &SyncOperation, remote: &Map<String, Value>) -> bool {
+    let Some(Ok(Value::Object(data))) = op.data.as_deref().map(serde_json::from_str::<Value>) else {
+        return false;
+    };
+    
+    data.iter()
+        .filter(|(column, _)| !matches!(column.as_str(), "sync_version" | "sync_status" | "last_synced_at"))
+        .all(|(column, value)| match (value, remote.get(column)) {
+            (Value::Number(a), Some(Value::Number(b))) => match (a.as_f64(), b.as_f64()) {
+                (Some(a), Some(b)) => (a - b).abs() < 1e-9,
+                _ => a == b,
+            },
+            (value, Some(remote)) => value == remote,
+            (Value::Null, None) => true,
+            _ => false,
+        })
+}
+
+/// Mark a pushed change's entries as synced, and the row itself once nothing
+/// else is queued for it.
+///
+/// The cloud bumps `sync_version` on every update, so after collapsing several
+/// local updates into one push it may sit below the local version. The local
+/// row and any entries queued on top of the pushed version adopt the cloud's
+/// version, keeping the next conflict check aligned.
+fn mark_as_synced(
+    conn: &Connection,
+    change: &PendingChange,
+    remote_version: Option<i64>,
+) -> Result<(), String> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    
+    for id in &change.queue_ids {
+        tx.execute(
+            "UPDATE sync_queue SET synced_at = ?1, status = 'synced' WHERE id = ?2",
+            params![now, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    
+    if let Some(record_id) = &change.op.record_id {
+        if let (Some(remote), Some(pushed)) = (remote_version, data_version(&change.op)) {
+            tx.execute(
+                "UPDATE sync_queue SET base_version = ?1
+                 WHERE table_name = ?2 AND record_id = ?3 AND status = 'pending'
+                   AND synced_at IS NULL AND base_version = ?4",
+                params![remote, change.op.table_name, record_id, pushed],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        release_row(&tx, &change.op.table_name, record_id, Some(&now), remote_version)?;
+    }
+    
+    tx.commit().map_err(|e| e.to_string())
 }
 
-/// Mark operation as synced
-fn mark_as_synced(conn: &Connection, op_id: &str) -> Result<(), String> {
+/// Mark a pending local row as synced once nothing is left queued for it
+fn release_row(
+    conn: &Connection,
+    table_name: &str,
+    record_id: &str,
+    synced_at: Option<&str>,
+    sync_version: Option<i64>,
+) -> Result<(), String> {
+    let table = match schema::table(table_name) {
+        Ok(table) if table.is_synced() => table,
+        _ => return Ok(()),
+    };
+    
+    let still_queued: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM sync_queue
+             WHERE table_name = ?1 AND record_id = ?2 AND synced_at IS NULL
+               AND status IN ('pending', 'dead_letter')",
+            params![table_name, record_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    
+    if still_queued > 0 {
+        return Ok(());
+    }
+    
+    capture::pause(conn).map_err(|e| e.to_string())?;
     conn.execute(
-        "UPDATE sync_queue SET synced_at = ?1, status = 'synced' WHERE id = ?2",
-        rusqlite::params![chrono::Utc::now().to_rfc3339(), op_id],
+        &format!(
+            "UPDATE {} SET sync_status = 'synced', last_synced_at = COALESCE(?1, last_synced_at),
+                 sync_version = COALESCE(?2, sync_version)
+             WHERE id = ?3 AND sync_status = 'pending'",
+            quote_ident(table.name)
+        ),
+        params![synced_at, sync_version, record_id],
     )
     .map_err(|e| e.to_string())?;
+    capture::resume(conn).map_err(|e| e.to_string())
+}
+
+/// Record a failed push and schedule the next attempt, or move the entry to
+/// the dead-letter state once it has failed `MAX_ATTEMPTS` times
+fn mark_as_failed(conn: &Connection, op_id: &str, error: &str) -> Result<(), String> {
+    let attempts: i64 = conn
+        .query_row(
+            "SELECT attempts FROM sync_queue WHERE id = ?1",
+            params![op_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let attempts = attempts + 1;
+    let now = chrono::Utc::now();
+    
+    if attempts >= MAX_ATTEMPTS {
+        conn.execute(
+            "UPDATE sync_queue
+             SET status = 'dead_letter', attempts = ?1, last_error = ?2, last_attempt_at = ?3,
+                 next_attempt_at = NULL
+             WHERE id = ?4",
+            params![attempts, error, now.to_rfc3339(), op_id],
+        )
+    } else {
+        let next_attempt_at = now + chrono::Duration::seconds(retry_delay_secs(attempts));
+        conn.execute(
+            "UPDATE sync_queue
+             SET status = 'pending', attempts = ?1, last_error = ?2, last_attempt_at = ?3,
+                 next_attempt_at = ?4
+             WHERE id = ?5",
+            params![attempts, error, now.to_rfc3339(), next_attempt_at.to_rfc3339(), op_id],
+        )
+    }
+    .map_err(|e| e.to_string())?;
     
     Ok(())
 }
 
-/// Mark operation as failed
-fn mark_as_failed(conn: &Connection, op_id: &str, error: &str) -> Result<(), String> {
+/// Exponential backoff with jitter: a random delay between half and all of
+/// `RETRY_BASE_SECS * 2^(attempts - 1)`, capped at `RETRY_MAX_SECS`
+fn retry_delay_secs(attempts: i64) -> i64 {
+    let exponent = (attempts - 1).clamp(0, 30) as u32;
+    let delay = RETRY_BASE_SECS.saturating_mul(1 << exponent).min(RETRY_MAX_SECS);
+    let half = delay / 2;
+    let random = (uuid::Uuid::new_v4().as_u128() % (half as u128 + 1)) as i64;
+    half + random
+}
+
+/// Operations that exhausted their retries, oldest first
+pub fn list_dead_letter(conn: &Connection) -> Result<Vec<QueuedOperation>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM sync_queue
+             WHERE status = 'dead_letter'
+             ORDER BY created_at ASC, rowid ASC",
+            QUEUED_OPERATION_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    
+    let operations = stmt
+        .query_map([], QueuedOperation::from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(operations)
+}
+
+/// Put dead-lettered operations (all of them when `ids` is None) back in the
+/// queue with a fresh retry budget. Returns how many were requeued.
+pub fn retry_dead_letter(conn: &Connection, ids: Option<&[String]>) -> Result<usize, String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let mut count = 0;
+    
+    for id in dead_letter_ids(&tx, ids)? {
+        count += tx
+            .execute(
+                "UPDATE sync_queue SET status = 'pending', attempts = 0, next_attempt_at = NULL
+                 WHERE id = ?1",
+                params![id],
+            )
+            .map_err(|e| e.to_string())?;
+    }
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(count)
+}
+
+/// Give up on dead-lettered operations (all of them when `ids` is None).
+/// The local change stays in SQLite but is no longer pushed, and the record is
+/// released so the next pull may overwrite it. Returns how many were discarded.
+pub fn discard_dead_letter(conn: &Connection, ids: Option<&[String]>) -> Result<usize, String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let mut count = 0;
+    
+    for id in dead_letter_ids(&tx, ids)? {
+        let (table_name, record_id): (String, Option<String>) = tx
+            .query_row(
+                "UPDATE sync_queue SET status = 'discarded', next_attempt_at = NULL
+                 WHERE id = ?1 RETURNING table_name, record_id",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| e.to_string())?;
+        count += 1;
+        
+        if let Some(record_id) = record_id {
+            release_row(&tx, &table_name, &record_id, None, None)?;
+        }
+    }
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(count)
+}
+
+fn dead_letter_ids(conn: &Connection, ids: Option<&[String]>) -> Result<Vec<String>, String> {
+    let all: Vec<String> = conn
+        .prepare("SELECT id FROM sync_queue WHERE status = 'dead_letter'")
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()
+        })
+        .map_err(|e| e.to_string())?;
+    
+    Ok(match ids {
+        Some(ids) => all.into_iter().filter(|id| ids.contains(id)).collect(),
+        None => all,
+    })
+}
+
+/// Current local row as JSON, limited to the columns the registry knows
+pub fn read_row(
+    conn: &Connection,
+    table: &TableDef,
+    record_id: &str,
+) -> Result<Option<Map<String, Value>>, String> {
+    let columns: Vec<String> = table.columns.iter().map(|c| quote_ident(c.name)).collect();
+    let sql = format!(
+        "SELECT {} FROM {} WHERE id = ?1",
+        columns.join(", "),
+        quote_ident(table.name)
+    );
+    
+    conn.query_row(&sql, params![record_id], |row| {
+        let mut map = Map::new();
+        for (i, column) in table.columns.iter().enumerate() {
+            map.insert(column.name.to_string(), crate::row_value_to_json(row, i)?);
+        }
+        Ok(map)
+    })
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+/// Append an entry to sync_queue directly (the capture triggers cover normal writes)
+pub fn enqueue(
+    conn: &Connection,
+    table: &str,
+    operation: &str,
+    record_id: &str,
+    data: Option<&Map<String, Value>>,
+    base_version: Option<i64>,
+) -> Result<(), String> {
     conn.execute(
-        "UPDATE sync_queue SET status = 'failed', synced_at = ?1 WHERE id = ?2",
-        rusqlite::params![error, op_id],
+        "INSERT INTO sync_queue (id, table_name, operation, record_id, data, created_at, base_version)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            uuid::Uuid::new_v4().to_string(),
+            table,
+            operation,
+            record_id,
+            data.map(|d| Value::Object(d.clone()).to_string()),
+            chrono::Utc::now().to_rfc3339(),
+            base_version,
+        ],
     )
     .map_err(|e| e.to_string())?;
     
     Ok(())
 }
+
+#[cfg(test)]
+mod tests;
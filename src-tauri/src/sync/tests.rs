@@ -0,0 +1,268 @@
+use super::*;
+use crate::mock_postgrest::{test_db, MockPostgrest};
+use crate::pull;
+
+const BANNER: &str = "8a1f4a52-0c3e-4b7e-9d55-3f0f4b1c2a01";
+const STIKER: &str = "8a1f4a52-0c3e-4b7e-9d55-3f0f4b1c2a02";
+const FLEXI: &str = "5b0c9e7d-7f3a-4d8e-a1b2-6c4d8e9f0b01";
+
+fn exec(db: &Mutex<Option<Connection>>, sql: &str) {
+    let guard = db.lock().unwrap();
+    guard.as_ref().unwrap().execute_batch(sql).unwrap();
+}
+
+fn query<T: rusqlite::types::FromSql>(db: &Mutex<Option<Connection>>, sql: &str) -> T {
+    let guard = db.lock().unwrap();
+    guard.as_ref().unwrap().query_row(sql, [], |row| row.get(0)).unwrap()
+}
+
+/// (status, attempts, last_error) of the only queue entry of a record
+fn queue_entry(db: &Mutex<Option<Connection>>, record_id: &str) -> (String, i64, Option<String>) {
+    let guard = db.lock().unwrap();
+    guard
+        .as_ref()
+        .unwrap()
+        .query_row(
+            "SELECT status, attempts, last_error FROM sync_queue WHERE record_id = ?1",
+            [record_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap()
+}
+
+/// A local database holding the fixture rows, as after a pull
+async fn pulled_db(mock: &MockPostgrest) -> Mutex<Option<Connection>> {
+    let db = test_db();
+    let report = pull::pull_all(&db, &mock.config()).await;
+    assert_eq!(report.failed_tables, 0, "{:?}", report.tables);
+    db
+}
+
+#[tokio::test]
+async fn pull_applies_fixture_rows_without_queueing_them() {
+    let mock = MockPostgrest::with_fixture("catalog").await;
+    let db = pulled_db(&mock).await;
+
+    assert_eq!(query::<i64>(&db, "SELECT COUNT(*) FROM kategori_barang"), 2);
+    assert_eq!(query::<String>(&db, &format!("SELECT nama FROM barang WHERE id = '{}'", FLEXI)), "Flexi 280gr");
+    assert_eq!(
+        query::<i64>(&db, &format!("SELECT sync_version FROM kategori_barang WHERE id = '{}'", STIKER)),
+        3
+    );
+    assert_eq!(query::<i64>(&db, "SELECT COUNT(*) FROM sync_queue"), 0);
+}
+
+#[tokio::test]
+async fn insert_is_pushed_and_the_local_row_marked_synced() {
+    let mock = MockPostgrest::with_fixture("catalog").await;
+    let db = test_db();
+    exec(&db, "INSERT INTO kategori_barang (id, nama) VALUES ('k-new', 'Kartu Nama')");
+
+    let result = push_pending(&db, &mock.config()).await.unwrap();
+
+    assert_eq!((result.synced, result.failed, result.conflicts), (1, 0, 0));
+    assert_eq!(mock.row("kategori_barang", "k-new").unwrap()["nama"], "Kartu Nama");
+    let post = mock.requests().into_iter().find(|r| r.method == "POST").unwrap();
+    assert!(post.query.contains(&("select".to_string(), "id,sync_version".to_string())));
+    assert!(post.body.contains("Kartu Nama"));
+    assert_eq!(queue_entry(&db, "k-new").0, "synced");
+    assert_eq!(
+        query::<String>(&db, "SELECT sync_status FROM kategori_barang WHERE id = 'k-new'"),
+        "synced"
+    );
+}
+
+#[tokio::test]
+async fn update_is_pushed_on_top_of_the_pulled_version() {
+    let mock = MockPostgrest::with_fixture("catalog").await;
+    let db = pulled_db(&mock).await;
+    exec(&db, &format!("UPDATE kategori_barang SET nama = 'Spanduk' WHERE id = '{}'", BANNER));
+
+    let result = push_pending(&db, &mock.config()).await.unwrap();
+
+    assert_eq!((result.synced, result.failed, result.conflicts), (1, 0, 0));
+    let remote = mock.row("kategori_barang", BANNER).unwrap();
+    assert_eq!(remote["nama"], "Spanduk");
+    assert_eq!(remote["sync_version"], 2);
+    // The local row adopts the version the cloud assigned
+    assert_eq!(
+        query::<i64>(&db, &format!("SELECT sync_version FROM kategori_barang WHERE id = '{}'", BANNER)),
+        2
+    );
+}
+
+#[tokio::test]
+async fn delete_removes_the_cloud_row() {
+    let mock = MockPostgrest::with_fixture("catalog").await;
+    let db = pulled_db(&mock).await;
+    exec(&db, &format!("DELETE FROM kategori_barang WHERE id = '{}'", STIKER));
+
+    let result = push_pending(&db, &mock.config()).await.unwrap();
+
+    assert_eq!((result.synced, result.failed), (1, 0));
+    assert!(mock.row("kategori_barang", STIKER).is_none());
+    assert!(mock.requests().iter().any(|r| r.method == "DELETE" && r.table == "kategori_barang"));
+}
+
+#[tokio::test]
+async fn edit_made_on_the_cloud_meanwhile_is_a_conflict() {
+    let mock = MockPostgrest::with_fixture("catalog").await;
+    let db = pulled_db(&mock).await;
+    exec(&db, &format!("UPDATE kategori_barang SET nama = 'Spanduk' WHERE id = '{}'", BANNER));
+
+    // Another PC saves the same row twice meanwhile (version 1 -> 3)
+    let other = SupabaseConfig::anon(mock.url(), "other".to_string()).with_rest_url(Some(mock.url()));
+    for nama in ["Banner Indoor", "Banner Outdoor"] {
+        let edit = serde_json::json!({ "id": BANNER, "nama": nama });
+        bulk_upsert(&other.client(), &other, "kategori_barang", &[&edit]).await.unwrap();
+    }
+
+    let result = push_pending(&db, &mock.config()).await.unwrap();
+
+    assert_eq!(result.conflicts, 1);
+    assert_eq!(query::<i64>(&db, "SELECT COUNT(*) FROM sync_conflicts"), 1);
+}
+
+#[tokio::test]
+async fn single_edit_on_each_side_is_a_conflict() {
+    let mock = MockPostgrest::with_fixture("catalog").await;
+    let db = pulled_db(&mock).await;
+    // Keep the conflict open so neither side is written back
+    exec(&db, "INSERT INTO sync_conflict_policy (table_name, policy) VALUES ('kategori_barang', 'manual')");
+    exec(&db, &format!("UPDATE kategori_barang SET nama = 'Spanduk' WHERE id = '{}'", BANNER));
+
+    // Another PC saves the row once (version 1 -> 2), the version our edit also carries
+    let other = SupabaseConfig::anon(mock.url(), "other".to_string()).with_rest_url(Some(mock.url()));
+    let edit = serde_json::json!({ "id": BANNER, "nama": "Banner Indoor" });
+    bulk_upsert(&other.client(), &other, "kategori_barang", &[&edit]).await.unwrap();
+
+    let result = push_pending(&db, &mock.config()).await.unwrap();
+
+    assert_eq!((result.synced, result.conflicts), (0, 1));
+    assert_eq!(query::<i64>(&db, "SELECT COUNT(*) FROM sync_conflicts"), 1);
+    assert_eq!(mock.row("kategori_barang", BANNER).unwrap()["nama"], "Banner Indoor");
+}
+
+#[tokio::test]
+async fn edit_landing_just_before_the_write_is_not_overwritten() {
+    let mock = MockPostgrest::with_fixture("catalog").await;
+    let db = pulled_db(&mock).await;
+    exec(&db, "INSERT INTO sync_conflict_policy (table_name, policy) VALUES ('kategori_barang', 'manual')");
+    exec(&db, &format!("UPDATE kategori_barang SET nama = 'Spanduk' WHERE id = '{}'", BANNER));
+    mock.edit_before_next("PATCH", "kategori_barang", BANNER, serde_json::json!({ "nama": "Banner Outdoor" }));
+
+    let result = push_pending(&db, &mock.config()).await.unwrap();
+
+    assert_eq!((result.synced, result.conflicts), (0, 1));
+    let remote = mock.row("kategori_barang", BANNER).unwrap();
+    assert_eq!((remote["nama"].as_str(), remote["sync_version"].as_i64()), (Some("Banner Outdoor"), Some(2)));
+    let patch = mock.requests().into_iter().find(|r| r.method == "PATCH").unwrap();
+    assert!(patch.query.contains(&("sync_version".to_string(), "eq.1".to_string())));
+}
+
+#[tokio::test]
+async fn update_already_on_the_cloud_counts_as_synced() {
+    let mock = MockPostgrest::with_fixture("catalog").await;
+    let db = pulled_db(&mock).await;
+    exec(&db, &format!("UPDATE kategori_barang SET nama = 'Spanduk' WHERE id = '{}'", BANNER));
+
+    // An earlier push applied this exact change but its response was lost
+    let pushed: String = query(&db, &format!("SELECT data FROM sync_queue WHERE record_id = '{}'", BANNER));
+    let pushed: serde_json::Value = serde_json::from_str(&pushed).unwrap();
+    let config = mock.config();
+    bulk_upsert(&config.client(), &config, "kategori_barang", &[&pushed]).await.unwrap();
+
+    let result = push_pending(&db, &mock.config()).await.unwrap();
+
+    assert_eq!((result.synced, result.conflicts), (1, 0));
+    assert_eq!(query::<i64>(&db, "SELECT COUNT(*) FROM sync_conflicts"), 0);
+    assert_eq!(queue_entry(&db, BANNER).0, "synced");
+}
+
+#[tokio::test]
+async fn server_error_backs_off_and_keeps_the_entry() {
+    let mock = MockPostgrest::with_fixture("catalog").await;
+    let db = test_db();
+    exec(&db, "INSERT INTO kategori_barang (id, nama) VALUES ('k-new', 'Kartu Nama')");
+    // The bulk request and its row-by-row retry
+    mock.fail_next("POST", 503, r#"{"message":"upstream unavailable"}"#, 2);
+
+    let result = push_pending(&db, &mock.config()).await.unwrap();
+
+    assert_eq!((result.synced, result.failed), (0, 1));
+    let (status, attempts, last_error) = queue_entry(&db, "k-new");
+    assert_eq!((status.as_str(), attempts), ("pending", 1));
+    assert!(last_error.unwrap().contains("upstream unavailable"));
+    assert!(mock.row("kategori_barang", "k-new").is_none());
+
+    // Backing off: an immediate second push does not retry it
+    let again = push_pending(&db, &mock.config()).await.unwrap();
+    assert_eq!((again.synced, again.failed), (0, 0));
+}
+
+#[tokio::test]
+async fn rejected_row_does_not_hold_back_the_rest_of_the_batch() {
+    let mock = MockPostgrest::with_fixture("catalog").await;
+    let db = pulled_db(&mock).await;
+    // A category that exists locally only (not queued), so the cloud lacks the parent
+    exec(
+        &db,
+        "INSERT INTO sync_capture_pause (id) VALUES (1);
+         INSERT INTO kategori_barang (id, nama, sync_status) VALUES ('k-local', 'Lokal', 'synced');
+         DELETE FROM sync_capture_pause;",
+    );
+    exec(
+        &db,
+        &format!(
+            "INSERT INTO barang (id, nama, satuan_dasar, kategori_id) VALUES ('b-orphan', 'Albatros', 'm2', 'k-local');
+             INSERT INTO barang (id, nama, satuan_dasar, kategori_id) VALUES ('b-ok', 'Vinyl', 'm2', '{}');",
+            BANNER
+        ),
+    );
+
+    let result = push_pending(&db, &mock.config()).await.unwrap();
+
+    assert_eq!((result.synced, result.failed), (1, 1));
+    assert!(mock.row("barang", "b-ok").is_some());
+    let (_, attempts, last_error) = queue_entry(&db, "b-orphan");
+    assert_eq!(attempts, 1);
+    assert!(last_error.unwrap().contains("foreign key"));
+}
+
+#[tokio::test]
+async fn timeout_counts_as_a_failed_attempt() {
+    let mock = MockPostgrest::with_fixture("catalog").await;
+    let db = test_db();
+    exec(&db, "INSERT INTO kategori_barang (id, nama) VALUES ('k-slow', 'Lambat')");
+    mock.set_delay(Duration::from_secs(2));
+    let mut config = mock.config();
+    config.timeout = Duration::from_millis(200);
+
+    let result = push_pending(&db, &config).await.unwrap();
+
+    assert_eq!((result.synced, result.failed), (0, 1));
+    let (status, attempts, _) = queue_entry(&db, "k-slow");
+    assert_eq!((status.as_str(), attempts), ("pending", 1));
+}
+
+#[tokio::test]
+async fn malformed_url_fails_the_attempt_instead_of_panicking() {
+    let db = test_db();
+    exec(&db, "INSERT INTO kategori_barang (id, nama) VALUES ('k-new', 'Kartu Nama')");
+    let config = SupabaseConfig::anon("not a url".to_string(), "key".to_string());
+
+    assert!(!check_connectivity(&config).await);
+    let result = push_pending(&db, &config).await.unwrap();
+
+    assert_eq!((result.synced, result.failed), (0, 1));
+    assert_eq!(queue_entry(&db, "k-new").1, 1);
+}
+
+#[tokio::test]
+async fn connectivity_check_reaches_a_local_postgrest() {
+    let mock = MockPostgrest::start().await;
+    assert!(check_connectivity(&mock.config()).await);
+
+    let unreachable = SupabaseConfig::anon("http://127.0.0.1:9".to_string(), "key".to_string());
+    assert!(!check_connectivity(&unreachable).await);
+}
@@ -415,6 +415,80 @@ CREATE TABLE IF NOT EXISTS pelunasan_hutang (
 
 CREATE INDEX IF NOT EXISTS idx_pelunasan_hutang_sync_status ON pelunasan_hutang(sync_status);
 
+-- ============================================================================
+-- INVENTORY
+-- ============================================================================
+
+-- Table: mutasi_stok (Stock Ledger, quantities in satuan_dasar)
+CREATE TABLE IF NOT EXISTS mutasi_stok (
+  id TEXT PRIMARY KEY,
+  barang_id TEXT NOT NULL,
+  tanggal TEXT NOT NULL,
+  jenis TEXT NOT NULL CHECK(jenis IN ('SALDO_AWAL', 'PENJUALAN', 'PEMBELIAN', 'RUSAK_PRODUKSI', 'PENYESUAIAN', 'STOK_OPNAME')),
+  jumlah REAL NOT NULL,
+  stok_sebelum REAL NOT NULL,
+  stok_sesudah REAL NOT NULL,
+  satuan TEXT,
+  referensi_tabel TEXT,
+  referensi_id TEXT,
+  catatan TEXT,
+  dibuat_oleh TEXT,
+  dibuat_pada TIMESTAMPTZ DEFAULT NOW(),
+  sync_status TEXT DEFAULT 'pending' CHECK(sync_status IN ('pending', 'synced', 'conflict')),
+  last_synced_at TIMESTAMPTZ,
+  sync_version INTEGER DEFAULT 1,
+  FOREIGN KEY (barang_id) REFERENCES barang(id) ON DELETE CASCADE,
+  FOREIGN KEY (dibuat_oleh) REFERENCES profil(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_mutasi_stok_barang ON mutasi_stok(barang_id, tanggal);
+CREATE INDEX IF NOT EXISTS idx_mutasi_stok_sync_status ON mutasi_stok(sync_status);
+
+-- Table: stok_opname (Stock Opname Sessions)
+CREATE TABLE IF NOT EXISTS stok_opname (
+  id TEXT PRIMARY KEY,
+  nomor_opname TEXT UNIQUE NOT NULL,
+  tanggal TEXT NOT NULL,
+  status TEXT DEFAULT 'DRAFT' CHECK(status IN ('DRAFT', 'DIPOSTING', 'DIBATALKAN')),
+  kategori_ids TEXT,
+  catatan TEXT,
+  dibuat_oleh TEXT,
+  diposting_pada TIMESTAMPTZ,
+  dibuat_pada TIMESTAMPTZ DEFAULT NOW(),
+  diperbarui_pada TIMESTAMPTZ DEFAULT NOW(),
+  sync_status TEXT DEFAULT 'pending' CHECK(sync_status IN ('pending', 'synced', 'conflict')),
+  last_synced_at TIMESTAMPTZ,
+  sync_version INTEGER DEFAULT 1,
+  FOREIGN KEY (dibuat_oleh) REFERENCES profil(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_stok_opname_sync_status ON stok_opname(sync_status);
+
+-- Table: item_stok_opname (Stock Opname Counts)
+CREATE TABLE IF NOT EXISTS item_stok_opname (
+  id TEXT PRIMARY KEY,
+  stok_opname_id TEXT NOT NULL,
+  barang_id TEXT NOT NULL,
+  stok_sistem REAL NOT NULL,
+  jumlah_hitung REAL,
+  jumlah_input REAL,
+  satuan_input TEXT,
+  faktor_konversi REAL,
+  harga_beli REAL DEFAULT 0,
+  catatan TEXT,
+  dihitung_pada TIMESTAMPTZ,
+  dibuat_pada TIMESTAMPTZ DEFAULT NOW(),
+  diperbarui_pada TIMESTAMPTZ DEFAULT NOW(),
+  sync_status TEXT DEFAULT 'pending' CHECK(sync_status IN ('pending', 'synced', 'conflict')),
+  last_synced_at TIMESTAMPTZ,
+  sync_version INTEGER DEFAULT 1,
+  UNIQUE (stok_opname_id, barang_id),
+  FOREIGN KEY (stok_opname_id) REFERENCES stok_opname(id) ON DELETE CASCADE,
+  FOREIGN KEY (barang_id) REFERENCES barang(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_item_stok_opname_sync_status ON item_stok_opname(sync_status);
+
 -- ============================================================================
 -- PRODUCTION TABLES
 -- ============================================================================
@@ -583,6 +657,8 @@ CREATE TRIGGER update_order_produksi_diperbarui_pada BEFORE UPDATE ON order_prod
 CREATE TRIGGER update_item_produksi_diperbarui_pada BEFORE UPDATE ON item_produksi FOR EACH ROW EXECUTE FUNCTION update_diperbarui_pada();
 CREATE TRIGGER update_item_finishing_diperbarui_pada BEFORE UPDATE ON item_finishing FOR EACH ROW EXECUTE FUNCTION update_diperbarui_pada();
 CREATE TRIGGER update_opsi_finishing_diperbarui_pada BEFORE UPDATE ON opsi_finishing FOR EACH ROW EXECUTE FUNCTION update_diperbarui_pada();
+CREATE TRIGGER update_stok_opname_diperbarui_pada BEFORE UPDATE ON stok_opname FOR EACH ROW EXECUTE FUNCTION update_diperbarui_pada();
+CREATE TRIGGER update_item_stok_opname_diperbarui_pada BEFORE UPDATE ON item_stok_opname FOR EACH ROW EXECUTE FUNCTION update_diperbarui_pada();
 
 -- ============================================================================
 -- NOTES